@@ -0,0 +1,64 @@
+//! Times `delauney_triangulation` against the Bowyer-Watson implementation it replaced, on
+//! random points. Run with `cargo bench --bench delauney`.
+//!
+//! Bowyer-Watson is quadratic, and takes minutes on 100k points, so it is only timed up to
+//! `REFERENCE_LIMIT` points unless `MOD1_BENCH_ALL` is set in the environment.
+
+use mod1::delauney::{bowyer_watson, delauney_triangulation, Triangle, Vec2};
+use mod1::random::Random;
+use std::time::{Duration, Instant};
+
+const SIZES: [usize; 3] = [1_000, 10_000, 100_000];
+const REFERENCE_LIMIT: usize = 10_000;
+const MAP_SIZE: f32 = 10_000.0;
+
+fn random_points(count: usize) -> Vec<Vec2> {
+    let mut random = Random::new(count as u64);
+    (0..count)
+        .map(|_| Vec2::new(random.range(0.0, MAP_SIZE), random.range(0.0, MAP_SIZE)))
+        .collect()
+}
+
+/// Best of `runs` timings of `triangulate`, along with the number of triangles it made.
+fn time<T, F: Fn() -> Vec<T>>(runs: usize, triangulate: F) -> (Duration, usize) {
+    let mut best = Duration::MAX;
+    let mut triangles = 0;
+    for _ in 0..runs {
+        let start = Instant::now();
+        triangles = triangulate().len();
+        best = best.min(start.elapsed());
+    }
+    (best, triangles)
+}
+
+fn main() {
+    let run_all = std::env::var_os("MOD1_BENCH_ALL").is_some();
+    let super_triangle = Triangle::new(
+        Vec2::new(MAP_SIZE / 2.0, MAP_SIZE * 10.0),
+        Vec2::new(-MAP_SIZE * 10.0, -MAP_SIZE * 10.0),
+        Vec2::new(MAP_SIZE * 10.0, -MAP_SIZE * 10.0),
+    );
+
+    for &size in SIZES.iter() {
+        let points = random_points(size);
+
+        let (elapsed, triangles) = time(5, || delauney_triangulation(&points));
+        println!(
+            "sweep hull     {:>7} points: {:>12.3?} ({} triangles)",
+            size, elapsed, triangles
+        );
+
+        if size <= REFERENCE_LIMIT || run_all {
+            let (elapsed, triangles) = time(1, || bowyer_watson(&points, super_triangle));
+            println!(
+                "bowyer-watson  {:>7} points: {:>12.3?} ({} triangles)",
+                size, elapsed, triangles
+            );
+        } else {
+            println!(
+                "bowyer-watson  {:>7} points: skipped, set MOD1_BENCH_ALL to run",
+                size
+            );
+        }
+    }
+}
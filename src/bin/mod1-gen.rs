@@ -0,0 +1,152 @@
+//! Generates terrain from a parametric shape and seeded noise, written as a map file or grid.
+//!
+//! Usage: mod1-gen <output.mod1 | output.asc> [--shape flat|bowl|island|ridge|slope]
+//!                 [--noise none|perlin|simplex] [--fractal fbm|ridged] [--octaves N]
+//!                 [--features N] [--roughness FRACTION] [--terraces N] [--size SIZE]
+//!                 [--height HEIGHT] [--seed N] [--points N] [--resolution CELLS]
+//!
+//! Map files hold control points scattered around a grid with `--points` cells along each side,
+//! and ASCII grids a sample at every corner of a grid with `--resolution` cells along each side.
+
+use mod1::generate::{Generator, GeneratorBuilder, NoiseKind};
+use mod1::map::io::{write_asc, write_mod1};
+use mod1::map::DEFAULT_RESOLUTION;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+/// Cells along each side of the grid control points are scattered around, unless specified
+/// otherwise.
+const DEFAULT_POINTS: usize = 16;
+
+enum Output {
+    MapFile(usize),
+    Grid(usize),
+}
+
+struct Config {
+    generator: Generator,
+    path: PathBuf,
+    output: Output,
+}
+
+impl Config {
+    fn new(mut args: std::env::Args) -> Result<Self, Box<dyn Error>> {
+        args.next(); // Skip executable name
+
+        let path = match args.next() {
+            Some(arg) => PathBuf::from(arg),
+            None => {
+                return Err(
+                    "mod1-gen must be run with at least one argument, the file to write".into(),
+                )
+            }
+        };
+        let is_grid = match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("mod1") => false,
+            Some(extension) if extension.eq_ignore_ascii_case("asc") => true,
+            _ => return Err("Output must be a .mod1 map file or .asc grid".into()),
+        };
+
+        let mut builder = GeneratorBuilder::new();
+        let mut points = DEFAULT_POINTS;
+        let mut resolution = DEFAULT_RESOLUTION;
+
+        let args: Vec<String> = args.collect();
+        let mut options = args.iter();
+        while let Some(option) = options.next() {
+            let value = match options.next() {
+                Some(value) => value,
+                None => return Err("Every option must be followed by a value".into()),
+            };
+            builder = match option.as_str() {
+                "--shape" => builder.shape(value.parse()?),
+                "--noise" if value.eq_ignore_ascii_case("none") => builder.noise(None),
+                "--noise" => builder.noise(Some(value.parse::<NoiseKind>()?)),
+                "--fractal" => builder.fractal(value.parse()?),
+                "--octaves" => match value.parse::<usize>() {
+                    Ok(value) if value > 0 => builder.octaves(value),
+                    _ => return Err("Octaves must be a positive whole number".into()),
+                },
+                "--features" => match value.parse::<f32>() {
+                    Ok(value) if value > 0.0 => builder.features(value),
+                    _ => return Err("Features must be a positive number".into()),
+                },
+                "--roughness" => match value.parse::<f32>() {
+                    Ok(value) if (0.0..=1.0).contains(&value) => builder.roughness(value),
+                    _ => return Err("Roughness must be a number from 0 to 1".into()),
+                },
+                "--terraces" => match value.parse::<usize>() {
+                    Ok(value) if value >= 2 => builder.terraces(Some(value)),
+                    _ => return Err("Terraces must be a whole number of at least 2".into()),
+                },
+                "--size" => match value.parse::<f32>() {
+                    Ok(value) if value > 0.0 => builder.size(value),
+                    _ => return Err("Size must be a positive number".into()),
+                },
+                "--height" => match value.parse::<f32>() {
+                    Ok(value) if value.is_finite() => builder.height(value),
+                    _ => return Err("Height must be a number".into()),
+                },
+                "--seed" => builder.seed(value.parse().map_err(|_| "Seed must be a whole number")?),
+                "--points" => match value.parse::<usize>() {
+                    Ok(value) if value > 0 => {
+                        points = value;
+                        builder
+                    }
+                    _ => return Err("Points must be a positive whole number".into()),
+                },
+                "--resolution" => match value.parse::<usize>() {
+                    Ok(value) if value > 0 => {
+                        resolution = value;
+                        builder
+                    }
+                    _ => return Err("Resolution must be a positive whole number".into()),
+                },
+                _ => {
+                    return Err(
+                        "Options are --shape, --noise, --fractal, --octaves, --features, \
+                         --roughness, --terraces, --size, --height, --seed, --points and \
+                         --resolution"
+                            .into(),
+                    )
+                }
+            };
+        }
+
+        Ok(Self {
+            generator: builder.build(),
+            path,
+            output: if is_grid {
+                Output::Grid(resolution)
+            } else {
+                Output::MapFile(points)
+            },
+        })
+    }
+}
+
+fn run(config: Config) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(&config.path)?);
+    match config.output {
+        Output::MapFile(points) => write_mod1(&mut writer, &config.generator.map_file(points))?,
+        Output::Grid(resolution) => {
+            write_asc(&mut writer, &config.generator.heightfield(resolution))?
+        }
+    }
+    writer.flush()
+}
+
+fn main() {
+    let config = Config::new(std::env::args()).unwrap_or_else(|err| {
+        eprintln!("Problem parsing arguments: {}", err);
+        std::process::exit(1);
+    });
+
+    if let Err(e) = run(config) {
+        eprintln!("Application error: {}", e);
+
+        std::process::exit(1);
+    }
+}
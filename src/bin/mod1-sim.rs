@@ -0,0 +1,398 @@
+//! Runs a scenario without a window, writing the water depth grid to disk after every step.
+//!
+//! Usage: mod1-sim <mod1-file | heightmap | asc-grid | xyz-points> [scenario...] [--steps N]
+//!                 [--dt SECONDS] [--format csv|raw|pgm] [--output DIRECTORY] [--seed N]
+//!                 [--level LEVEL] [--snapshot IMAGE] [--resolution CELLS]
+//!                 [--border-padding FRACTION] [--border-height HEIGHT]
+//!                 [--border-falloff interpolated|linear|smooth] [--normalise SIZE]
+//!                 [--exaggeration FACTOR] [--spacing DISTANCE] [--height-scale HEIGHT]
+//!                 [--export MESH] [--sun AZIMUTH,ALTITUDE] [--ambient FRACTION]
+//!                 [--ramp terrain|grayscale|viridis|RAMP-FILE]
+//!
+//! Heightmaps are PNG or TIFF images, read with `--spacing` between pixels and white at
+//! `--height-scale`. ESRI ASCII grids (`.asc`) are read like heightmaps, and XYZ point clouds
+//! (`.xyz`) like map files. `--export` writes the terrain and the water after the last step as a
+//! `.obj` or `.ply` mesh. Water levels, sinks, depths and volumes are all in the units of the map
+//! file or heightmap.
+
+use mod1::map::io::{self as map_io, Format};
+use mod1::map::{HeightmapOptions, Map, MapFile, Normalisation, Units, DEFAULT_RESOLUTION};
+use mod1::render::{CameraMatrix, Lighting, Ramp, Snapshot};
+use mod1::simulation::{FrameFormat, Report, Scenario, Simulation, SimulationBuilder};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+const DEFAULT_STEPS: usize = 600;
+const DEFAULT_DT: f32 = 1.0 / 60.0;
+const SNAPSHOT_WIDTH: u32 = 1024;
+const SNAPSHOT_HEIGHT: u32 = 768;
+
+struct Config {
+    simulation: Simulation,
+    steps: usize,
+    dt: f32,
+    format: FrameFormat,
+    output: PathBuf,
+    snapshot: Option<PathBuf>,
+    export: Option<PathBuf>,
+    camera: CameraMatrix,
+    units: Units,
+    elevation_range: (f32, f32),
+    lighting: Lighting,
+    ramp: Ramp,
+}
+
+impl Config {
+    fn new(mut args: impl Iterator<Item = String>) -> Result<Self, Box<dyn Error>> {
+        args.next(); // Skip executable name
+
+        let filename = match args.next() {
+            Some(arg) => arg,
+            None => {
+                return Err(
+                    "mod1-sim must be run with at least one argument, a mod1 file, heightmap, \
+                     ASC grid or XYZ point cloud"
+                        .into(),
+                )
+            }
+        };
+
+        // Everything up to the first option describes the scenario
+        let args: Vec<String> = args.collect();
+        let options_start = args
+            .iter()
+            .position(|arg| arg.starts_with("--"))
+            .unwrap_or(args.len());
+        let (scenario, sinks) = Scenario::from_args(&args[..options_start])?;
+
+        let mut steps = DEFAULT_STEPS;
+        let mut dt = DEFAULT_DT;
+        let mut format = FrameFormat::Csv;
+        let mut output = PathBuf::from("frames");
+        let mut seed = 0;
+        let mut water_level = None;
+        let mut snapshot = None;
+        let mut export = None;
+        let mut lighting = Lighting::default();
+        let mut ramp = Ramp::default();
+        // Heightmaps keep one sample per pixel unless a resolution is given
+        let mut resolution = None;
+        let mut heightmap = HeightmapOptions::default();
+        // Border settings given here override those in the map file
+        let mut border_padding = None;
+        let mut border_height = None;
+        let mut border_falloff = None;
+        let mut world_size = None;
+        let mut exaggeration = None;
+
+        let mut options = args[options_start..].iter();
+        while let Some(option) = options.next() {
+            let value = match options.next() {
+                Some(value) => value,
+                None => return Err("Every option must be followed by a value".into()),
+            };
+            match option.as_str() {
+                "--steps" => steps = value.parse().map_err(|_| "Steps must be a whole number")?,
+                "--dt" => {
+                    dt = number(
+                        value,
+                        |dt| dt > 0.0,
+                        "Timestep must be a positive number of seconds",
+                    )?
+                }
+                "--format" => {
+                    format = value
+                        .parse()
+                        .map_err(|_| "Format must be one of csv, raw or pgm")?
+                }
+                "--output" => output = PathBuf::from(value),
+                "--snapshot" => snapshot = Some(PathBuf::from(value)),
+                "--export" => match Path::new(value).extension().and_then(|ext| ext.to_str()) {
+                    Some(ext)
+                        if ext.eq_ignore_ascii_case("obj") || ext.eq_ignore_ascii_case("ply") =>
+                    {
+                        export = Some(PathBuf::from(value))
+                    }
+                    _ => return Err("Exported meshes must be .obj or .ply files".into()),
+                },
+                "--sun" => lighting.sun = value.parse()?,
+                "--ambient" => {
+                    lighting.ambient = number(
+                        value,
+                        |ambient| (0.0..=1.0).contains(&ambient),
+                        "Ambient light must be a number from 0 to 1",
+                    )?
+                }
+                "--ramp" => ramp = value.parse()?,
+                "--seed" => seed = value.parse().map_err(|_| "Seed must be a whole number")?,
+                "--resolution" => match value.parse::<usize>() {
+                    Ok(value) if value > 0 => resolution = Some(value),
+                    _ => return Err("Resolution must be a positive whole number".into()),
+                },
+                "--border-padding" => {
+                    border_padding = Some(number(
+                        value,
+                        |padding| padding >= 0.0,
+                        "Border padding must be a number of at least 0",
+                    )?)
+                }
+                "--border-height" => {
+                    border_height = Some(number(value, |_| true, "Border height must be a number")?)
+                }
+                "--border-falloff" => border_falloff = Some(value.parse()?),
+                "--normalise" => {
+                    world_size = Some(number(
+                        value,
+                        |size| size > 0.0,
+                        "World size must be a positive number",
+                    )?)
+                }
+                "--exaggeration" => {
+                    exaggeration = Some(number(
+                        value,
+                        |exaggeration| exaggeration > 0.0,
+                        "Exaggeration must be a positive number",
+                    )?)
+                }
+                "--spacing" => {
+                    heightmap.spacing = number(
+                        value,
+                        |spacing| spacing > 0.0,
+                        "Spacing must be a positive distance",
+                    )?
+                }
+                "--height-scale" => {
+                    heightmap.height_scale =
+                        number(value, |_| true, "Height scale must be a number")?
+                }
+                "--level" => {
+                    water_level = Some(number(value, |_| true, "Water level must be a number")?)
+                }
+                _ => return Err(
+                    "Options are --steps, --dt, --format, --output, --seed, --level, --snapshot, \
+                     --export, --sun, --ambient, --ramp, --resolution, --border-padding, \
+                     --border-height, --border-falloff, --normalise, --exaggeration, --spacing \
+                     and --height-scale"
+                        .into(),
+                ),
+            }
+        }
+
+        // Grids have no directives of their own, so only the options here place them
+        let normalisation = Normalisation {
+            world_size,
+            exaggeration: exaggeration.unwrap_or(1.0),
+            unit: None,
+        };
+        let map = match Format::of(&filename) {
+            Format::Heightmap => {
+                heightmap.resolution = resolution;
+                heightmap.normalisation = normalisation;
+                Map::from_heightmap(&filename, &heightmap)?
+            }
+            Format::Asc => {
+                Map::from_heightfield(map_io::open_asc(&filename)?, &normalisation, resolution)
+            }
+            source => {
+                let mut map_file = if source == Format::Xyz {
+                    map_io::open_xyz(&filename)?
+                } else {
+                    MapFile::open(&filename)?
+                };
+                let border = &mut map_file.border;
+                border.padding = border_padding.unwrap_or(border.padding);
+                border.height = border_height.unwrap_or(border.height);
+                border.falloff = border_falloff.unwrap_or(border.falloff);
+                let normalisation = &mut map_file.normalisation;
+                normalisation.world_size = world_size.or(normalisation.world_size);
+                normalisation.exaggeration = exaggeration.unwrap_or(normalisation.exaggeration);
+                Map::from_map_file(map_file, resolution.unwrap_or(DEFAULT_RESOLUTION))
+            }
+        };
+        for warning in map.warnings.iter() {
+            eprintln!("Warning: {}", warning);
+        }
+        let units = map.units.clone();
+        let water_level = match water_level {
+            Some(level) => units.to_world_height(level),
+            None => scenario.initial_water_level(map.elevation_max),
+        };
+        let mut sinks: Vec<_> = sinks
+            .into_iter()
+            .map(|sink| units.sink_to_world(sink))
+            .collect();
+        sinks.extend(map.sinks.iter());
+        let elevation_range = map.heightfield.range();
+        let camera = map.camera();
+        let simulation = SimulationBuilder::new(map.heightfield)
+            .scenario(scenario)
+            .water_level(water_level)
+            .sinks(&sinks)
+            .seed(seed)
+            .build();
+
+        Ok(Self {
+            simulation,
+            steps,
+            dt,
+            format,
+            output,
+            snapshot,
+            export,
+            camera,
+            units,
+            elevation_range,
+            lighting,
+            ramp,
+        })
+    }
+}
+
+/// Parse `value` as a finite number accepted by `valid`, as map file settings are, or fail with
+/// `message`.
+fn number(value: &str, valid: fn(f32) -> bool, message: &str) -> Result<f32, Box<dyn Error>> {
+    match value.parse::<f32>() {
+        Ok(number) if number.is_finite() && valid(number) => Ok(number),
+        _ => Err(message.into()),
+    }
+}
+
+fn write_frame(config: &Config, frame: usize, report: &Report) -> io::Result<()> {
+    let filename = format!("frame_{:05}.{}", frame, config.format.extension());
+    let mut writer = BufWriter::new(File::create(config.output.join(filename))?);
+    let depth_scale = config.units.depth_to_map(1.0);
+    config
+        .format
+        .write_scaled(config.simulation.water(), depth_scale, &mut writer)?;
+    writer.flush()?;
+
+    println!(
+        "{},{},{},{},{}",
+        frame,
+        report.time,
+        config.units.volume_to_map(report.volume),
+        report.wet_count,
+        config.units.volume_to_map(report.drained)
+    );
+    Ok(())
+}
+
+/// Render the terrain and water, as the window would show them, to an image at `path`.
+fn save_snapshot(config: &Config, path: &Path) -> io::Result<()> {
+    let heightfield = config.simulation.heightfield();
+    let water = config.simulation.water();
+    let mut snapshot = Snapshot::new(SNAPSHOT_WIDTH, SNAPSHOT_HEIGHT, config.camera);
+    snapshot.set_elevation_range(config.elevation_range.0, config.elevation_range.1);
+    snapshot.set_ramp(config.ramp.clone());
+    snapshot.set_lighting(config.lighting);
+    snapshot.draw(&heightfield.vertices(), &heightfield.indices());
+    snapshot.draw(&water.vertices(heightfield), &water.indices(0));
+    snapshot.save(path).map_err(io::Error::other)
+}
+
+/// Write the terrain and water as a mesh to `path`, in the format its extension names.
+fn export_mesh(config: &Config, path: &Path) -> io::Result<()> {
+    let heightfield = config.simulation.heightfield();
+    let water = Some(config.simulation.water());
+    let mut writer = BufWriter::new(File::create(path)?);
+    let is_ply = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("ply"));
+    if is_ply {
+        map_io::write_ply(&mut writer, heightfield, water)?;
+    } else {
+        map_io::write_obj(&mut writer, heightfield, water)?;
+    }
+    writer.flush()
+}
+
+fn run(mut config: Config) -> io::Result<()> {
+    fs::create_dir_all(&config.output)?;
+
+    println!("frame,time,volume,wet_count,drained");
+    // Frame 0 is the water the scenario starts with, before any step
+    let report = config.simulation.report();
+    write_frame(&config, 0, &report)?;
+    for frame in 1..=config.steps {
+        let report = config.simulation.step(config.dt);
+        write_frame(&config, frame, &report)?;
+    }
+    if let Some(path) = &config.snapshot {
+        save_snapshot(&config, path)?;
+    }
+    if let Some(path) = &config.export {
+        export_mesh(&config, path)?;
+    }
+    Ok(())
+}
+
+fn main() {
+    let config = Config::new(std::env::args()).unwrap_or_else(|err| {
+        eprintln!("Problem parsing arguments: {}", err);
+        std::process::exit(1);
+    });
+
+    if let Err(e) = run(config) {
+        eprintln!("Application error: {}", e);
+
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_must_be_finite() {
+        let positive = |value| number(value, |value| value > 0.0, "positive").ok();
+        assert_eq!(positive("0.5"), Some(0.5));
+        assert_eq!(positive("0"), None);
+        assert_eq!(positive("inf"), None);
+        assert_eq!(positive("NaN"), None);
+        assert_eq!(positive("fast"), None);
+
+        let any = |value| number(value, |_| true, "any").ok();
+        assert_eq!(any("-3"), Some(-3.0));
+        assert_eq!(any("-inf"), None);
+    }
+
+    /// The error given for `options`, which are rejected before any map file is read.
+    fn rejection(options: &[&str]) -> String {
+        let args = ["mod1-sim", "missing.mod1"].iter().chain(options);
+        match Config::new(args.map(|arg| arg.to_string())) {
+            Ok(_) => panic!("{:?} should be rejected", options),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    #[test]
+    fn world_size_and_exaggeration_must_be_finite() {
+        assert_eq!(
+            rejection(&["--normalise", "inf"]),
+            "World size must be a positive number"
+        );
+        assert_eq!(
+            rejection(&["--exaggeration", "NaN"]),
+            "Exaggeration must be a positive number"
+        );
+        assert_eq!(
+            rejection(&["--exaggeration", "-2"]),
+            "Exaggeration must be a positive number"
+        );
+    }
+
+    #[test]
+    fn heightmap_options_must_be_finite() {
+        assert_eq!(
+            rejection(&["--spacing", "inf"]),
+            "Spacing must be a positive distance"
+        );
+        assert_eq!(
+            rejection(&["--height-scale", "-inf"]),
+            "Height scale must be a number"
+        );
+    }
+}
@@ -13,6 +13,8 @@ pub struct Vertex {
     position: (f32, f32, f32),
 }
 
+glium::implement_vertex!(Vertex, position);
+
 fn main() {
     use glium::glutin;
 
@@ -32,8 +34,6 @@ fn main() {
 
     let indices: [u16; 6] = [0, 1, 2, 0, 3, 2];
 
-    glium::implement_vertex!(Vertex, position);
-
     let mut camera_matrix = CameraBuilder::new()
         .zoom(2.0)
         .position(Coord::new(0.5, -0.6, 1.5))
@@ -97,7 +97,7 @@ fn main() {
             let (width, height) = target.get_dimensions();
             let aspect_ratio = height as f32 / width as f32;
 
-            let fov: f32 = 3.141592 / 3.0;
+            let fov: f32 = std::f32::consts::PI / 3.0;
             let zfar = 1024.0;
             let znear = 0.1;
 
@@ -134,8 +134,8 @@ fn main() {
             std::time::Instant::now() + std::time::Duration::from_nanos(16_666_667);
         *control_flow = glutin::event_loop::ControlFlow::WaitUntil(next_frame_time);
 
-        match ev {
-            glutin::event::Event::WindowEvent { event, .. } => match event {
+        if let glutin::event::Event::WindowEvent { event, .. } = ev {
+            match event {
                 glutin::event::WindowEvent::KeyboardInput {
                     device_id: _,
                     input,
@@ -153,11 +153,9 @@ fn main() {
                 }
                 glutin::event::WindowEvent::CloseRequested => {
                     *control_flow = glutin::event_loop::ControlFlow::Exit;
-                    return;
                 }
-                _ => return,
-            },
-            _ => (),
+                _ => (),
+            }
         }
     });
 }
@@ -23,12 +23,7 @@ fn main() {
 
     let vertex_buffer = glium::VertexBuffer::new(&display, &map.vertices).unwrap();
     println!("{:?}", map.indices);
-    let indices = glium::IndexBuffer::new(
-        &display,
-        glium::index::PrimitiveType::TrianglesList,
-        &map.indices,
-    )
-    .unwrap();
+    let indices = map.indices.index_buffer(&display).unwrap();
 
     let mut camera_matrix = CameraBuilder::new()
         .zoom(2.0)
@@ -89,7 +84,7 @@ fn main() {
             let (width, height) = target.get_dimensions();
             let aspect_ratio = height as f32 / width as f32;
 
-            let fov: f32 = 3.141592 / 3.0;
+            let fov: f32 = std::f32::consts::PI / 3.0;
             let zfar = 1024.0;
             let znear = 0.1;
 
@@ -126,8 +121,8 @@ fn main() {
             std::time::Instant::now() + std::time::Duration::from_nanos(16_666_667);
         *control_flow = glutin::event_loop::ControlFlow::WaitUntil(next_frame_time);
 
-        match ev {
-            glutin::event::Event::WindowEvent { event, .. } => match event {
+        if let glutin::event::Event::WindowEvent { event, .. } = ev {
+            match event {
                 glutin::event::WindowEvent::KeyboardInput {
                     device_id: _,
                     input,
@@ -145,11 +140,9 @@ fn main() {
                 }
                 glutin::event::WindowEvent::CloseRequested => {
                     *control_flow = glutin::event_loop::ControlFlow::Exit;
-                    return;
                 }
-                _ => return,
-            },
-            _ => (),
+                _ => (),
+            }
         }
     });
 }
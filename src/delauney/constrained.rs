@@ -0,0 +1,559 @@
+//! Delaunay triangulation forced to contain given segments, such as ridge lines, river banks or
+//! walls, as edges.
+//!
+//! Each segment is added to an ordinary triangulation by flipping the edges it crosses until it
+//! appears as an edge itself (Sloan, "A fast algorithm for generating constrained Delaunay
+//! triangulations", 1993). The triangles around it are then flipped back towards the Delaunay
+//! condition, without ever flipping an edge that lies on a segment. The result is as close to a
+//! Delaunay triangulation as the segments allow: no edge can be flipped to improve it.
+
+use super::predicates::{incircle, orient2d, segments_cross};
+use super::sweep::{next, prev, Triangulation, EMPTY};
+use super::Vec2;
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConstraintError {
+    /// A segment ends at a point that was not given, or that is not finite.
+    OutOfRange { segment: [usize; 2] },
+    /// A segment crosses the edge between two points of an earlier segment.
+    Crossing {
+        segment: [usize; 2],
+        edge: [usize; 2],
+    },
+    /// Segments given among points that all lie on one line, leaving no triangles to hold them.
+    Collinear,
+}
+
+impl fmt::Display for ConstraintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConstraintError::OutOfRange { segment } => write!(
+                f,
+                "segment from point {} to {} refers to a missing or unplaced point",
+                segment[0], segment[1]
+            ),
+            ConstraintError::Crossing { segment, edge } => write!(
+                f,
+                "segment from point {} to {} crosses the segment from point {} to {}",
+                segment[0], segment[1], edge[0], edge[1]
+            ),
+            ConstraintError::Collinear => {
+                write!(
+                    f,
+                    "segments cannot be placed among points that all lie on one line"
+                )
+            }
+        }
+    }
+}
+
+impl Error for ConstraintError {}
+
+/// A triangulation containing every one of a list of segments as edges.
+#[derive(Clone, Debug, Default)]
+pub struct ConstrainedTriangulation {
+    pub triangulation: Triangulation,
+    /// Whether each half-edge of `triangulation` lies on a segment.
+    pub constrained: Vec<bool>,
+}
+
+impl ConstrainedTriangulation {
+    /// Triangulate `points` so that each segment, given as a pair of indices into `points`, is
+    /// made of triangle edges. A segment passing through other points is split at them.
+    pub fn new(points: &[Vec2], segments: &[[usize; 2]]) -> Result<Self, ConstraintError> {
+        if let Some(&segment) = segments.iter().find(|segment| {
+            segment.iter().any(|&i| {
+                points
+                    .get(i)
+                    .map_or(true, |point| !point.x.is_finite() || !point.y.is_finite())
+            })
+        }) {
+            return Err(ConstraintError::OutOfRange { segment });
+        }
+
+        let triangulation = Triangulation::new(points);
+        if triangulation.is_empty() {
+            return if segments.is_empty() {
+                Ok(Self::default())
+            } else {
+                Err(ConstraintError::Collinear)
+            };
+        }
+        let mut builder = Builder::new(points, triangulation);
+        for &segment in segments.iter() {
+            let a = builder.representative(segment[0]);
+            let b = builder.representative(segment[1]);
+            builder.insert(a, b, segment)?;
+        }
+        Ok(builder.finish())
+    }
+
+    pub fn is_constrained(&self, edge: usize) -> bool {
+        self.constrained[edge]
+    }
+}
+
+/// Triangulation being edited, along with an outgoing half-edge of every point to find edges by.
+struct Builder {
+    coords: Vec<(f64, f64)>,
+    triangulation: Triangulation,
+    constrained: Vec<bool>,
+    // Some half-edge starting at each point, or `EMPTY` for points left out as repeats
+    vertex_edge: Vec<usize>,
+    // Point kept in the triangulation in place of each point, by the bits of its position
+    kept: HashMap<(u64, u64), usize>,
+}
+
+impl Builder {
+    fn new(points: &[Vec2], triangulation: Triangulation) -> Self {
+        let coords: Vec<(f64, f64)> = points.iter().map(<(f64, f64)>::from).collect();
+        let mut vertex_edge = vec![EMPTY; points.len()];
+        for (e, &point) in triangulation.triangles.iter().enumerate() {
+            vertex_edge[point] = e;
+        }
+        let kept = (0..points.len())
+            .filter(|&i| vertex_edge[i] != EMPTY)
+            .map(|i| ((coords[i].0.to_bits(), coords[i].1.to_bits()), i))
+            .collect();
+        Self {
+            coords,
+            constrained: vec![false; triangulation.triangles.len()],
+            triangulation,
+            vertex_edge,
+            kept,
+        }
+    }
+
+    fn finish(self) -> ConstrainedTriangulation {
+        ConstrainedTriangulation {
+            triangulation: self.triangulation,
+            constrained: self.constrained,
+        }
+    }
+
+    /// The point standing for point `i` in the triangulation, which is `i` unless it repeats
+    /// another point.
+    fn representative(&self, i: usize) -> usize {
+        let (x, y) = self.coords[i];
+        self.kept[&(x.to_bits(), y.to_bits())]
+    }
+
+    fn orient(&self, a: usize, b: usize, c: usize) -> f64 {
+        orient2d(self.coords[a], self.coords[b], self.coords[c])
+    }
+
+    fn point(&self, e: usize) -> usize {
+        self.triangulation.triangles[e]
+    }
+
+    fn twin(&self, e: usize) -> usize {
+        self.triangulation.halfedges[e]
+    }
+
+    /// Half-edges starting at point `v`, going round it counter-clockwise where possible.
+    fn edges_around(&self, v: usize) -> Vec<usize> {
+        let start = self.vertex_edge[v];
+        let mut edges = vec![start];
+        let mut e = start;
+        loop {
+            e = self.twin(prev(e));
+            if e == start {
+                return edges;
+            }
+            if e == EMPTY {
+                break;
+            }
+            edges.push(e);
+        }
+        // Reached the hull, so go round the other way from the start as well
+        let mut e = start;
+        loop {
+            let twin = self.twin(e);
+            if twin == EMPTY {
+                return edges;
+            }
+            e = next(twin);
+            edges.push(e);
+        }
+    }
+
+    /// A half-edge between `u` and `v`, if they are joined. It runs from `u` to `v` unless the
+    /// edge is on the hull, and only has a half-edge in the other direction.
+    fn find_edge(&self, u: usize, v: usize) -> Option<usize> {
+        let around = self.edges_around(u);
+        let forward = around.iter().find(|&&e| self.point(next(e)) == v);
+        let backward = || {
+            around
+                .iter()
+                .find(|&&e| self.point(prev(e)) == v)
+                .map(|&e| prev(e))
+        };
+        forward.copied().or_else(backward)
+    }
+
+    fn mark(&mut self, e: usize) {
+        self.constrained[e] = true;
+        let twin = self.twin(e);
+        if twin != EMPTY {
+            self.constrained[twin] = true;
+        }
+    }
+
+    /// Make the segment from `a` to `b` an edge, or a chain of edges through points lying on it.
+    fn insert(&mut self, a: usize, b: usize, segment: [usize; 2]) -> Result<(), ConstraintError> {
+        if a == b {
+            return Ok(());
+        }
+        if let Some(e) = self.find_edge(a, b) {
+            self.mark(e);
+            return Ok(());
+        }
+
+        // Walk from `a` to `b` through the triangles the segment crosses, noting the edges
+        // crossed on the way. Each crossed edge runs from the right of the segment to its left
+        let mut crossed = VecDeque::new();
+        let mut h = EMPTY;
+        for e in self.edges_around(a) {
+            let (p, q) = (self.point(next(e)), self.point(prev(e)));
+            for &point in [p, q].iter() {
+                if self.orient(a, b, point) == 0.0 && self.ahead(a, b, point) {
+                    self.insert(a, point, segment)?;
+                    return self.insert(point, b, segment);
+                }
+            }
+            if self.orient(a, b, p) < 0.0 && self.orient(a, b, q) > 0.0 {
+                h = next(e);
+                break;
+            }
+        }
+        // Both ends are inside the convex hull, so the segment has to leave `a` through a triangle
+        debug_assert!(h != EMPTY);
+        loop {
+            let (u, v) = (self.point(h), self.point(next(h)));
+            if self.constrained[h] {
+                return Err(ConstraintError::Crossing {
+                    segment,
+                    edge: [u, v],
+                });
+            }
+            crossed.push_back([u, v]);
+
+            let t = self.twin(h);
+            let r = self.point(prev(t));
+            if r == b {
+                break;
+            }
+            let side = self.orient(a, b, r);
+            if side == 0.0 {
+                self.insert(a, r, segment)?;
+                return self.insert(r, b, segment);
+            }
+            h = if side < 0.0 { prev(t) } else { next(t) };
+        }
+
+        // Flip crossed edges until none are left. An edge that cannot be flipped yet, because its
+        // two triangles do not form a convex quadrilateral, is tried again later
+        let mut touched = Vec::new();
+        while let Some([u, v]) = crossed.pop_front() {
+            let e = self.find_edge(u, v).unwrap();
+            let (w, z) = (self.point(prev(e)), self.point(prev(self.twin(e))));
+            if !segments_cross(
+                self.coords[w],
+                self.coords[z],
+                self.coords[u],
+                self.coords[v],
+            ) {
+                crossed.push_back([u, v]);
+                continue;
+            }
+            self.flip(e);
+            touched.extend_from_slice(&[[w, u], [u, z], [z, v], [v, w]]);
+            if segments_cross(
+                self.coords[a],
+                self.coords[b],
+                self.coords[w],
+                self.coords[z],
+            ) {
+                crossed.push_back([w, z]);
+            } else {
+                touched.push([w, z]);
+            }
+        }
+        let e = self.find_edge(a, b).unwrap();
+        self.mark(e);
+
+        self.restore_delaunay(touched);
+        Ok(())
+    }
+
+    /// Whether `point`, on the line through `a` and `b`, lies beyond `a` in the direction of `b`.
+    fn ahead(&self, a: usize, b: usize, point: usize) -> bool {
+        let (a, b, p) = (self.coords[a], self.coords[b], self.coords[point]);
+        (b.0 - a.0) * (p.0 - a.0) + (b.1 - a.1) * (p.1 - a.1) > 0.0
+    }
+
+    /// Flip edges starting with `edges` until each one that is not on a segment satisfies the
+    /// Delaunay condition.
+    fn restore_delaunay(&mut self, mut edges: Vec<[usize; 2]>) {
+        while let Some([u, v]) = edges.pop() {
+            let e = match self.find_edge(u, v) {
+                Some(e) => e,
+                None => continue,
+            };
+            let twin = self.twin(e);
+            if twin == EMPTY || self.constrained[e] {
+                continue;
+            }
+            let (w, z) = (self.point(prev(e)), self.point(prev(twin)));
+            let coords = &self.coords;
+            if incircle(coords[u], coords[v], coords[w], coords[z]) <= 0.0 {
+                continue;
+            }
+            self.flip(e);
+            edges.extend_from_slice(&[[w, u], [u, z], [z, v], [v, w]]);
+        }
+    }
+
+    /// Replace the edge `e` between two triangles by the other diagonal of the quadrilateral they
+    /// make up. Half-edges `e` and its twin become the new diagonal.
+    ///
+    /// ```text
+    ///          w                    w
+    ///         /|\                  / \
+    ///        / | \                /   \
+    ///       /  |  \              /     \
+    ///     u\  e|   /v   flip   u\------/v
+    ///       \  |  /      =>      \     /
+    ///        \ | /                \   /
+    ///         \|/                  \ /
+    ///          z                    z
+    /// ```
+    fn flip(&mut self, e: usize) {
+        let t = self.twin(e);
+        let (e1, e2, t1, t2) = (next(e), prev(e), next(t), prev(t));
+        let (u, v, w, z) = (
+            self.point(e),
+            self.point(e1),
+            self.point(e2),
+            self.point(t2),
+        );
+        let outer = [
+            (self.twin(e2), self.constrained[e2]),
+            (self.twin(t1), self.constrained[t1]),
+            (self.twin(t2), self.constrained[t2]),
+            (self.twin(e1), self.constrained[e1]),
+        ];
+
+        // The triangles become (w, u, z) and (z, v, w)
+        let triangles = &mut self.triangulation.triangles;
+        triangles[e] = w;
+        triangles[e1] = u;
+        triangles[e2] = z;
+        triangles[t] = z;
+        triangles[t1] = v;
+        triangles[t2] = w;
+        for (&edge, &(twin, constrained)) in [e, e1, t, t1].iter().zip(outer.iter()) {
+            self.link(edge, twin);
+            self.constrained[edge] = constrained;
+        }
+        self.link(e2, t2);
+        self.constrained[e2] = false;
+        self.constrained[t2] = false;
+
+        self.vertex_edge[w] = e;
+        self.vertex_edge[u] = e1;
+        self.vertex_edge[z] = t;
+        self.vertex_edge[v] = t1;
+    }
+
+    fn link(&mut self, a: usize, b: usize) {
+        self.triangulation.halfedges[a] = b;
+        if b != EMPTY {
+            self.triangulation.halfedges[b] = a;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+
+    fn grid(columns: usize, rows: usize) -> Vec<Vec2> {
+        let mut points = Vec::new();
+        for row in 0..rows {
+            for column in 0..columns {
+                points.push(Vec2::new(column as f32, row as f32));
+            }
+        }
+        points
+    }
+
+    /// Half-edges agree with their twins, triangles are counter-clockwise, every segment is made
+    /// of constrained edges, and no edge off the segments could be flipped to improve the
+    /// triangulation.
+    fn assert_constrained_delaunay(points: &[Vec2], result: &ConstrainedTriangulation) {
+        let coords: Vec<(f64, f64)> = points.iter().map(<(f64, f64)>::from).collect();
+        let triangles = &result.triangulation.triangles;
+        let halfedges = &result.triangulation.halfedges;
+        for triangle in triangles.chunks_exact(3) {
+            let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+            assert!(orient2d(coords[a], coords[b], coords[c]) > 0.0);
+        }
+        for (e, &twin) in halfedges.iter().enumerate() {
+            if twin == EMPTY {
+                assert!(result.triangulation.hull.contains(&triangles[e]));
+                continue;
+            }
+            assert_eq!(halfedges[twin], e);
+            assert_eq!(triangles[e], triangles[next(twin)]);
+            assert_eq!(result.is_constrained(e), result.is_constrained(twin));
+            if !result.is_constrained(e) {
+                let (u, v, w) = (triangles[e], triangles[next(e)], triangles[prev(e)]);
+                let z = triangles[prev(twin)];
+                assert!(incircle(coords[u], coords[v], coords[w], coords[z]) <= 0.0);
+            }
+        }
+    }
+
+    /// Whether the constrained edges join up into a path from `a` to `b` along their segment.
+    fn has_segment(points: &[Vec2], result: &ConstrainedTriangulation, a: usize, b: usize) -> bool {
+        let triangles = &result.triangulation.triangles;
+        let (start, end) = (points[a], points[b]);
+        let mut at = a;
+        while points[at] != end {
+            let step = (0..triangles.len()).find(|&e| {
+                let v = triangles[next(e)];
+                triangles[e] == at
+                    && result.is_constrained(e)
+                    && orient2d((&start).into(), (&end).into(), (&points[v]).into()) == 0.0
+                    && Vec2::distance(&points[v], &end) < Vec2::distance(&points[at], &end)
+            });
+            match step {
+                Some(e) => at = triangles[next(e)],
+                None => return false,
+            }
+        }
+        true
+    }
+
+    #[test]
+    fn segment_across_a_grid_becomes_an_edge() {
+        let mut points = grid(6, 6);
+        // Two points off the grid, so the segment between them crosses many grid edges
+        points.push(Vec2::new(0.5, 0.2));
+        points.push(Vec2::new(4.5, 4.9));
+        let (a, b) = (points.len() - 2, points.len() - 1);
+
+        let unconstrained = Triangulation::new(&points);
+        let result = ConstrainedTriangulation::new(&points, &[[a, b]]).unwrap();
+        assert_constrained_delaunay(&points, &result);
+        assert!(has_segment(&points, &result, a, b));
+        assert_eq!(result.triangulation.len(), unconstrained.len());
+        assert_eq!(result.constrained.iter().filter(|&&c| c).count(), 2);
+    }
+
+    #[test]
+    fn segments_are_split_at_points_on_them() {
+        let points = grid(5, 5);
+        // Both diagonals, meeting at the middle point, and a segment ending on one of them
+        let segments = [[0, 24], [20, 4], [2, 14]];
+        let result = ConstrainedTriangulation::new(&points, &segments).unwrap();
+        assert_constrained_delaunay(&points, &result);
+        for &[a, b] in segments.iter() {
+            assert!(has_segment(&points, &result, a, b));
+        }
+        assert_eq!(
+            result.constrained.iter().filter(|&&c| c).count(),
+            2 * (4 + 4 + 2)
+        );
+    }
+
+    #[test]
+    fn crossing_segments_are_rejected() {
+        let points = vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(10.0, 0.0),
+            Vec2::new(10.0, 10.0),
+            Vec2::new(0.0, 10.0),
+            Vec2::new(1.0, 5.0),
+            Vec2::new(9.0, 5.5),
+        ];
+        let error = ConstrainedTriangulation::new(&points, &[[0, 2], [4, 5]]).unwrap_err();
+        assert_eq!(
+            error,
+            ConstraintError::Crossing {
+                segment: [4, 5],
+                edge: [0, 2]
+            }
+        );
+        // Edges of the hull, in both directions, are fine
+        let result = ConstrainedTriangulation::new(&points, &[[0, 1], [2, 1]]).unwrap();
+        assert_eq!(result.constrained.iter().filter(|&&c| c).count(), 2);
+        assert_eq!(
+            ConstrainedTriangulation::new(&points, &[[0, 6]]).unwrap_err(),
+            ConstraintError::OutOfRange { segment: [0, 6] }
+        );
+        let mut unplaced = points.clone();
+        unplaced.push(Vec2::new(f32::NAN, 1.0));
+        assert_eq!(
+            ConstrainedTriangulation::new(&unplaced, &[[0, 1], [0, 6]]).unwrap_err(),
+            ConstraintError::OutOfRange { segment: [0, 6] }
+        );
+        let line = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(2.0, 2.0),
+        ];
+        assert_eq!(
+            ConstrainedTriangulation::new(&line, &[[0, 2]]).unwrap_err(),
+            ConstraintError::Collinear
+        );
+        assert!(ConstrainedTriangulation::new(&line, &[]).is_ok());
+    }
+
+    #[test]
+    fn repeated_points_stand_for_each_other() {
+        let mut points = grid(4, 4);
+        points.push(Vec2::new(3.0, 0.0));
+        let result = ConstrainedTriangulation::new(&points, &[[16, 12]]).unwrap();
+        assert_constrained_delaunay(&points, &result);
+        assert!(has_segment(&points, &result, 3, 12));
+    }
+
+    #[test]
+    fn random_segments_are_all_kept() {
+        let mut random = Random::new(3);
+        for _ in 0..10 {
+            let points: Vec<Vec2> = (0..200)
+                .map(|_| Vec2::new(random.range(0.0, 100.0), random.range(0.0, 100.0)))
+                .collect();
+            let coords: Vec<(f64, f64)> = points.iter().map(<(f64, f64)>::from).collect();
+
+            // Segments between random points that do not cross each other
+            let mut segments: Vec<[usize; 2]> = Vec::new();
+            while segments.len() < 20 {
+                let (a, b) = (random.below(points.len()), random.below(points.len()));
+                let crosses = segments
+                    .iter()
+                    .any(|&[c, d]| segments_cross(coords[a], coords[b], coords[c], coords[d]));
+                if a != b && !crosses {
+                    segments.push([a, b]);
+                }
+            }
+
+            let result = ConstrainedTriangulation::new(&points, &segments).unwrap();
+            assert_constrained_delaunay(&points, &result);
+            assert_eq!(
+                result.triangulation.len(),
+                Triangulation::new(&points).len()
+            );
+            for &[a, b] in segments.iter() {
+                assert!(has_segment(&points, &result, a, b));
+            }
+        }
+    }
+}
@@ -1,14 +1,28 @@
+pub mod constrained;
+pub mod predicates;
+pub mod sweep;
+pub mod voronoi;
+
+pub use constrained::{ConstrainedTriangulation, ConstraintError};
+pub use sweep::Triangulation;
+pub use voronoi::{Cell, Voronoi};
+
+use predicates::{incircle, orient2d};
 use std::fmt;
 
-fn circumcenter(a: &Vec2, b: &Vec2, c: &Vec2) -> Vec2 {
+/// Centre of the circle through `a`, `b` and `c`, or `None` when they lie on one line.
+fn circumcenter(a: &Vec2, b: &Vec2, c: &Vec2) -> Option<Vec2> {
+    if orient2d(a.into(), b.into(), c.into()) == 0.0 {
+        return None;
+    }
     let ad = a.x.powi(2) + a.y.powi(2);
     let bd = b.x.powi(2) + b.y.powi(2);
     let cd = c.x.powi(2) + c.y.powi(2);
     let dis = 2.0 * (a.x * (b.y - c.y) + b.x * (c.y - a.y) + c.x * (a.y - b.y));
-    Vec2::new(
+    Some(Vec2::new(
         1.0 / dis * (ad * (b.y - c.y) + bd * (c.y - a.y) + cd * (a.y - b.y)),
         1.0 / dis * (ad * (c.x - b.x) + bd * (a.x - c.x) + cd * (b.x - a.x)),
-    )
+    ))
 }
 
 #[derive(Copy, Clone, PartialEq, Debug)]
@@ -19,6 +33,12 @@ pub struct Vec2 {
 
 impl Eq for Vec2 {}
 
+impl From<&Vec2> for (f64, f64) {
+    fn from(point: &Vec2) -> Self {
+        (point.x as f64, point.y as f64)
+    }
+}
+
 impl Vec2 {
     pub fn new(x: f32, y: f32) -> Self {
         Self { x, y }
@@ -29,7 +49,7 @@ impl Vec2 {
     }
 
     pub fn distance_to(&self, b: &Self) -> f32 {
-        Vec2::distance(&self, b)
+        Vec2::distance(self, b)
     }
 }
 
@@ -59,7 +79,8 @@ pub struct Triangle {
     pub b: Vec2,
     pub c: Vec2,
     pub edges: [Edge; 3],
-    pub circumcenter: Vec2,
+    /// `None` for degenerate triangles, whose corners lie on one line.
+    pub circumcenter: Option<Vec2>,
 }
 
 impl Triangle {
@@ -74,8 +95,15 @@ impl Triangle {
         }
     }
 
+    /// Whether `point` lies strictly inside the circle through the corners, decided exactly.
+    /// Degenerate triangles have no circumcircle and contain nothing.
     pub fn contains_point_in_circumcircle(&self, point: &Vec2) -> bool {
-        self.a.distance_to(&self.circumcenter) > point.distance_to(&self.circumcenter)
+        let (a, b, c) = ((&self.a).into(), (&self.b).into(), (&self.c).into());
+        let orientation = orient2d(a, b, c);
+        if orientation == 0.0 {
+            return false;
+        }
+        incircle(a, b, c, point.into()) * orientation.signum() > 0.0
     }
 
     pub fn has_vertex(&self, point: &Vec2) -> bool {
@@ -96,7 +124,7 @@ impl fmt::Display for Triangle {
 fn edge_is_not_shared_by_other_triangles(
     edge: Edge,
     current: &Triangle,
-    triangles: &Vec<Triangle>,
+    triangles: &[Triangle],
 ) -> bool {
     for triangle in triangles {
         if triangle == current {
@@ -111,11 +139,35 @@ fn edge_is_not_shared_by_other_triangles(
     true
 }
 
-// Using Bowyer-Watson algorithm (https://en.wikipedia.org/wiki/Bowyer%E2%80%93Watson_algorithm)
-pub fn delauney_triangulation(points: &Vec<Vec2>, super_triangle: Triangle) -> Vec<Triangle> {
-    let mut triangulation = Vec::new();
+/// Delaunay triangulation of `points`, covering their convex hull, as indices into `points` with
+/// every triangle in counter-clockwise order. Repeated points are left out.
+pub fn delauney_triangulation(points: &[Vec2]) -> Vec<[u32; 3]> {
+    triangle_indices(&Triangulation::new(points))
+}
 
-    triangulation.push(super_triangle);
+/// Triangulation of `points` as for `delauney_triangulation`, but with every segment, given as a
+/// pair of indices into `points`, made of triangle edges.
+pub fn constrained_delauney_triangulation(
+    points: &[Vec2],
+    segments: &[[usize; 2]],
+) -> Result<Vec<[u32; 3]>, ConstraintError> {
+    let constrained = ConstrainedTriangulation::new(points, segments)?;
+    Ok(triangle_indices(&constrained.triangulation))
+}
+
+fn triangle_indices(triangulation: &Triangulation) -> Vec<[u32; 3]> {
+    triangulation
+        .triangles
+        .chunks_exact(3)
+        .map(|triangle| [triangle[0] as u32, triangle[1] as u32, triangle[2] as u32])
+        .collect()
+}
+
+// Using Bowyer-Watson algorithm (https://en.wikipedia.org/wiki/Bowyer%E2%80%93Watson_algorithm)
+// This is quadratic in the number of points, and only kept to compare `delauney_triangulation`
+// against.
+pub fn bowyer_watson(points: &[Vec2], super_triangle: Triangle) -> Vec<Triangle> {
+    let mut triangulation = vec![super_triangle];
 
     for point in points.iter() {
         let mut bad_triangles = Vec::new();
@@ -138,10 +190,7 @@ pub fn delauney_triangulation(points: &Vec<Vec2>, super_triangle: Triangle) -> V
         }
 
         // Remove bad triangles
-        triangulation = triangulation
-            .into_iter()
-            .filter(|&triangle| bad_triangles.contains(&triangle) == false)
-            .collect();
+        triangulation.retain(|triangle| !bad_triangles.contains(triangle));
 
         // Re-triangulate the polygonal hole
         for edge in polygon.into_iter() {
@@ -185,18 +234,10 @@ mod tests {
             Vec2::new(100.0, 0.0),
             Vec2::new(0.0, 100.0),
         ];
-        let triangulation = delauney_triangulation(
-            &vertices,
-            Triangle::new(
-                Vec2::new(50.0, 600.0),
-                Vec2::new(-200.0, -200.0),
-                Vec2::new(400.0, -200.0),
-            ),
-        );
-        for triangle in triangulation.iter() {
-            println!("{}", triangle);
-        }
+        let triangulation = delauney_triangulation(&vertices);
         assert_eq!(triangulation.len(), 4);
+        // The centre point is a corner of every triangle
+        assert!(triangulation.iter().all(|triangle| triangle.contains(&0)));
     }
 
     #[test]
@@ -215,16 +256,56 @@ mod tests {
             Vec2::new(200.0, 0.0),
             Vec2::new(200.0, 200.0),
         ];
-        let triangulation = delauney_triangulation(
-            &vertices,
-            Triangle::new(
-                Vec2::new(100.0, 600.0),
-                Vec2::new(-200.0, -200.0),
-                Vec2::new(400.0, -200.0),
-            ),
+        let triangulation = delauney_triangulation(&vertices);
+        // 12 points, 4 of them on the hull
+        assert_eq!(triangulation.len(), 2 * 12 - 4 - 2);
+    }
+
+    #[test]
+    fn circumcircle_test_is_exact() {
+        let triangle = Triangle::new(
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 0.0),
+            Vec2::new(1.0, 1.0),
         );
-        for triangle in triangulation.iter() {
-            println!("{}", triangle);
+        assert!(triangle.circumcenter.is_some());
+        // The fourth corner of the square lies on the circle, not inside it
+        assert!(!triangle.contains_point_in_circumcircle(&Vec2::new(0.0, 1.0)));
+        assert!(triangle.contains_point_in_circumcircle(&Vec2::new(0.1, 0.9)));
+
+        let flat = Triangle::new(
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(3.0, 3.0),
+        );
+        assert_eq!(flat.circumcenter, None);
+        assert!(!flat.contains_point_in_circumcircle(&Vec2::new(1.0, 0.0)));
+    }
+
+    #[test]
+    fn matches_bowyer_watson() {
+        let vertices = vec![
+            Vec2::new(3.0, 1.0),
+            Vec2::new(17.0, 4.0),
+            Vec2::new(9.0, 12.0),
+            Vec2::new(1.0, 15.0),
+            Vec2::new(14.0, 18.0),
+            Vec2::new(8.0, 5.0),
+        ];
+        let super_triangle = Triangle::new(
+            Vec2::new(10.0, 1000.0),
+            Vec2::new(-1000.0, -1000.0),
+            Vec2::new(1000.0, -1000.0),
+        );
+        let fast = delauney_triangulation(&vertices);
+        let slow = bowyer_watson(&vertices, super_triangle);
+
+        assert_eq!(fast.len(), slow.len());
+        for triangle in fast.iter() {
+            let [a, b, c] = triangle.map(|i| vertices[i as usize]);
+            assert!(slow.iter().any(|other| {
+                other.has_vertex(&a) && other.has_vertex(&b) && other.has_vertex(&c)
+            }));
         }
     }
 }
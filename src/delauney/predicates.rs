@@ -0,0 +1,296 @@
+//! Exact geometric predicates, after Shewchuk's "Adaptive Precision Floating-Point Arithmetic
+//! and Fast Robust Geometric Predicates" (1997).
+//!
+//! Each predicate is first evaluated with ordinary floating point arithmetic, along with a bound
+//! on its rounding error. Only when the result is too close to zero for its sign to be trusted is
+//! it evaluated again exactly, using expansions: sums of non-overlapping doubles that represent a
+//! number without any rounding. The sign of the result is therefore always correct, even for
+//! points that are exactly collinear or cocircular.
+
+type Point = (f64, f64);
+
+/// Half the distance between 1.0 and the next double, bounding the relative rounding error of a
+/// single operation.
+const EPSILON: f64 = f64::EPSILON / 2.0;
+const ORIENT_ERROR_BOUND: f64 = (3.0 + 16.0 * EPSILON) * EPSILON;
+const INCIRCLE_ERROR_BOUND: f64 = (10.0 + 96.0 * EPSILON) * EPSILON;
+
+/// Positive when (`a`, `b`, `c`) run counter-clockwise, negative when they run clockwise, and
+/// exactly zero when they lie on one line. The magnitude approximates twice the area of the
+/// triangle.
+pub fn orient2d(a: Point, b: Point, c: Point) -> f64 {
+    let left = (a.0 - c.0) * (b.1 - c.1);
+    let right = (a.1 - c.1) * (b.0 - c.0);
+    let det = left - right;
+
+    // When both products have different signs, no cancellation can flip the sign of `det`
+    let sum = if left > 0.0 {
+        if right <= 0.0 {
+            return det;
+        }
+        left + right
+    } else if left < 0.0 {
+        if right >= 0.0 {
+            return det;
+        }
+        -left - right
+    } else {
+        return det;
+    };
+
+    let bound = ORIENT_ERROR_BOUND * sum;
+    if det >= bound || -det >= bound {
+        return det;
+    }
+    orient2d_exact(a, b, c)
+}
+
+/// Positive when `d` lies inside the circle through the counter-clockwise triangle (`a`, `b`,
+/// `c`), negative when outside, and exactly zero when all four points lie on one circle. The
+/// sign is reversed for clockwise triangles.
+pub fn incircle(a: Point, b: Point, c: Point, d: Point) -> f64 {
+    let (adx, ady) = (a.0 - d.0, a.1 - d.1);
+    let (bdx, bdy) = (b.0 - d.0, b.1 - d.1);
+    let (cdx, cdy) = (c.0 - d.0, c.1 - d.1);
+
+    let bdxcdy = bdx * cdy;
+    let cdxbdy = cdx * bdy;
+    let alift = adx * adx + ady * ady;
+    let cdxady = cdx * ady;
+    let adxcdy = adx * cdy;
+    let blift = bdx * bdx + bdy * bdy;
+    let adxbdy = adx * bdy;
+    let bdxady = bdx * ady;
+    let clift = cdx * cdx + cdy * cdy;
+
+    let det = alift * (bdxcdy - cdxbdy) + blift * (cdxady - adxcdy) + clift * (adxbdy - bdxady);
+    let permanent = (bdxcdy.abs() + cdxbdy.abs()) * alift
+        + (cdxady.abs() + adxcdy.abs()) * blift
+        + (adxbdy.abs() + bdxady.abs()) * clift;
+
+    let bound = INCIRCLE_ERROR_BOUND * permanent;
+    if det > bound || -det > bound {
+        return det;
+    }
+    incircle_exact(a, b, c, d)
+}
+
+/// Whether the segments `a`-`b` and `c`-`d` cross at a single point inside both of them. Segments
+/// that only touch, at an end or by lying on one line, do not cross.
+pub fn segments_cross(a: Point, b: Point, c: Point, d: Point) -> bool {
+    let opposite = |x: f64, y: f64| (x > 0.0 && y < 0.0) || (x < 0.0 && y > 0.0);
+    opposite(orient2d(a, b, c), orient2d(a, b, d)) && opposite(orient2d(c, d, a), orient2d(c, d, b))
+}
+
+fn orient2d_exact(a: Point, b: Point, c: Point) -> f64 {
+    let acx = difference(a.0, c.0);
+    let acy = difference(a.1, c.1);
+    let bcx = difference(b.0, c.0);
+    let bcy = difference(b.1, c.1);
+    let det = subtract(&multiply(&acx, &bcy), &multiply(&acy, &bcx));
+    estimate(&det)
+}
+
+fn incircle_exact(a: Point, b: Point, c: Point, d: Point) -> f64 {
+    let (adx, ady) = (difference(a.0, d.0), difference(a.1, d.1));
+    let (bdx, bdy) = (difference(b.0, d.0), difference(b.1, d.1));
+    let (cdx, cdy) = (difference(c.0, d.0), difference(c.1, d.1));
+
+    let lift = |x: &[f64], y: &[f64]| add(&multiply(x, x), &multiply(y, y));
+    let cross = |x1: &[f64], y1: &[f64], x2: &[f64], y2: &[f64]| {
+        subtract(&multiply(x1, y2), &multiply(y1, x2))
+    };
+
+    let a_term = multiply(&lift(&adx, &ady), &cross(&bdx, &bdy, &cdx, &cdy));
+    let b_term = multiply(&lift(&bdx, &bdy), &cross(&cdx, &cdy, &adx, &ady));
+    let c_term = multiply(&lift(&cdx, &cdy), &cross(&adx, &ady, &bdx, &bdy));
+    estimate(&add(&add(&a_term, &b_term), &c_term))
+}
+
+/// `a + b` as the rounded sum and the rounding error.
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let x = a + b;
+    let b_virtual = x - a;
+    let a_virtual = x - b_virtual;
+    let error = (a - a_virtual) + (b - b_virtual);
+    (x, error)
+}
+
+/// `a * b` as the rounded product and the rounding error.
+fn two_product(a: f64, b: f64) -> (f64, f64) {
+    let x = a * b;
+    (x, a.mul_add(b, -x))
+}
+
+/// `a - b` exactly, as an expansion.
+fn difference(a: f64, b: f64) -> Vec<f64> {
+    let (x, error) = two_sum(a, -b);
+    vec![error, x]
+}
+
+/// Sum of two expansions, without zero components.
+fn add(e: &[f64], f: &[f64]) -> Vec<f64> {
+    let mut sum = e.to_vec();
+    for &component in f.iter() {
+        sum = grow(&sum, component);
+    }
+    sum
+}
+
+fn subtract(e: &[f64], f: &[f64]) -> Vec<f64> {
+    let negated: Vec<f64> = f.iter().map(|component| -component).collect();
+    add(e, &negated)
+}
+
+/// Add a single double to an expansion, dropping components that come out as zero.
+fn grow(e: &[f64], b: f64) -> Vec<f64> {
+    let mut result = Vec::with_capacity(e.len() + 1);
+    let mut q = b;
+    for &component in e.iter() {
+        let (sum, error) = two_sum(q, component);
+        if error != 0.0 {
+            result.push(error);
+        }
+        q = sum;
+    }
+    if q != 0.0 || result.is_empty() {
+        result.push(q);
+    }
+    result
+}
+
+/// Multiply an expansion by a single double.
+fn scale(e: &[f64], b: f64) -> Vec<f64> {
+    let mut result = Vec::with_capacity(e.len() * 2);
+    let mut q = 0.0;
+    for &component in e.iter() {
+        let (product, product_error) = two_product(component, b);
+        let (sum, error) = two_sum(q, product_error);
+        if error != 0.0 {
+            result.push(error);
+        }
+        let (total, error) = two_sum(product, sum);
+        if error != 0.0 {
+            result.push(error);
+        }
+        q = total;
+    }
+    if q != 0.0 || result.is_empty() {
+        result.push(q);
+    }
+    result
+}
+
+fn multiply(e: &[f64], f: &[f64]) -> Vec<f64> {
+    let mut product = vec![0.0];
+    for &component in f.iter() {
+        product = add(&product, &scale(e, component));
+    }
+    product
+}
+
+/// Approximate value of an expansion, with the correct sign. Components are non-overlapping and
+/// ordered by magnitude, so the largest one decides the sign.
+fn estimate(e: &[f64]) -> f64 {
+    e.iter().sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orientation_is_exact_for_nearly_collinear_points() {
+        // Classic failure case for naive orientation tests: points on a line, nudged by one ulp
+        let a = (0.5, 0.5);
+        let c = (24.0, 24.0);
+        for i in 0..64 {
+            let b = (0.5 + i as f64 * f64::EPSILON, 0.5);
+            let naive = (a.0 - c.0) * (b.1 - c.1) - (a.1 - c.1) * (b.0 - c.0);
+            let exact = orient2d(a, b, c);
+            if i == 0 {
+                assert_eq!(exact, 0.0);
+            } else {
+                assert!(exact > 0.0, "naive {}, exact {}", naive, exact);
+            }
+        }
+        assert!(orient2d((0.0, 0.0), (1.0, 0.0), (0.0, 1.0)) > 0.0);
+        assert_eq!(orient2d((1.0, 1.0), (2.0, 2.0), (1e9, 1e9)), 0.0);
+    }
+
+    #[test]
+    fn cocircular_points_are_exactly_zero() {
+        // Four corners of a grid cell, and of a large offset square
+        assert_eq!(
+            incircle((0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)),
+            0.0
+        );
+        let offset = 1e7 + 0.1;
+        assert_eq!(
+            incircle(
+                (offset, offset),
+                (offset + 0.3, offset),
+                (offset + 0.3, offset + 0.3),
+                (offset, offset + 0.3)
+            ),
+            0.0
+        );
+        assert!(incircle((0.0, 0.0), (2.0, 0.0), (0.0, 2.0), (1.0, 1.0)) > 0.0);
+        assert!(incircle((0.0, 0.0), (2.0, 0.0), (0.0, 2.0), (3.0, 3.0)) < 0.0);
+        // Clockwise triangles reverse the sign
+        assert!(incircle((0.0, 0.0), (0.0, 2.0), (2.0, 0.0), (1.0, 1.0)) < 0.0);
+    }
+
+    #[test]
+    fn only_proper_crossings_count() {
+        assert!(segments_cross(
+            (0.0, 0.0),
+            (2.0, 2.0),
+            (0.0, 2.0),
+            (2.0, 0.0)
+        ));
+        // Parallel, apart, sharing an end, and one ending on the other
+        assert!(!segments_cross(
+            (0.0, 0.0),
+            (2.0, 0.0),
+            (0.0, 1.0),
+            (2.0, 1.0)
+        ));
+        assert!(!segments_cross(
+            (0.0, 0.0),
+            (1.0, 1.0),
+            (2.0, 2.0),
+            (3.0, 0.0)
+        ));
+        assert!(!segments_cross(
+            (0.0, 0.0),
+            (2.0, 2.0),
+            (2.0, 2.0),
+            (4.0, 0.0)
+        ));
+        assert!(!segments_cross(
+            (0.0, 0.0),
+            (2.0, 0.0),
+            (1.0, 0.0),
+            (1.0, 5.0)
+        ));
+        // Overlapping on one line
+        assert!(!segments_cross(
+            (0.0, 0.0),
+            (2.0, 0.0),
+            (1.0, 0.0),
+            (3.0, 0.0)
+        ));
+    }
+
+    #[test]
+    fn expansions_are_exact() {
+        let big = 1e20;
+        let sum = add(&[1.0], &[big]);
+        assert_eq!(sum, vec![1.0, big]);
+        assert_eq!(estimate(&subtract(&sum, &[big])), 1.0);
+        let product = multiply(&difference(big, 1.0), &difference(big, -1.0));
+        // (big - 1) * (big + 1) = big^2 - 1
+        assert_eq!(estimate(&subtract(&product, &scale(&[big], big))), -1.0);
+    }
+}
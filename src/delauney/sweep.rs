@@ -0,0 +1,673 @@
+//! Delaunay triangulation by sweeping a convex hull outwards from a seed triangle, in
+//! O(n log n). This follows the approach of Delaunator (https://github.com/mapbox/delaunator):
+//!
+//! Points are added in order of their distance from the circumcentre of the seed triangle, so
+//! each one lies outside the hull built so far. A new point is joined to every hull edge it can
+//! see, and the new triangles are flipped until they satisfy the Delaunay condition again. A hash
+//! of hull points by angle around the centre finds visible edges without scanning the hull.
+//!
+//! All decisions are made with the exact predicates from `predicates`, and ties between points at
+//! the same distance are broken by their position, so that the result only depends on the set of
+//! points and not on the order they are given in.
+
+use super::predicates::{incircle, orient2d};
+use super::Vec2;
+use std::cmp::Ordering;
+
+/// Marks a half-edge on the convex hull, which has no twin in a neighbouring triangle.
+pub const EMPTY: usize = usize::MAX;
+
+/// Depth of the stack of edges still to be checked while flipping triangles.
+const EDGE_STACK_SIZE: usize = 512;
+
+/// Triangles as indices into the triangulated points, with adjacency between them.
+///
+/// Triangle `t` is made of the points `triangles[3 * t]`, `triangles[3 * t + 1]` and
+/// `triangles[3 * t + 2]`, in counter-clockwise order. Half-edge `e` runs from `triangles[e]` to
+/// the next point of the same triangle, and `halfedges[e]` is the opposite half-edge in the
+/// neighbouring triangle, or `EMPTY` on the convex hull.
+#[derive(Clone, Debug, Default)]
+pub struct Triangulation {
+    pub triangles: Vec<usize>,
+    pub halfedges: Vec<usize>,
+    /// Points on the convex hull, in counter-clockwise order.
+    pub hull: Vec<usize>,
+}
+
+impl Triangulation {
+    /// Triangulate `points`. Points repeating an earlier one or with a coordinate that is not
+    /// finite are left out, and fewer than three points, or points all on one line, give no
+    /// triangles.
+    pub fn new(points: &[Vec2]) -> Self {
+        let coords: Vec<(f64, f64)> = points.iter().map(<(f64, f64)>::from).collect();
+        Sweep::new(&coords).triangulate()
+    }
+
+    pub fn len(&self) -> usize {
+        self.triangles.len() / 3
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+}
+
+/// Half-edge after `e` in the same triangle.
+pub(crate) fn next(e: usize) -> usize {
+    if e % 3 == 2 {
+        e - 2
+    } else {
+        e + 1
+    }
+}
+
+/// Half-edge before `e` in the same triangle.
+pub(crate) fn prev(e: usize) -> usize {
+    if e % 3 == 0 {
+        e + 2
+    } else {
+        e - 1
+    }
+}
+
+/// State of the hull while points are being swept into it.
+struct Sweep<'a> {
+    coords: &'a [(f64, f64)],
+    triangles: Vec<usize>,
+    halfedges: Vec<usize>,
+    // Doubly linked list of the points on the hull. Removed points link to themselves
+    hull_prev: Vec<usize>,
+    hull_next: Vec<usize>,
+    // Half-edge of the hull edge starting at each hull point
+    hull_tri: Vec<usize>,
+    hull_hash: Vec<usize>,
+    hull_start: usize,
+    center: (f64, f64),
+    edge_stack: Vec<usize>,
+}
+
+impl<'a> Sweep<'a> {
+    fn new(coords: &'a [(f64, f64)]) -> Self {
+        let count = coords.len();
+        let max_triangles = (2 * count).saturating_sub(5);
+        let hash_size = (count as f64).sqrt().ceil() as usize;
+        Self {
+            coords,
+            triangles: Vec::with_capacity(max_triangles * 3),
+            halfedges: Vec::with_capacity(max_triangles * 3),
+            hull_prev: vec![0; count],
+            hull_next: vec![0; count],
+            hull_tri: vec![0; count],
+            hull_hash: vec![EMPTY; hash_size.max(1)],
+            hull_start: 0,
+            center: (0.0, 0.0),
+            edge_stack: Vec::with_capacity(EDGE_STACK_SIZE),
+        }
+    }
+
+    fn triangulate(mut self) -> Triangulation {
+        let (i0, i1, i2) = match self.seed() {
+            Some(seed) => seed,
+            None => return Triangulation::default(),
+        };
+        let coords = self.coords;
+        self.center = circumcenter(coords[i0], coords[i1], coords[i2]);
+
+        // Sweep the points in order of distance from the centre of the seed triangle
+        let distances: Vec<f64> = coords
+            .iter()
+            .map(|&point| squared_distance(point, self.center))
+            .collect();
+        let mut ids: Vec<usize> = (0..coords.len())
+            .filter(|&i| is_finite(coords[i]))
+            .collect();
+        ids.sort_by(|&a, &b| {
+            by_key_then_position((distances[a], coords[a]), (distances[b], coords[b]))
+        });
+
+        self.hull_start = i0;
+        self.hull_next[i0] = i1;
+        self.hull_prev[i2] = i1;
+        self.hull_next[i1] = i2;
+        self.hull_prev[i0] = i2;
+        self.hull_next[i2] = i0;
+        self.hull_prev[i1] = i0;
+        self.hull_tri[i0] = 0;
+        self.hull_tri[i1] = 1;
+        self.hull_tri[i2] = 2;
+        for &i in [i0, i1, i2].iter() {
+            let key = self.hash_key(coords[i]);
+            self.hull_hash[key] = i;
+        }
+        self.add_triangle(i0, i1, i2, EMPTY, EMPTY, EMPTY);
+
+        let mut previous: Option<(f64, f64)> = None;
+        for &i in ids.iter() {
+            let point = coords[i];
+
+            // Skip points repeating the previous one, and the seed triangle. Repeated points are
+            // sorted next to each other, as they are at the same distance and position
+            if previous == Some(point) {
+                continue;
+            }
+            previous = Some(point);
+            if i == i0 || i == i1 || i == i2 {
+                continue;
+            }
+
+            let (mut e, start) = match self.visible_edge(point) {
+                Some(edge) => edge,
+                // Only happens for repeats of a point that is not next to it in the sweep
+                None => continue,
+            };
+
+            // Join the point to the first visible edge
+            let t = self.add_triangle(e, i, self.hull_next[e], EMPTY, EMPTY, self.hull_tri[e]);
+            self.hull_tri[i] = self.legalize(t + 2);
+            self.hull_tri[e] = t;
+
+            // Walk forward along the hull, joining the point to every other visible edge
+            let mut n = self.hull_next[e];
+            loop {
+                let q = self.hull_next[n];
+                if orient2d(point, coords[n], coords[q]) >= 0.0 {
+                    break;
+                }
+                let t = self.add_triangle(n, i, q, self.hull_tri[i], EMPTY, self.hull_tri[n]);
+                self.hull_tri[i] = self.legalize(t + 2);
+                self.hull_next[n] = n;
+                n = q;
+            }
+
+            // If the first edge checked was already visible, edges before it may be too
+            if e == start {
+                loop {
+                    let q = self.hull_prev[e];
+                    if orient2d(point, coords[q], coords[e]) >= 0.0 {
+                        break;
+                    }
+                    let t = self.add_triangle(q, i, e, EMPTY, self.hull_tri[e], self.hull_tri[q]);
+                    self.legalize(t + 2);
+                    self.hull_tri[q] = t;
+                    self.hull_next[e] = e;
+                    e = q;
+                }
+            }
+
+            self.hull_start = e;
+            self.hull_prev[i] = e;
+            self.hull_next[e] = i;
+            self.hull_prev[n] = i;
+            self.hull_next[i] = n;
+
+            let key = self.hash_key(point);
+            self.hull_hash[key] = i;
+            let key = self.hash_key(coords[e]);
+            self.hull_hash[key] = e;
+        }
+
+        let mut hull = vec![self.hull_start];
+        let mut e = self.hull_next[self.hull_start];
+        while e != self.hull_start {
+            hull.push(e);
+            e = self.hull_next[e];
+        }
+
+        Triangulation {
+            triangles: self.triangles,
+            halfedges: self.halfedges,
+            hull,
+        }
+    }
+
+    /// A counter-clockwise seed triangle near the middle of the points, with a small
+    /// circumcircle, or `None` if the points do not span an area.
+    fn seed(&self) -> Option<(usize, usize, usize)> {
+        let coords = self.coords;
+        if coords.len() < 3 {
+            return None;
+        }
+        let (mut min_x, mut min_y) = (f64::INFINITY, f64::INFINITY);
+        let (mut max_x, mut max_y) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for &(x, y) in coords.iter().filter(|&&point| is_finite(point)) {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+        let middle = ((min_x + max_x) / 2.0, (min_y + max_y) / 2.0);
+
+        // Ties are broken by position rather than by index, so that the seed does not depend on
+        // the order of the points
+        let smallest = |key: &dyn Fn((f64, f64)) -> Option<f64>| {
+            (0..coords.len())
+                .filter(|&i| is_finite(coords[i]))
+                .filter_map(|i| key(coords[i]).map(|value| (i, value)))
+                .min_by(|a, b| by_key_then_position((a.1, coords[a.0]), (b.1, coords[b.0])))
+                .map(|(i, _)| i)
+        };
+        let i0 = smallest(&|point| Some(squared_distance(middle, point)))?;
+        let a = coords[i0];
+        let i1 = smallest(&|point| Some(squared_distance(a, point)).filter(|&d| d > 0.0))?;
+        let b = coords[i1];
+        let i2 = smallest(&|point| {
+            Some(circumradius(a, b, point)).filter(|_| orient2d(a, b, point) != 0.0)
+        })?;
+
+        if orient2d(coords[i0], coords[i1], coords[i2]) < 0.0 {
+            Some((i0, i2, i1))
+        } else {
+            Some((i0, i1, i2))
+        }
+    }
+
+    /// First hull point `e` whose edge to the next hull point can be seen from `point`, along
+    /// with the hull point the search started from.
+    fn visible_edge(&self, point: (f64, f64)) -> Option<(usize, usize)> {
+        let key = self.hash_key(point);
+        let size = self.hull_hash.len();
+        let mut start = 0;
+        for j in 0..size {
+            start = self.hull_hash[(key + j) % size];
+            if start != EMPTY && start != self.hull_next[start] {
+                break;
+            }
+        }
+
+        let start = self.hull_prev[start];
+        let mut e = start;
+        loop {
+            let q = self.hull_next[e];
+            if orient2d(point, self.coords[e], self.coords[q]) < 0.0 {
+                return Some((e, start));
+            }
+            e = q;
+            if e == start {
+                return None;
+            }
+        }
+    }
+
+    /// Bucket of the hull hash for `point`, by its angle around the centre. Buckets run
+    /// clockwise, so that searching forward from a point finds the hull just behind it.
+    fn hash_key(&self, point: (f64, f64)) -> usize {
+        let size = self.hull_hash.len();
+        let angle = pseudo_angle(point.0 - self.center.0, self.center.1 - point.1);
+        ((angle * size as f64).floor() as usize) % size
+    }
+
+    fn link(&mut self, a: usize, b: usize) {
+        self.halfedges[a] = b;
+        if b != EMPTY {
+            self.halfedges[b] = a;
+        }
+    }
+
+    /// Add the triangle (`i0`, `i1`, `i2`), whose edges border the half-edges `a`, `b` and `c`.
+    fn add_triangle(
+        &mut self,
+        i0: usize,
+        i1: usize,
+        i2: usize,
+        a: usize,
+        b: usize,
+        c: usize,
+    ) -> usize {
+        let t = self.triangles.len();
+        self.triangles.extend_from_slice(&[i0, i1, i2]);
+        self.halfedges.extend_from_slice(&[EMPTY; 3]);
+        self.link(t, a);
+        self.link(t + 1, b);
+        self.link(t + 2, c);
+        t
+    }
+
+    /// Flip the edge `a` and the edges around it until every triangle they touch satisfies the
+    /// Delaunay condition. Returns the half-edge now in the place of the edge before `a`.
+    ///
+    /// ```text
+    ///           pl                    pl
+    ///          /||\                  /  \
+    ///       al/ || \bl            al/    \a
+    ///        /  ||  \              /      \
+    ///       /  a||b  \    flip    /___ar___\
+    ///     p0\   ||   /p1   =>   p0\---bl---/p1
+    ///        \  ||  /              \      /
+    ///       ar\ || /br             b\    /br
+    ///          \||/                  \  /
+    ///           pr                    pr
+    /// ```
+    fn legalize(&mut self, mut a: usize) -> usize {
+        let mut ar;
+        loop {
+            let b = self.halfedges[a];
+            let a0 = a - a % 3;
+            ar = a0 + (a + 2) % 3;
+
+            if b == EMPTY {
+                match self.edge_stack.pop() {
+                    Some(edge) => a = edge,
+                    None => break,
+                }
+                continue;
+            }
+
+            let b0 = b - b % 3;
+            let al = a0 + (a + 1) % 3;
+            let bl = b0 + (b + 2) % 3;
+            let p0 = self.triangles[ar];
+            let pr = self.triangles[a];
+            let pl = self.triangles[al];
+            let p1 = self.triangles[bl];
+
+            let coords = self.coords;
+            let illegal = incircle(coords[p0], coords[pr], coords[pl], coords[p1]) > 0.0;
+            if illegal {
+                self.triangles[a] = p1;
+                self.triangles[b] = p0;
+
+                // The edge was on the hull, so the hull must point at its new place
+                let hbl = self.halfedges[bl];
+                if hbl == EMPTY {
+                    let mut e = self.hull_start;
+                    loop {
+                        if self.hull_tri[e] == bl {
+                            self.hull_tri[e] = a;
+                            break;
+                        }
+                        e = self.hull_prev[e];
+                        if e == self.hull_start {
+                            break;
+                        }
+                    }
+                }
+                self.link(a, hbl);
+                self.link(b, self.halfedges[ar]);
+                self.link(ar, bl);
+
+                // Degenerate input could keep flipping forever, so the stack is capped
+                let br = b0 + (b + 1) % 3;
+                if self.edge_stack.len() < EDGE_STACK_SIZE {
+                    self.edge_stack.push(br);
+                }
+            } else {
+                match self.edge_stack.pop() {
+                    Some(edge) => a = edge,
+                    None => break,
+                }
+            }
+        }
+        ar
+    }
+}
+
+/// Order by `key`, then by x and y, so that equal keys still give a fixed order.
+fn by_key_then_position(a: (f64, (f64, f64)), b: (f64, (f64, f64))) -> Ordering {
+    a.0.total_cmp(&b.0)
+        .then(a.1 .0.total_cmp(&b.1 .0))
+        .then(a.1 .1.total_cmp(&b.1 .1))
+}
+
+fn is_finite(point: (f64, f64)) -> bool {
+    point.0.is_finite() && point.1.is_finite()
+}
+
+fn squared_distance(a: (f64, f64), b: (f64, f64)) -> f64 {
+    (a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)
+}
+
+/// Offset of the circumcentre of (`a`, `b`, `c`) from `a`. Infinite or NaN when the points are
+/// on one line.
+fn circumcenter_offset(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> (f64, f64) {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let (ex, ey) = (c.0 - a.0, c.1 - a.1);
+    let bl = dx * dx + dy * dy;
+    let cl = ex * ex + ey * ey;
+    let d = 0.5 / (dx * ey - dy * ex);
+    ((ey * bl - dy * cl) * d, (dx * cl - ex * bl) * d)
+}
+
+fn circumcenter(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> (f64, f64) {
+    let (x, y) = circumcenter_offset(a, b, c);
+    (a.0 + x, a.1 + y)
+}
+
+/// Squared radius of the circumcircle of (`a`, `b`, `c`).
+fn circumradius(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    let (x, y) = circumcenter_offset(a, b, c);
+    x * x + y * y
+}
+
+/// Increases monotonically with the angle of (`dx`, `dy`), between 0 and 1, without any
+/// trigonometry.
+fn pseudo_angle(dx: f64, dy: f64) -> f64 {
+    let p = dx / (dx.abs() + dy.abs());
+    if dy > 0.0 {
+        (3.0 - p) / 4.0
+    } else {
+        (1.0 + p) / 4.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+
+    fn random_points(count: usize, seed: u64) -> Vec<Vec2> {
+        let mut random = Random::new(seed);
+        (0..count)
+            .map(|_| Vec2::new(random.range(0.0, 1000.0), random.range(0.0, 1000.0)))
+            .collect()
+    }
+
+    /// A `columns` by `rows` grid of points `spacing` apart, the worst case for cocircular points.
+    fn grid(columns: usize, rows: usize, origin: Vec2, spacing: f32) -> Vec<Vec2> {
+        let mut points = Vec::new();
+        for row in 0..rows {
+            for column in 0..columns {
+                points.push(Vec2::new(
+                    origin.x + column as f32 * spacing,
+                    origin.y + row as f32 * spacing,
+                ));
+            }
+        }
+        points
+    }
+
+    fn shuffle(points: &mut [Vec2], random: &mut Random) {
+        for i in (1..points.len()).rev() {
+            points.swap(i, random.below(i + 1));
+        }
+    }
+
+    fn point(points: &[Vec2], i: usize) -> (f64, f64) {
+        (&points[i]).into()
+    }
+
+    /// Every triangle is counter-clockwise with no point strictly inside its circumcircle, every
+    /// half-edge agrees with its twin, every distinct point is used, and the number of triangles
+    /// matches Euler's formula.
+    fn assert_delaunay(points: &[Vec2], triangulation: &Triangulation) {
+        let triangles = &triangulation.triangles;
+        for triangle in triangles.chunks_exact(3) {
+            let (a, b, c) = (
+                point(points, triangle[0]),
+                point(points, triangle[1]),
+                point(points, triangle[2]),
+            );
+            assert!(orient2d(a, b, c) > 0.0);
+            for i in 0..points.len() {
+                assert!(incircle(a, b, c, point(points, i)) <= 0.0);
+            }
+        }
+
+        for (e, &twin) in triangulation.halfedges.iter().enumerate() {
+            if twin == EMPTY {
+                continue;
+            }
+            assert_eq!(triangulation.halfedges[twin], e);
+            assert_eq!(triangles[e], triangles[next(twin)]);
+            assert_eq!(triangles[twin], triangles[next(e)]);
+        }
+
+        let mut distinct: Vec<(u32, u32)> = points
+            .iter()
+            .map(|point| (point.x.to_bits(), point.y.to_bits()))
+            .collect();
+        distinct.sort_unstable();
+        distinct.dedup();
+        let mut used: Vec<(u32, u32)> = triangles
+            .iter()
+            .map(|&i| (points[i].x.to_bits(), points[i].y.to_bits()))
+            .collect();
+        used.sort_unstable();
+        used.dedup();
+        assert_eq!(used, distinct);
+
+        let hull = &triangulation.hull;
+        let hull_edges = triangulation
+            .halfedges
+            .iter()
+            .filter(|&&twin| twin == EMPTY)
+            .count();
+        assert_eq!(hull_edges, hull.len());
+        assert_eq!(triangulation.len(), 2 * distinct.len() - hull.len() - 2);
+    }
+
+    /// Triangles by their corners' positions, independent of point indices and starting corner.
+    fn canonical(points: &[Vec2], triangulation: &Triangulation) -> Vec<[(u32, u32); 3]> {
+        let mut triangles: Vec<[(u32, u32); 3]> = triangulation
+            .triangles
+            .chunks_exact(3)
+            .map(|triangle| {
+                let mut corners = [(0, 0); 3];
+                for (corner, &i) in corners.iter_mut().zip(triangle) {
+                    *corner = (points[i].x.to_bits(), points[i].y.to_bits());
+                }
+                let first = (0..3).min_by_key(|&k| corners[k]).unwrap();
+                corners.rotate_left(first);
+                corners
+            })
+            .collect();
+        triangles.sort_unstable();
+        triangles
+    }
+
+    #[test]
+    fn random_points_are_delaunay() {
+        for seed in 0..20 {
+            let points = random_points(300, seed);
+            assert_delaunay(&points, &Triangulation::new(&points));
+        }
+    }
+
+    #[test]
+    fn grids_are_delaunay() {
+        let origins = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(-50.0, 20.0),
+            Vec2::new(10_000.5, -3_000.25),
+        ];
+        for &origin in origins.iter() {
+            for &(columns, rows, spacing) in [(2, 2, 1.0), (7, 5, 10.0), (30, 30, 0.1)].iter() {
+                let points = grid(columns, rows, origin, spacing);
+                let triangulation = Triangulation::new(&points);
+                assert_delaunay(&points, &triangulation);
+                assert_eq!(triangulation.len(), 2 * (columns - 1) * (rows - 1));
+            }
+        }
+    }
+
+    #[test]
+    fn repeated_points_are_left_out() {
+        let mut random = Random::new(5);
+        for seed in 0..10 {
+            let mut points = random_points(50, seed);
+            points.extend_from_within(10..20);
+            points.extend(grid(4, 4, Vec2::new(100.0, 100.0), 25.0));
+            points.extend(grid(4, 4, Vec2::new(100.0, 100.0), 25.0));
+            shuffle(&mut points, &mut random);
+
+            let triangulation = Triangulation::new(&points);
+            assert_delaunay(&points, &triangulation);
+        }
+    }
+
+    #[test]
+    fn order_of_points_does_not_matter() {
+        let mut random = Random::new(9);
+        let mut points = grid(12, 9, Vec2::new(-5.0, 3.0), 2.5);
+        points.extend(
+            random_points(40, 2)
+                .iter()
+                .map(|p| Vec2::new(p.x / 40.0, p.y / 40.0)),
+        );
+        let expected = canonical(&points, &Triangulation::new(&points));
+
+        for _ in 0..10 {
+            shuffle(&mut points, &mut random);
+            assert_eq!(canonical(&points, &Triangulation::new(&points)), expected);
+        }
+    }
+
+    #[test]
+    fn hull_is_counter_clockwise() {
+        let points = vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(10.0, 0.0),
+            Vec2::new(10.0, 10.0),
+            Vec2::new(0.0, 10.0),
+            Vec2::new(4.0, 6.0),
+        ];
+        let triangulation = Triangulation::new(&points);
+
+        assert_eq!(triangulation.len(), 4);
+        let hull = &triangulation.hull;
+        assert_eq!(hull.len(), 4);
+        for i in 0..hull.len() {
+            let (a, b, c) = (hull[i], hull[(i + 1) % 4], hull[(i + 2) % 4]);
+            assert!(orient2d(point(&points, a), point(&points, b), point(&points, c)) > 0.0);
+        }
+    }
+
+    #[test]
+    fn degenerate_input_gives_no_triangles() {
+        assert!(Triangulation::new(&[]).is_empty());
+        assert!(Triangulation::new(&[Vec2::new(0.0, 0.0), Vec2::new(1.0, 1.0)]).is_empty());
+        let line: Vec<Vec2> = (0..10)
+            .map(|i| Vec2::new(i as f32 * 0.1, 2.0 * i as f32 * 0.1))
+            .collect();
+        assert!(Triangulation::new(&line).is_empty());
+        let same = vec![Vec2::new(3.0, 3.0); 5];
+        assert!(Triangulation::new(&same).is_empty());
+        let unplaced = vec![
+            Vec2::new(f32::NAN, 0.0),
+            Vec2::new(0.0, f32::INFINITY),
+            Vec2::new(1.0, 1.0),
+        ];
+        assert!(Triangulation::new(&unplaced).is_empty());
+    }
+
+    #[test]
+    fn points_that_are_not_finite_are_left_out() {
+        let mut points = random_points(30, 4);
+        points.insert(0, Vec2::new(f32::INFINITY, 0.0));
+        points.insert(7, Vec2::new(f32::NAN, f32::NAN));
+        points.push(Vec2::new(5.0, f32::NEG_INFINITY));
+
+        let triangulation = Triangulation::new(&points);
+        for &i in triangulation.triangles.iter() {
+            assert!(points[i].x.is_finite() && points[i].y.is_finite());
+        }
+        let finite_points: Vec<Vec2> = points
+            .iter()
+            .cloned()
+            .filter(|point| point.x.is_finite() && point.y.is_finite())
+            .collect();
+        assert_eq!(
+            canonical(&points, &triangulation),
+            canonical(&finite_points, &Triangulation::new(&finite_points))
+        );
+    }
+}
@@ -0,0 +1,362 @@
+//! Voronoi diagram of a set of points, read off their Delaunay triangulation.
+//!
+//! The cell of a point is the region closer to it than to any other point. Its corners are the
+//! circumcentres of the triangles around the point, and its neighbours are the points it is
+//! joined to by triangle edges. Cells of points on the convex hull reach out to infinity, so they
+//! are also given the directions they open up in, and can be clipped to a rectangle.
+
+use super::sweep::{next, prev, Triangulation, EMPTY};
+use super::{circumcenter, Vec2};
+use std::f32::consts::PI;
+
+/// Largest angle between two far corners used to stand in for the open side of a cell.
+const FAR_CORNER_ANGLE: f32 = PI / 3.0;
+
+/// The region of the plane closer to one point than to any other.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Cell {
+    /// Corners of the cell, in counter-clockwise order.
+    pub vertices: Vec<Vec2>,
+    /// For cells of points on the convex hull: the directions in which the cell reaches out to
+    /// infinity from its first and from its last corner.
+    pub rays: Option<(Vec2, Vec2)>,
+    /// Points joined to this one in the triangulation, whose cells share an edge with this one,
+    /// or only a corner where four or more points lie on one circle.
+    pub neighbours: Vec<usize>,
+}
+
+impl Cell {
+    pub fn is_open(&self) -> bool {
+        self.rays.is_some()
+    }
+
+    /// Area of a closed cell, or `None` for an open one.
+    pub fn area(&self) -> Option<f32> {
+        if self.is_open() {
+            None
+        } else {
+            Some(polygon_area(&self.vertices))
+        }
+    }
+}
+
+/// Voronoi cells of every point, in the same order as the points.
+///
+/// Points repeating an earlier one are left out of the triangulation, so they get an empty cell.
+#[derive(Clone, Debug, Default)]
+pub struct Voronoi {
+    pub points: Vec<Vec2>,
+    pub cells: Vec<Cell>,
+}
+
+impl Voronoi {
+    pub fn new(points: &[Vec2]) -> Self {
+        Voronoi::from_triangulation(points, &Triangulation::new(points))
+    }
+
+    /// Voronoi diagram of `points`, from their Delaunay `triangulation`.
+    pub fn from_triangulation(points: &[Vec2], triangulation: &Triangulation) -> Self {
+        let triangles = &triangulation.triangles;
+        let halfedges = &triangulation.halfedges;
+        let centers: Vec<Vec2> = triangles
+            .chunks_exact(3)
+            .map(|t| {
+                circumcenter(&points[t[0]], &points[t[1]], &points[t[2]])
+                    .expect("Delaunay triangles are never flat")
+            })
+            .collect();
+
+        // Some half-edge ending at each point, on the hull if the point is on it, so that going
+        // round the point from there passes every triangle in one sweep
+        let mut incoming = vec![EMPTY; points.len()];
+        for e in 0..triangles.len() {
+            let end = triangles[next(e)];
+            if incoming[end] == EMPTY || halfedges[e] == EMPTY {
+                incoming[end] = e;
+            }
+        }
+
+        let mut cells = vec![Cell::default(); points.len()];
+        for (point, cell) in cells.iter_mut().enumerate() {
+            let start = incoming[point];
+            if start == EMPTY {
+                continue;
+            }
+
+            // Round the point clockwise, one triangle at a time
+            let mut e = start;
+            loop {
+                cell.vertices.push(centers[e / 3]);
+                cell.neighbours.push(triangles[e]);
+                let twin = halfedges[next(e)];
+                if twin == EMPTY {
+                    // Back on the hull, with a hull edge out to the third point of this triangle.
+                    // Once in counter-clockwise order, this triangle gives the first corner
+                    let end = triangles[prev(e)];
+                    cell.neighbours.push(end);
+                    let first = outward(&points[point], &points[end]);
+                    let last = outward(&points[triangles[start]], &points[point]);
+                    cell.rays = Some((first, last));
+                    break;
+                }
+                e = twin;
+                if e == start {
+                    break;
+                }
+            }
+            cell.vertices.reverse();
+            cell.vertices.dedup();
+            if cell.vertices.len() > 1 && cell.vertices.first() == cell.vertices.last() {
+                cell.vertices.pop();
+            }
+            cell.neighbours.reverse();
+        }
+
+        Self {
+            points: points.to_vec(),
+            cells,
+        }
+    }
+
+    /// The point closest to `query`, searching from the point `start`. Starting near the answer,
+    /// such as from the answer to a previous nearby query, makes the search faster.
+    pub fn nearest_from(&self, query: Vec2, start: usize) -> usize {
+        let mut nearest = start;
+        let mut distance = squared_distance(&self.points[nearest], &query);
+        // Walking to whichever neighbour is closest to the query always ends at the nearest point
+        loop {
+            let closer = self.cells[nearest]
+                .neighbours
+                .iter()
+                .map(|&i| (i, squared_distance(&self.points[i], &query)))
+                .filter(|&(_, d)| d < distance)
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            match closer {
+                Some((i, d)) => {
+                    nearest = i;
+                    distance = d;
+                }
+                None => return nearest,
+            }
+        }
+    }
+
+    /// The point closest to `query`, or `None` if there are no cells.
+    pub fn nearest(&self, query: Vec2) -> Option<usize> {
+        let start = self
+            .cells
+            .iter()
+            .position(|cell| !cell.vertices.is_empty())?;
+        Some(self.nearest_from(query, start))
+    }
+
+    /// Corners of the cell of point `i` within the rectangle from `min` to `max`, in
+    /// counter-clockwise order. Empty if the cell is entirely outside it.
+    pub fn clipped_cell(&self, i: usize, min: Vec2, max: Vec2) -> Vec<Vec2> {
+        let cell = &self.cells[i];
+        let mut polygon = cell.vertices.clone();
+        if let Some((first, last)) = cell.rays {
+            // Stand in for the open side of the cell with corners far outside the rectangle
+            let point = self.points[i];
+            let reach = cell
+                .vertices
+                .iter()
+                .chain([min, max, Vec2::new(min.x, max.y), Vec2::new(max.x, min.y)].iter())
+                .map(|corner| Vec2::distance(&point, corner))
+                .fold(0.0, f32::max);
+            let far = 4.0 * reach + 1.0;
+
+            let start = polygon[0];
+            let end = polygon[polygon.len() - 1];
+            polygon.insert(
+                0,
+                Vec2::new(start.x + first.x * far, start.y + first.y * far),
+            );
+            polygon.push(Vec2::new(end.x + last.x * far, end.y + last.y * far));
+
+            // Round the far side, counter-clockwise from the last ray to the first
+            let from = last.y.atan2(last.x);
+            let to = first.y.atan2(first.x);
+            let mut sweep = (to - from).rem_euclid(2.0 * PI);
+            if sweep == 0.0 {
+                sweep = 2.0 * PI;
+            }
+            let steps = ((sweep / FAR_CORNER_ANGLE).ceil() as usize).max(2);
+            for step in 1..steps {
+                let angle = from + sweep * step as f32 / steps as f32;
+                polygon.push(Vec2::new(
+                    point.x + angle.cos() * far,
+                    point.y + angle.sin() * far,
+                ));
+            }
+        }
+        clip_to_rectangle(polygon, min, max)
+    }
+
+    /// Area of the cell of point `i` within the rectangle from `min` to `max`: the part of the
+    /// rectangle closer to point `i` than to any other.
+    pub fn clipped_area(&self, i: usize, min: Vec2, max: Vec2) -> f32 {
+        polygon_area(&self.clipped_cell(i, min, max))
+    }
+}
+
+/// Unit vector pointing out of the convex hull, across its counter-clockwise edge from `a` to `b`.
+fn outward(a: &Vec2, b: &Vec2) -> Vec2 {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let length = (dx * dx + dy * dy).sqrt();
+    Vec2::new(dy / length, -dx / length)
+}
+
+fn squared_distance(a: &Vec2, b: &Vec2) -> f32 {
+    (a.x - b.x).powi(2) + (a.y - b.y).powi(2)
+}
+
+/// Area of a counter-clockwise polygon.
+fn polygon_area(polygon: &[Vec2]) -> f32 {
+    let mut twice_area = 0.0;
+    for (i, a) in polygon.iter().enumerate() {
+        let b = &polygon[(i + 1) % polygon.len()];
+        twice_area += a.x * b.y - b.x * a.y;
+    }
+    twice_area / 2.0
+}
+
+/// Cut `polygon` down to the rectangle from `min` to `max`, one side at a time
+/// (Sutherland-Hodgman).
+fn clip_to_rectangle(polygon: Vec<Vec2>, min: Vec2, max: Vec2) -> Vec<Vec2> {
+    // Each side as (vertical, limit, keep the side above the limit)
+    let sides = [
+        (true, min.x, true),
+        (true, max.x, false),
+        (false, min.y, true),
+        (false, max.y, false),
+    ];
+    let mut polygon = polygon;
+    for &(vertical, limit, above) in sides.iter() {
+        let axis = |p: &Vec2| if vertical { p.x } else { p.y };
+        let inside = |p: &Vec2| {
+            if above {
+                axis(p) >= limit
+            } else {
+                axis(p) <= limit
+            }
+        };
+        let mut clipped = Vec::with_capacity(polygon.len() + 1);
+        for (i, current) in polygon.iter().enumerate() {
+            let previous = &polygon[(i + polygon.len() - 1) % polygon.len()];
+            if inside(current) != inside(previous) {
+                let t = (limit - axis(previous)) / (axis(current) - axis(previous));
+                clipped.push(Vec2::new(
+                    previous.x + t * (current.x - previous.x),
+                    previous.y + t * (current.y - previous.y),
+                ));
+            }
+            if inside(current) {
+                clipped.push(*current);
+            }
+        }
+        polygon = clipped;
+        if polygon.is_empty() {
+            break;
+        }
+    }
+    polygon
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+
+    fn random_points(count: usize, seed: u64) -> Vec<Vec2> {
+        let mut random = Random::new(seed);
+        (0..count)
+            .map(|_| Vec2::new(random.range(0.0, 100.0), random.range(0.0, 100.0)))
+            .collect()
+    }
+
+    #[test]
+    fn middle_of_a_grid_has_a_square_cell() {
+        let mut points = Vec::new();
+        for y in 0..3 {
+            for x in 0..3 {
+                points.push(Vec2::new(x as f32 * 2.0, y as f32 * 2.0));
+            }
+        }
+        let voronoi = Voronoi::new(&points);
+
+        let middle = &voronoi.cells[4];
+        assert!(!middle.is_open());
+        assert_eq!(middle.vertices.len(), 4);
+        assert_eq!(middle.area(), Some(4.0));
+        for neighbour in [1, 3, 5, 7].iter() {
+            assert!(middle.neighbours.contains(neighbour));
+        }
+
+        let corner = &voronoi.cells[0];
+        assert!(corner.is_open());
+        assert_eq!(corner.area(), None);
+        let clipped = voronoi.clipped_cell(0, Vec2::new(-1.0, -1.0), Vec2::new(5.0, 5.0));
+        assert_eq!(polygon_area(&clipped), 4.0);
+    }
+
+    #[test]
+    fn clipped_cells_cover_the_rectangle() {
+        for seed in 0..5 {
+            let points = random_points(300, seed);
+            let voronoi = Voronoi::new(&points);
+            let (min, max) = (Vec2::new(-10.0, 5.0), Vec2::new(110.0, 90.0));
+
+            let mut total = 0.0;
+            let inside = |p: &Vec2| p.x > min.x && p.x < max.x && p.y > min.y && p.y < max.y;
+            for (i, cell) in voronoi.cells.iter().enumerate() {
+                let area = voronoi.clipped_area(i, min, max);
+                assert!(area >= 0.0);
+                total += area;
+                // Closed cells inside the rectangle are left as they are
+                if let Some(cell_area) = cell.area() {
+                    if cell.vertices.iter().all(inside) {
+                        assert!((area - cell_area).abs() <= cell_area * 1e-4);
+                    }
+                }
+            }
+            let rectangle = (max.x - min.x) * (max.y - min.y);
+            assert!((total - rectangle).abs() < rectangle * 1e-4);
+        }
+    }
+
+    #[test]
+    fn neighbours_are_mutual() {
+        let voronoi = Voronoi::new(&random_points(200, 7));
+        for (i, cell) in voronoi.cells.iter().enumerate() {
+            assert!(!cell.neighbours.contains(&i));
+            for &neighbour in cell.neighbours.iter() {
+                assert!(voronoi.cells[neighbour].neighbours.contains(&i));
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_point_is_found_by_walking() {
+        let points = random_points(500, 11);
+        let voronoi = Voronoi::new(&points);
+        let mut random = Random::new(12);
+        let mut previous = 0;
+        for _ in 0..200 {
+            let query = Vec2::new(random.range(-20.0, 120.0), random.range(-20.0, 120.0));
+            let expected = (0..points.len())
+                .min_by(|&a, &b| {
+                    let (da, db) = (
+                        squared_distance(&points[a], &query),
+                        squared_distance(&points[b], &query),
+                    );
+                    da.total_cmp(&db)
+                })
+                .unwrap();
+            assert_eq!(voronoi.nearest(query), Some(expected));
+            previous = voronoi.nearest_from(query, previous);
+            assert_eq!(previous, expected);
+        }
+        assert_eq!(Voronoi::new(&[]).nearest(Vec2::new(0.0, 0.0)), None);
+    }
+}
@@ -0,0 +1,312 @@
+//! Procedural terrain, for test landscapes of a known shape and size.
+//!
+//! A `Generator` combines one of the parametric `Shape`s with seeded fractal noise, and can
+//! optionally cut the result into terraces. It is sampled either onto a `Heightfield` directly,
+//! or at scattered control points making up a `MapFile`, which exercises the triangulation the
+//! same way a hand written map does. The same settings and seed always give the same terrain.
+
+pub mod noise;
+pub mod shape;
+
+pub use noise::{Fractal, Noise, NoiseKind};
+pub use shape::{Shape, ShapeError};
+
+use crate::map::units::DEFAULT_WORLD_SIZE;
+use crate::map::{Border, Heightfield, MapFile, Rectangle};
+use crate::random::Random;
+use crate::render::Coord;
+
+/// Height of the highest possible terrain, unless specified otherwise.
+pub const DEFAULT_HEIGHT: f32 = 50.0;
+
+/// Number of noise features across the map, unless specified otherwise.
+pub const DEFAULT_FEATURES: f32 = 4.0;
+
+/// Share of the height given to noise rather than the shape, unless specified otherwise.
+pub const DEFAULT_ROUGHNESS: f32 = 0.5;
+
+/// Furthest a control point is moved from its place on the grid, as a fraction of the spacing.
+/// Less than half, so that no two points can swap places or meet.
+const JITTER: f32 = 0.4;
+
+#[derive(Clone, Debug)]
+pub struct Generator {
+    size: f32,
+    height: f32,
+    shape: Shape,
+    noise: Option<Noise>,
+    fractal: Fractal,
+    octaves: usize,
+    features: f32,
+    roughness: f32,
+    terraces: Option<usize>,
+    seed: u64,
+}
+
+impl Generator {
+    /// Length of each side of the square map, with its south west corner at the origin.
+    pub fn size(&self) -> f32 {
+        self.size
+    }
+
+    /// Height of the terrain at (`x`, `y`), between zero and the height of the generator.
+    pub fn height_at(&self, x: f32, y: f32) -> f32 {
+        let (u, v) = (x / self.size, y / self.size);
+        let mut height = self.shape.height(u, v);
+        if let Some(noise) = &self.noise {
+            let detail = noise.fractal(
+                self.fractal,
+                self.octaves,
+                u * self.features,
+                v * self.features,
+            );
+            height += (detail - height) * self.roughness;
+        }
+        let mut height = height.clamp(0.0, 1.0);
+        if let Some(terraces) = self.terraces {
+            // Every terrace is level, with a sharp step up to the next
+            height = (height * terraces as f32)
+                .floor()
+                .min(terraces as f32 - 1.0)
+                / (terraces as f32 - 1.0).max(1.0);
+        }
+        height * self.height
+    }
+
+    /// The terrain sampled on a grid with `resolution` cells along each side.
+    pub fn heightfield(&self, resolution: usize) -> Heightfield {
+        let resolution = resolution.max(1);
+        let spacing = self.size / resolution as f32;
+        let mut heights = Vec::with_capacity((resolution + 1) * (resolution + 1));
+        for row in 0..=resolution {
+            for column in 0..=resolution {
+                heights.push(self.height_at(column as f32 * spacing, row as f32 * spacing));
+            }
+        }
+        Heightfield::from_heights(self.base(), resolution + 1, resolution + 1, heights)
+    }
+
+    /// Control points on a grid with `cells` cells along each side, each moved a random distance
+    /// from its place. Points on the edges only move along them, so that the corners stay put and
+    /// the map stays square. The map file has no border.
+    pub fn map_file(&self, cells: usize) -> MapFile {
+        let cells = cells.max(1);
+        let spacing = self.size / cells as f32;
+        let mut random = Random::new(self.seed);
+        let mut jitter = |index: usize| {
+            let offset = random.range(-JITTER, JITTER) * spacing;
+            if index == 0 || index == cells {
+                index as f32 * spacing
+            } else {
+                index as f32 * spacing + offset
+            }
+        };
+
+        let mut points = Vec::with_capacity((cells + 1) * (cells + 1));
+        for row in 0..=cells {
+            for column in 0..=cells {
+                let x = jitter(column);
+                let y = jitter(row);
+                points.push(Coord::new(x, y, self.height_at(x, y)));
+            }
+        }
+        MapFile {
+            points,
+            border: Border::none(),
+            ..MapFile::default()
+        }
+    }
+
+    fn base(&self) -> Rectangle {
+        Rectangle {
+            origin: Coord::new(0.0, 0.0, 0.0),
+            x_size: self.size,
+            y_size: self.size,
+        }
+    }
+}
+
+pub struct GeneratorBuilder {
+    size: f32,
+    height: f32,
+    shape: Shape,
+    noise: Option<NoiseKind>,
+    fractal: Fractal,
+    octaves: usize,
+    features: f32,
+    roughness: f32,
+    terraces: Option<usize>,
+    seed: u64,
+}
+
+impl GeneratorBuilder {
+    pub fn new() -> Self {
+        Self {
+            size: DEFAULT_WORLD_SIZE,
+            height: DEFAULT_HEIGHT,
+            shape: Shape::Island,
+            noise: None,
+            fractal: Fractal::Fbm,
+            octaves: noise::DEFAULT_OCTAVES,
+            features: DEFAULT_FEATURES,
+            roughness: DEFAULT_ROUGHNESS,
+            terraces: None,
+            seed: 0,
+        }
+    }
+    /// Length of each side of the square map.
+    pub fn size(mut self, size: f32) -> GeneratorBuilder {
+        self.size = size;
+        self
+    }
+    /// Height of the highest possible terrain. The lowest is always zero.
+    pub fn height(mut self, height: f32) -> GeneratorBuilder {
+        self.height = height;
+        self
+    }
+    pub fn shape(mut self, shape: Shape) -> GeneratorBuilder {
+        self.shape = shape;
+        self
+    }
+    /// Noise to add to the shape, or `None` for the shape alone, which is the default.
+    pub fn noise(mut self, noise: Option<NoiseKind>) -> GeneratorBuilder {
+        self.noise = noise;
+        self
+    }
+    pub fn fractal(mut self, fractal: Fractal) -> GeneratorBuilder {
+        self.fractal = fractal;
+        self
+    }
+    pub fn octaves(mut self, octaves: usize) -> GeneratorBuilder {
+        self.octaves = octaves;
+        self
+    }
+    /// Number of noise features across the map at the coarsest octave.
+    pub fn features(mut self, features: f32) -> GeneratorBuilder {
+        self.features = features;
+        self
+    }
+    /// Share of the height given to noise rather than the shape, from 0 to 1.
+    pub fn roughness(mut self, roughness: f32) -> GeneratorBuilder {
+        self.roughness = roughness.clamp(0.0, 1.0);
+        self
+    }
+    /// Number of level terraces to cut the terrain into, or `None` to leave it smooth.
+    pub fn terraces(mut self, terraces: Option<usize>) -> GeneratorBuilder {
+        self.terraces = terraces;
+        self
+    }
+    /// Seed for the noise and for where control points are placed.
+    pub fn seed(mut self, seed: u64) -> GeneratorBuilder {
+        self.seed = seed;
+        self
+    }
+    pub fn build(self) -> Generator {
+        Generator {
+            size: self.size,
+            height: self.height,
+            shape: self.shape,
+            noise: self.noise.map(|kind| Noise::new(kind, self.seed)),
+            fractal: self.fractal,
+            octaves: self.octaves,
+            features: self.features,
+            roughness: self.roughness,
+            terraces: self.terraces,
+            seed: self.seed,
+        }
+    }
+}
+
+impl Default for GeneratorBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::io::write_mod1;
+    use crate::map::MapParseError;
+
+    #[test]
+    fn same_settings_same_terrain() {
+        let generator = || {
+            GeneratorBuilder::new()
+                .shape(Shape::Ridge)
+                .noise(Some(NoiseKind::Simplex))
+                .fractal(Fractal::Ridged)
+                .seed(11)
+                .build()
+        };
+        let a = generator().heightfield(32);
+        assert_eq!(a.heights(), generator().heightfield(32).heights());
+        assert_eq!(generator().map_file(8), generator().map_file(8));
+
+        let other = GeneratorBuilder::new()
+            .shape(Shape::Ridge)
+            .noise(Some(NoiseKind::Simplex))
+            .fractal(Fractal::Ridged)
+            .seed(12)
+            .build();
+        assert_ne!(a.heights(), other.heightfield(32).heights());
+    }
+
+    #[test]
+    fn heightfields_cover_the_square() {
+        let generator = GeneratorBuilder::new()
+            .size(120.0)
+            .height(30.0)
+            .shape(Shape::Bowl)
+            .build();
+        let heightfield = generator.heightfield(12);
+        assert_eq!((heightfield.columns(), heightfield.rows()), (13, 13));
+        assert_eq!(heightfield.base.x_size, 120.0);
+        // The bottom of the bowl is in the middle, and its rim at the edges
+        assert_eq!(heightfield.height(6, 6), 0.0);
+        assert_eq!(heightfield.height(0, 6), 30.0);
+        assert!(heightfield
+            .heights()
+            .iter()
+            .all(|height| (0.0..=30.0).contains(height)));
+    }
+
+    #[test]
+    fn terraces_are_level() {
+        let generator = GeneratorBuilder::new()
+            .shape(Shape::Slope)
+            .height(40.0)
+            .terraces(Some(5))
+            .build();
+        let mut levels = generator.heightfield(50).heights().to_vec();
+        levels.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        levels.dedup();
+        assert_eq!(levels, vec![0.0, 10.0, 20.0, 30.0, 40.0]);
+    }
+
+    #[test]
+    fn control_points_are_scattered_over_the_square() -> Result<(), MapParseError> {
+        let generator = GeneratorBuilder::new()
+            .noise(Some(NoiseKind::Perlin))
+            .seed(3)
+            .build();
+        let map_file = generator.map_file(10);
+        assert_eq!(map_file.points.len(), 11 * 11);
+        assert!(map_file.border.is_none());
+        for point in map_file.points.iter() {
+            assert!((0.0..=200.0).contains(point.x()) && (0.0..=200.0).contains(point.y()));
+            assert_eq!(*point.z(), generator.height_at(*point.x(), *point.y()));
+        }
+        assert_eq!(
+            map_file.points[120],
+            Coord::new(200.0, 200.0, map_file.points[120][2])
+        );
+        // Inner points are moved off the grid
+        assert_ne!(*map_file.points[12].x(), 20.0);
+
+        let mut output = Vec::new();
+        write_mod1(&mut output, &map_file).unwrap();
+        assert_eq!(MapFile::parse(output.as_slice(), "gen.mod1")?, map_file);
+        Ok(())
+    }
+}
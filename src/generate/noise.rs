@@ -0,0 +1,274 @@
+//! Seeded gradient noise in two dimensions, and fractal sums of it.
+//!
+//! Both Perlin and simplex noise hash lattice points through a permutation of 0 to 255 shuffled
+//! by the seed, so the same seed always gives the same landscape. Fractals add octaves of noise,
+//! each at twice the frequency and half the amplitude of the one before.
+
+use crate::random::Random;
+use std::fmt;
+use std::str::FromStr;
+
+/// Octaves summed by fractals, unless specified otherwise.
+pub const DEFAULT_OCTAVES: usize = 5;
+
+/// Frequency of each octave relative to the one before.
+const LACUNARITY: f32 = 2.0;
+
+/// Amplitude of each octave relative to the one before.
+const GAIN: f32 = 0.5;
+
+/// Gradients at the lattice points, picked by hash.
+const GRADIENTS: [(f32, f32); 8] = [
+    (1.0, 1.0),
+    (-1.0, 1.0),
+    (1.0, -1.0),
+    (-1.0, -1.0),
+    (1.0, 0.0),
+    (-1.0, 0.0),
+    (0.0, 1.0),
+    (0.0, -1.0),
+];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NoiseKind {
+    /// Gradients on a square lattice, blended with a quintic fade.
+    Perlin,
+    /// Gradients on a triangular lattice, with fewer directional artifacts than Perlin noise.
+    Simplex,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct NoiseKindError;
+
+impl fmt::Display for NoiseKindError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Noise must be either perlin or simplex")
+    }
+}
+
+impl std::error::Error for NoiseKindError {}
+
+impl FromStr for NoiseKind {
+    type Err = NoiseKindError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "perlin" => Ok(NoiseKind::Perlin),
+            "simplex" => Ok(NoiseKind::Simplex),
+            _ => Err(NoiseKindError),
+        }
+    }
+}
+
+/// How octaves of noise are added up.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Fractal {
+    /// Fractional Brownian motion: rolling hills with detail at every scale.
+    Fbm,
+    /// Octaves folded at zero and inverted, giving sharp crests like mountain ranges.
+    Ridged,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FractalError;
+
+impl fmt::Display for FractalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Fractal must be either fbm or ridged")
+    }
+}
+
+impl std::error::Error for FractalError {}
+
+impl FromStr for Fractal {
+    type Err = FractalError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fbm" => Ok(Fractal::Fbm),
+            "ridged" => Ok(Fractal::Ridged),
+            _ => Err(FractalError),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Noise {
+    kind: NoiseKind,
+    // The shuffled permutation twice over, so that hashes of neighbouring points need no wrapping
+    permutation: Vec<u8>,
+}
+
+impl Noise {
+    pub fn new(kind: NoiseKind, seed: u64) -> Self {
+        let mut random = Random::new(seed);
+        let mut permutation: Vec<u8> = (0..=255).collect();
+        for i in (1..permutation.len()).rev() {
+            permutation.swap(i, random.below(i + 1));
+        }
+        permutation.extend_from_within(..);
+        Self { kind, permutation }
+    }
+
+    pub fn kind(&self) -> NoiseKind {
+        self.kind
+    }
+
+    /// Noise at (`x`, `y`), between -1 and 1. Features are about one unit across.
+    pub fn sample(&self, x: f32, y: f32) -> f32 {
+        let value = match self.kind {
+            NoiseKind::Perlin => self.perlin(x, y),
+            NoiseKind::Simplex => self.simplex(x, y),
+        };
+        value.clamp(-1.0, 1.0)
+    }
+
+    /// `octaves` of noise at (`x`, `y`) added up as `fractal` asks, between 0 and 1.
+    pub fn fractal(&self, fractal: Fractal, octaves: usize, x: f32, y: f32) -> f32 {
+        let mut sum = 0.0;
+        let mut total = 0.0;
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        for octave in 0..octaves.max(1) {
+            // Shift every octave so that their lattices do not line up at the origin
+            let offset = octave as f32 * 17.31;
+            let value = self.sample(x * frequency + offset, y * frequency + offset);
+            sum += amplitude
+                * match fractal {
+                    Fractal::Fbm => (value + 1.0) / 2.0,
+                    Fractal::Ridged => (1.0 - value.abs()).powi(2),
+                };
+            total += amplitude;
+            frequency *= LACUNARITY;
+            amplitude *= GAIN;
+        }
+        sum / total
+    }
+
+    fn hash(&self, x: i32, y: i32) -> usize {
+        let y = self.permutation[(y & 255) as usize] as usize;
+        self.permutation[(x & 255) as usize + y] as usize
+    }
+
+    /// Dot product of the gradient at lattice point (`i`, `j`) with the offset (`x`, `y`) from it.
+    fn gradient(&self, i: i32, j: i32, x: f32, y: f32) -> f32 {
+        let (gx, gy) = GRADIENTS[self.hash(i, j) & 7];
+        gx * x + gy * y
+    }
+
+    fn perlin(&self, x: f32, y: f32) -> f32 {
+        let (i, j) = (x.floor(), y.floor());
+        let (x, y) = (x - i, y - j);
+        let (i, j) = (i as i32, j as i32);
+        let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let (u, v) = (fade(x), fade(y));
+
+        let south = lerp(
+            self.gradient(i, j, x, y),
+            self.gradient(i + 1, j, x - 1.0, y),
+            u,
+        );
+        let north = lerp(
+            self.gradient(i, j + 1, x, y - 1.0),
+            self.gradient(i + 1, j + 1, x - 1.0, y - 1.0),
+            u,
+        );
+        lerp(south, north, v)
+    }
+
+    fn simplex(&self, x: f32, y: f32) -> f32 {
+        // Skew the plane so that the triangular lattice becomes a square one, and back
+        let skew = 0.5 * (3f32.sqrt() - 1.0);
+        let unskew = (3.0 - 3f32.sqrt()) / 6.0;
+
+        let s = (x + y) * skew;
+        let (i, j) = ((x + s).floor(), (y + s).floor());
+        let t = (i + j) * unskew;
+        let (x0, y0) = (x - (i - t), y - (j - t));
+        let (i, j) = (i as i32, j as i32);
+        // The middle corner of the triangle holding the point
+        let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+        let corners = [
+            (i, j, x0, y0),
+            (
+                i + i1,
+                j + j1,
+                x0 - i1 as f32 + unskew,
+                y0 - j1 as f32 + unskew,
+            ),
+            (
+                i + 1,
+                j + 1,
+                x0 - 1.0 + 2.0 * unskew,
+                y0 - 1.0 + 2.0 * unskew,
+            ),
+        ];
+
+        let mut sum = 0.0;
+        for &(i, j, x, y) in corners.iter() {
+            let falloff = 0.5 - x * x - y * y;
+            if falloff > 0.0 {
+                sum += falloff.powi(4) * self.gradient(i, j, x, y);
+            }
+        }
+        // Brings the largest values close to 1
+        70.0 * sum
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_noise() {
+        for &kind in [NoiseKind::Perlin, NoiseKind::Simplex].iter() {
+            let a = Noise::new(kind, 7);
+            let b = Noise::new(kind, 7);
+            let c = Noise::new(kind, 8);
+            let mut differs = false;
+            for step in 0..100 {
+                let (x, y) = (step as f32 * 0.37, step as f32 * -0.71);
+                assert_eq!(a.sample(x, y), b.sample(x, y));
+                differs |= a.sample(x, y) != c.sample(x, y);
+            }
+            assert!(differs);
+        }
+    }
+
+    #[test]
+    fn noise_is_smooth_and_bounded() {
+        for &kind in [NoiseKind::Perlin, NoiseKind::Simplex].iter() {
+            let noise = Noise::new(kind, 1);
+            let (mut lowest, mut highest) = (f32::INFINITY, f32::NEG_INFINITY);
+            for row in 0..200 {
+                for column in 0..200 {
+                    let (x, y) = (column as f32 * 0.05 - 5.0, row as f32 * 0.05 - 5.0);
+                    let value = noise.sample(x, y);
+                    lowest = lowest.min(value);
+                    highest = highest.max(value);
+                    assert!((noise.sample(x + 0.001, y) - value).abs() < 0.01);
+                    for &fractal in [Fractal::Fbm, Fractal::Ridged].iter() {
+                        let value = noise.fractal(fractal, DEFAULT_OCTAVES, x, y);
+                        assert!((0.0..=1.0).contains(&value));
+                    }
+                }
+            }
+            // Noise varies over most of its range
+            assert!(lowest < -0.4 && highest > 0.4, "{:?}", kind);
+        }
+        // Perlin noise is zero on the lattice
+        assert_eq!(Noise::new(NoiseKind::Perlin, 3).sample(4.0, -2.0), 0.0);
+    }
+
+    #[test]
+    fn noise_and_fractals_are_read_case_insensitively() {
+        assert_eq!("Perlin".parse(), Ok(NoiseKind::Perlin));
+        assert_eq!("simplex".parse(), Ok(NoiseKind::Simplex));
+        assert_eq!("value".parse::<NoiseKind>(), Err(NoiseKindError));
+        assert_eq!("FBM".parse(), Ok(Fractal::Fbm));
+        assert_eq!("ridged".parse(), Ok(Fractal::Ridged));
+        assert_eq!("billow".parse::<Fractal>(), Err(FractalError));
+    }
+}
@@ -0,0 +1,83 @@
+//! Parametric landforms, defined over a square map.
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Shape {
+    /// Level ground halfway up, so that noise alone shapes the terrain.
+    Flat,
+    /// Low in the middle, rising to a rim around the edges, to hold water.
+    Bowl,
+    /// A hill in the middle, falling smoothly to the ground before the edges.
+    Island,
+    /// A crest running north to south through the middle, sloping down to the east and west.
+    Ridge,
+    /// An even slope, rising from the west edge to the east edge.
+    Slope,
+}
+
+impl Shape {
+    /// Height of the shape at (`u`, `v`), as fractions of the way across the map from its south
+    /// west corner, between 0 and 1.
+    pub fn height(&self, u: f32, v: f32) -> f32 {
+        // Distance from the middle, 1 at the middle of each edge
+        let distance = ((u - 0.5).powi(2) + (v - 0.5).powi(2)).sqrt() * 2.0;
+        let smooth = |t: f32| {
+            let t = t.clamp(0.0, 1.0);
+            t * t * (3.0 - 2.0 * t)
+        };
+        match self {
+            Shape::Flat => 0.5,
+            Shape::Bowl => distance.min(1.0).powi(2),
+            Shape::Island => smooth(1.0 - distance),
+            Shape::Ridge => 1.0 - (2.0 * u - 1.0).abs().min(1.0),
+            Shape::Slope => u.clamp(0.0, 1.0),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ShapeError;
+
+impl fmt::Display for ShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Shape must be one of flat, bowl, island, ridge or slope")
+    }
+}
+
+impl std::error::Error for ShapeError {}
+
+impl FromStr for Shape {
+    type Err = ShapeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "flat" => Ok(Shape::Flat),
+            "bowl" => Ok(Shape::Bowl),
+            "island" => Ok(Shape::Island),
+            "ridge" => Ok(Shape::Ridge),
+            "slope" => Ok(Shape::Slope),
+            _ => Err(ShapeError),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shapes_have_their_landmarks_where_expected() {
+        assert_eq!(Shape::Bowl.height(0.5, 0.5), 0.0);
+        assert_eq!(Shape::Bowl.height(0.0, 0.5), 1.0);
+        assert_eq!(Shape::Island.height(0.5, 0.5), 1.0);
+        assert_eq!(Shape::Island.height(1.0, 0.5), 0.0);
+        assert_eq!(Shape::Ridge.height(0.5, 0.0), 1.0);
+        assert_eq!(Shape::Ridge.height(0.0, 0.3), 0.0);
+        assert_eq!(Shape::Slope.height(0.25, 0.9), 0.25);
+        assert_eq!(Shape::Flat.height(0.1, 0.7), 0.5);
+        assert_eq!("Island".parse(), Ok(Shape::Island));
+        assert_eq!("crater".parse::<Shape>(), Err(ShapeError));
+    }
+}
@@ -0,0 +1,199 @@
+//! Margin of terrain added around the control points of a map, so that the landscape ends at a
+//! chosen height instead of being cut off at its outermost points.
+
+use super::{Heightfield, Rectangle};
+use std::fmt;
+use std::str::FromStr;
+
+/// Fraction of the extent of the control points added on every side, unless specified otherwise.
+pub const DEFAULT_PADDING: f32 = 0.5;
+
+/// Margin added on each side of a map whose control points all lie on one line along that axis.
+pub const DEGENERATE_PADDING: f32 = 10.0;
+
+/// How the terrain reaches the border height across the margin.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Falloff {
+    /// Only the four corners of the map are pinned to the border height, and the margin is
+    /// interpolated between them and the control points like the rest of the map.
+    Interpolated,
+    /// Blend towards the border height in proportion to the distance into the margin, reaching
+    /// it exactly at the edge.
+    Linear,
+    /// As `Linear`, but easing in and out so that the terrain meets the margin without a crease.
+    Smooth,
+}
+
+impl Falloff {
+    /// How much of the border height replaces the terrain `t` of the way across the margin.
+    fn weight(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Falloff::Interpolated => 0.0,
+            Falloff::Linear => t,
+            Falloff::Smooth => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FalloffError;
+
+impl fmt::Display for FalloffError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Falloff must be one of interpolated, linear or smooth")
+    }
+}
+
+impl std::error::Error for FalloffError {}
+
+impl fmt::Display for Falloff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Falloff::Interpolated => write!(f, "interpolated"),
+            Falloff::Linear => write!(f, "linear"),
+            Falloff::Smooth => write!(f, "smooth"),
+        }
+    }
+}
+
+impl FromStr for Falloff {
+    type Err = FalloffError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "interpolated" => Ok(Falloff::Interpolated),
+            "linear" => Ok(Falloff::Linear),
+            "smooth" => Ok(Falloff::Smooth),
+            _ => Err(FalloffError),
+        }
+    }
+}
+
+/// Margin around the control points of a map.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Border {
+    /// Width of the margin on each side, as a fraction of the extent of the control points along
+    /// that axis. No margin at all when zero.
+    pub padding: f32,
+    /// Elevation the terrain is brought to at the edge of the map.
+    pub height: f32,
+    pub falloff: Falloff,
+}
+
+impl Default for Border {
+    fn default() -> Self {
+        Self {
+            padding: DEFAULT_PADDING,
+            height: 0.0,
+            falloff: Falloff::Interpolated,
+        }
+    }
+}
+
+impl Border {
+    /// No margin: the map ends at its outermost control points.
+    pub fn none() -> Self {
+        Self {
+            padding: 0.0,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.padding <= 0.0
+    }
+
+    /// Blend the samples of `heightfield` outside `inner`, the extent of the control points,
+    /// towards the border height along the falloff curve.
+    pub fn apply(&self, heightfield: &mut Heightfield, inner: Rectangle) {
+        if self.is_none() || self.falloff == Falloff::Interpolated {
+            return;
+        }
+        let base = heightfield.base;
+        let (inner_x, inner_y) = (*inner.origin.x(), *inner.origin.y());
+        let (base_x, base_y) = (*base.origin.x(), *base.origin.y());
+        // Width of the margin on each side
+        let west = inner_x - base_x;
+        let east = base_x + base.x_size - (inner_x + inner.x_size);
+        let south = inner_y - base_y;
+        let north = base_y + base.y_size - (inner_y + inner.y_size);
+        let across = |outside: f32, width: f32| {
+            if outside > 0.0 && width > 0.0 {
+                outside / width
+            } else {
+                0.0
+            }
+        };
+
+        let columns = heightfield.columns();
+        for row in 0..heightfield.rows() {
+            for column in 0..columns {
+                let position = heightfield.position(column, row);
+                let (x, y) = (*position.x(), *position.y());
+                let t = across(inner_x - x, west)
+                    .max(across(x - (inner_x + inner.x_size), east))
+                    .max(across(inner_y - y, south))
+                    .max(across(y - (inner_y + inner.y_size), north));
+                let weight = self.falloff.weight(t);
+                let height = &mut heightfield.heights_mut()[row * columns + column];
+                *height += (self.height - *height) * weight;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::Coord;
+
+    #[test]
+    fn falloff_reaches_the_border_height_at_the_edge() {
+        // A plateau at 30 around (50, 50), with a margin of 25 on every side
+        let heightfield = Heightfield::from_heights(
+            Rectangle {
+                origin: Coord::new(0.0, 0.0, 0.0),
+                x_size: 100.0,
+                y_size: 100.0,
+            },
+            11,
+            11,
+            vec![30.0; 121],
+        );
+        let inner = Rectangle {
+            origin: Coord::new(25.0, 25.0, 0.0),
+            x_size: 50.0,
+            y_size: 50.0,
+        };
+
+        for &falloff in [Falloff::Linear, Falloff::Smooth].iter() {
+            let mut blended = heightfield.clone();
+            let border = Border {
+                padding: 0.5,
+                height: -10.0,
+                falloff,
+            };
+            border.apply(&mut blended, inner);
+            assert_eq!(blended.height(0, 5), -10.0);
+            assert_eq!(blended.height(10, 10), -10.0);
+            assert_eq!(blended.height(5, 5), 30.0);
+            assert_eq!(blended.height(3, 5), 30.0);
+            let halfway = blended.height(5, 1);
+            assert!(halfway > -10.0 && halfway < 30.0);
+        }
+
+        let mut untouched = heightfield.clone();
+        Border::default().apply(&mut untouched, inner);
+        assert_eq!(untouched.heights(), heightfield.heights());
+    }
+
+    #[test]
+    fn falloff_is_read_case_insensitively() {
+        assert_eq!("Smooth".parse(), Ok(Falloff::Smooth));
+        assert_eq!("linear".parse(), Ok(Falloff::Linear));
+        assert_eq!("interpolated".parse(), Ok(Falloff::Interpolated));
+        assert_eq!("cubic".parse::<Falloff>(), Err(FalloffError));
+        assert_eq!(Falloff::Smooth.to_string().parse(), Ok(Falloff::Smooth));
+    }
+}
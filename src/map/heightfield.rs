@@ -0,0 +1,597 @@
+use super::Rectangle;
+use crate::delauney::predicates::segments_cross;
+use crate::render::{Coord, Indices};
+
+/// Exponent applied to distances when weighting control points. Higher values make the surface
+/// flatten out around each control point, lower values blend neighbouring points together more.
+pub const IDW_POWER: f32 = 2.0;
+
+/// Terrain elevation sampled on a regular grid covering `base`.
+///
+/// Heights are stored row by row, starting at `base.origin`, with `columns` samples along the x
+/// axis and `rows` samples along the y axis.
+#[derive(Clone, Debug)]
+pub struct Heightfield {
+    pub base: Rectangle,
+    columns: usize,
+    rows: usize,
+    heights: Vec<f32>,
+}
+
+impl Heightfield {
+    /// Resample sparse control points onto a grid with `resolution` cells along the longest side
+    /// of `base`, using inverse distance weighting. The shorter side gets as many cells as keeps
+    /// them closest to square.
+    pub fn new(points: &[Coord], base: Rectangle, resolution: usize) -> Self {
+        Heightfield::with_breaklines(points, &[], base, resolution)
+    }
+
+    /// Resample control points as `new` does, without smoothing across breaklines: segments
+    /// between two of the `points`, given by their indices.
+    ///
+    /// A sample is only influenced by the points it can see without looking across a breakline.
+    /// Each breakline it can see also counts as a control point where it passes closest to the
+    /// sample, at the height along the breakline there. Ridges and levees along breaklines keep
+    /// their height between control points, and cliffs stay sharp.
+    pub fn with_breaklines(
+        points: &[Coord],
+        breaklines: &[[usize; 2]],
+        base: Rectangle,
+        resolution: usize,
+    ) -> Self {
+        let (columns, rows) = grid_size(&base, resolution);
+
+        let mut heightfield = Self {
+            base,
+            columns,
+            rows,
+            heights: vec![0.0; columns * rows],
+        };
+        for row in 0..rows {
+            for column in 0..columns {
+                let position = heightfield.position(column, row);
+                let (x, y) = (*position.x(), *position.y());
+                heightfield.heights[row * columns + column] = if breaklines.is_empty() {
+                    inverse_distance_weighting(points, x, y)
+                } else {
+                    let visible = visible_points(points, breaklines, x, y);
+                    if visible.is_empty() {
+                        inverse_distance_weighting(points, x, y)
+                    } else {
+                        inverse_distance_weighting(&visible, x, y)
+                    }
+                };
+            }
+        }
+        heightfield
+    }
+
+    /// Build a heightfield from already sampled `heights`, laid out row by row.
+    pub fn from_heights(base: Rectangle, columns: usize, rows: usize, heights: Vec<f32>) -> Self {
+        assert!(columns >= 2 && rows >= 2);
+        assert_eq!(heights.len(), columns * rows);
+        Self {
+            base,
+            columns,
+            rows,
+            heights,
+        }
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn heights(&self) -> &[f32] {
+        &self.heights
+    }
+
+    /// Lowest and highest heights of the grid.
+    pub fn range(&self) -> (f32, f32) {
+        self.heights.iter().fold(
+            (f32::INFINITY, f32::NEG_INFINITY),
+            |(lowest, highest), &height| (lowest.min(height), highest.max(height)),
+        )
+    }
+
+    pub fn heights_mut(&mut self) -> &mut [f32] {
+        &mut self.heights
+    }
+
+    /// Distance between two neighbouring samples along the x axis.
+    pub fn x_step(&self) -> f32 {
+        self.base.x_size / (self.columns - 1) as f32
+    }
+
+    /// Distance between two neighbouring samples along the y axis.
+    pub fn y_step(&self) -> f32 {
+        self.base.y_size / (self.rows - 1) as f32
+    }
+
+    pub fn height(&self, column: usize, row: usize) -> f32 {
+        self.heights[row * self.columns + column]
+    }
+
+    /// Grid coordinates of the sample closest to (`x`, `y`), clamped to the grid.
+    pub fn nearest(&self, x: f32, y: f32) -> (usize, usize) {
+        let column = ((x - self.base.origin.x()) / self.x_step()).round();
+        let row = ((y - self.base.origin.y()) / self.y_step()).round();
+        (
+            column.clamp(0.0, (self.columns - 1) as f32) as usize,
+            row.clamp(0.0, (self.rows - 1) as f32) as usize,
+        )
+    }
+
+    /// The grid triangle containing (`x`, `y`), as three samples by (`column`, `row`) with the
+    /// barycentric weight of each, or `None` outside the grid. The grid is its own spatial index,
+    /// and triangles are split the same way as by `indices`.
+    pub fn barycentric(&self, x: f32, y: f32) -> Option<[((usize, usize), f32); 3]> {
+        let (column, row, u, v) = self.cell_at(x, y)?;
+        Some(if u + v <= 1.0 {
+            [
+                ((column, row), 1.0 - u - v),
+                ((column + 1, row), u),
+                ((column, row + 1), v),
+            ]
+        } else {
+            [
+                ((column + 1, row), 1.0 - v),
+                ((column + 1, row + 1), u + v - 1.0),
+                ((column, row + 1), 1.0 - u),
+            ]
+        })
+    }
+
+    /// Terrain elevation at (`x`, `y`), interpolated linearly over the containing triangle.
+    pub fn height_at(&self, x: f32, y: f32) -> Option<f32> {
+        let samples = self.barycentric(x, y)?;
+        Some(
+            samples
+                .iter()
+                .map(|&((column, row), weight)| weight * self.height(column, row))
+                .sum(),
+        )
+    }
+
+    /// Rate of change of the elevation along the x and y axes at (`x`, `y`). It is constant over
+    /// each triangle of the grid.
+    pub fn gradient_at(&self, x: f32, y: f32) -> Option<(f32, f32)> {
+        let (column, row, u, v) = self.cell_at(x, y)?;
+        let (x_step, y_step) = (self.x_step(), self.y_step());
+        Some(if u + v <= 1.0 {
+            let bottom_left = self.height(column, row);
+            (
+                (self.height(column + 1, row) - bottom_left) / x_step,
+                (self.height(column, row + 1) - bottom_left) / y_step,
+            )
+        } else {
+            let top_right = self.height(column + 1, row + 1);
+            (
+                (top_right - self.height(column, row + 1)) / x_step,
+                (top_right - self.height(column + 1, row)) / y_step,
+            )
+        })
+    }
+
+    /// Upward unit normal of the terrain at (`x`, `y`).
+    pub fn normal_at(&self, x: f32, y: f32) -> Option<Coord> {
+        let (dx, dy) = self.gradient_at(x, y)?;
+        let length = (dx * dx + dy * dy + 1.0).sqrt();
+        Some(Coord::new(-dx / length, -dy / length, 1.0 / length))
+    }
+
+    /// Steepness of the terrain at (`x`, `y`), as the rise over run along the steepest direction.
+    pub fn slope_at(&self, x: f32, y: f32) -> Option<f32> {
+        let (dx, dy) = self.gradient_at(x, y)?;
+        Some((dx * dx + dy * dy).sqrt())
+    }
+
+    /// Bottom left sample of the grid cell containing (`x`, `y`), and the offset of the point
+    /// into that cell as fractions of its width and depth.
+    fn cell_at(&self, x: f32, y: f32) -> Option<(usize, usize, f32, f32)> {
+        let (column, u) = locate((x - self.base.origin.x()) / self.x_step(), self.columns)?;
+        let (row, v) = locate((y - self.base.origin.y()) / self.y_step(), self.rows)?;
+        Some((column, row, u, v))
+    }
+
+    /// World position of the sample at (`column`, `row`), including its elevation.
+    pub fn position(&self, column: usize, row: usize) -> Coord {
+        Coord::new(
+            *self.base.origin.x() + column as f32 * self.x_step(),
+            *self.base.origin.y() + row as f32 * self.y_step(),
+            self.heights[row * self.columns + column],
+        )
+    }
+
+    /// Interpolate the heightfield onto a grid over the same base, with `resolution` cells along
+    /// its longest side.
+    pub fn resample(&self, resolution: usize) -> Heightfield {
+        let base = self.base;
+        let (columns, rows) = grid_size(&base, resolution);
+        let (x_end, y_end) = (
+            *base.origin.x() + base.x_size,
+            *base.origin.y() + base.y_size,
+        );
+
+        let mut resampled =
+            Heightfield::from_heights(base, columns, rows, vec![0.0; columns * rows]);
+        for row in 0..rows {
+            for column in 0..columns {
+                let position = resampled.position(column, row);
+                // Clamp against rounding just past the far edges
+                let (x, y) = (position.x().min(x_end), position.y().min(y_end));
+                resampled.heights[row * columns + column] =
+                    self.height_at(x, y).unwrap_or_default();
+            }
+        }
+        resampled
+    }
+
+    /// One vertex per sample, in the same order as `heights`, with the normal of the terrain
+    /// around it.
+    pub fn vertices(&self) -> Vec<Coord> {
+        let normals = self.normals(&self.heights);
+        let mut vertices = Vec::with_capacity(self.heights.len());
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let mut vertex = self.position(column, row);
+                vertex.normal = normals[row * self.columns + column];
+                vertices.push(vertex);
+            }
+        }
+        vertices
+    }
+
+    /// Upward unit normal at every sample of a surface of `heights` over the grid, such as the
+    /// terrain or the water on top of it. Slopes are taken between the neighbours on either side,
+    /// or between the sample and its only neighbour along the edges.
+    pub fn normals(&self, heights: &[f32]) -> Vec<(f32, f32, f32)> {
+        let (columns, rows) = (self.columns, self.rows);
+        let height = |column: usize, row: usize| heights[row * columns + column];
+        let mut normals = Vec::with_capacity(heights.len());
+        for row in 0..rows {
+            let (south, north) = (row.saturating_sub(1), (row + 1).min(rows - 1));
+            for column in 0..columns {
+                let (west, east) = (column.saturating_sub(1), (column + 1).min(columns - 1));
+                let dx = (height(east, row) - height(west, row))
+                    / ((east - west) as f32 * self.x_step());
+                let dy = (height(column, north) - height(column, south))
+                    / ((north - south) as f32 * self.y_step());
+                let length = (dx * dx + dy * dy + 1.0).sqrt();
+                normals.push((-dx / length, -dy / length, 1.0 / length));
+            }
+        }
+        normals
+    }
+
+    /// Two triangles per grid cell, indexing into `vertices`.
+    pub fn indices(&self) -> Indices {
+        let mut indices =
+            Indices::with_capacity(self.heights.len(), (self.columns - 1) * (self.rows - 1) * 6);
+        for row in 0..self.rows - 1 {
+            for column in 0..self.columns - 1 {
+                let bottom_left = row * self.columns + column;
+                let bottom_right = bottom_left + 1;
+                let top_left = bottom_left + self.columns;
+                let top_right = top_left + 1;
+
+                for &index in [bottom_left, bottom_right, top_left].iter() {
+                    indices.push(index);
+                }
+                for &index in [bottom_right, top_right, top_left].iter() {
+                    indices.push(index);
+                }
+            }
+        }
+        indices
+    }
+}
+
+/// Columns and rows of samples in a grid over `base` with `resolution` square cells along its
+/// longest side.
+fn grid_size(base: &Rectangle, resolution: usize) -> (usize, usize) {
+    let cell_size = base.x_size.max(base.y_size) / resolution.max(1) as f32;
+    let columns = ((base.x_size / cell_size).round() as usize).max(1) + 1;
+    let rows = ((base.y_size / cell_size).round() as usize).max(1) + 1;
+    (columns, rows)
+}
+
+/// Cell along one axis of a grid with `samples` samples containing the fractional sample position
+/// `position`, and the offset of `position` into that cell.
+fn locate(position: f32, samples: usize) -> Option<(usize, f32)> {
+    let last = (samples - 1) as f32;
+    if !(0.0..=last).contains(&position) {
+        return None;
+    }
+    let cell = (position.floor() as usize).min(samples - 2);
+    Some((cell, position - cell as f32))
+}
+
+fn inverse_distance_weighting(points: &[Coord], x: f32, y: f32) -> f32 {
+    let mut weighted_sum = 0.0;
+    let mut total_weight = 0.0;
+    for point in points.iter() {
+        let distance_squared = (point.x() - x).powi(2) + (point.y() - y).powi(2);
+        if distance_squared < f32::EPSILON {
+            return *point.z();
+        }
+        let weight = 1.0 / distance_squared.powf(IDW_POWER / 2.0);
+        weighted_sum += weight * point.z();
+        total_weight += weight;
+    }
+    if total_weight == 0.0 {
+        0.0
+    } else {
+        weighted_sum / total_weight
+    }
+}
+
+/// Control points that can be seen from (`x`, `y`) without looking across a breakline, along
+/// with the closest point of every breakline that can be seen.
+fn visible_points(points: &[Coord], breaklines: &[[usize; 2]], x: f32, y: f32) -> Vec<Coord> {
+    let position = |point: &Coord| (*point.x() as f64, *point.y() as f64);
+    let sample = (x as f64, y as f64);
+    let can_see = |point: &Coord| {
+        breaklines.iter().all(|&[a, b]| {
+            !segments_cross(
+                sample,
+                position(point),
+                position(&points[a]),
+                position(&points[b]),
+            )
+        })
+    };
+
+    let mut visible: Vec<Coord> = points.iter().copied().filter(can_see).collect();
+    for &[a, b] in breaklines.iter() {
+        let closest = closest_on_segment(&points[a], &points[b], x, y);
+        if can_see(&closest) {
+            visible.push(closest);
+        }
+    }
+    visible
+}
+
+/// Point of the segment from `a` to `b` closest to (`x`, `y`), with its height interpolated along
+/// the segment.
+fn closest_on_segment(a: &Coord, b: &Coord, x: f32, y: f32) -> Coord {
+    let (dx, dy) = (b.x() - a.x(), b.y() - a.y());
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((x - a.x()) * dx + (y - a.y()) * dy) / length_squared).clamp(0.0, 1.0)
+    };
+    Coord::new(a.x() + t * dx, a.y() + t * dy, a.z() + t * (b.z() - a.z()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square_base(size: f32) -> Rectangle {
+        Rectangle {
+            origin: Coord::new(0.0, 0.0, 0.0),
+            x_size: size,
+            y_size: size,
+        }
+    }
+
+    #[test]
+    fn grid_has_requested_resolution() {
+        let points = vec![Coord::new(50.0, 50.0, 10.0)];
+        let heightfield = Heightfield::new(&points, square_base(100.0), 10);
+
+        assert_eq!(heightfield.columns(), 11);
+        assert_eq!(heightfield.rows(), 11);
+        assert_eq!(heightfield.heights().len(), 121);
+        assert_eq!(heightfield.vertices().len(), 121);
+        assert_eq!(heightfield.indices().len(), 10 * 10 * 6);
+    }
+
+    #[test]
+    fn breaklines_keep_their_height_between_points() {
+        // A levee at height 40 from west to east, between lowlands at height 0
+        let points = vec![
+            Coord::new(0.0, 50.0, 40.0),
+            Coord::new(100.0, 50.0, 40.0),
+            Coord::new(50.0, 0.0, 0.0),
+            Coord::new(50.0, 100.0, 0.0),
+        ];
+        let smooth = Heightfield::new(&points, square_base(100.0), 10);
+        let levee = Heightfield::with_breaklines(&points, &[[0, 1]], square_base(100.0), 10);
+
+        // Without the breakline the middle of the levee sags to half its height
+        assert!(smooth.height(5, 5) < 25.0);
+        for column in 0..=10 {
+            assert!((levee.height(column, 5) - 40.0).abs() < 1e-3);
+        }
+        // Further away the lowlands still slope down from the levee
+        assert!(levee.height(5, 0) < levee.height(5, 3));
+        assert!(levee.height(5, 3) < 40.0);
+    }
+
+    #[test]
+    fn breaklines_keep_cliffs_sharp() {
+        // High ground to the north of a cliff edge, low ground to the south of its foot. Both run
+        // past the sides of the grid, so nothing can be seen around their ends
+        let points = vec![
+            Coord::new(-10.0, 55.0, 50.0),
+            Coord::new(110.0, 55.0, 50.0),
+            Coord::new(-10.0, 45.0, 0.0),
+            Coord::new(110.0, 45.0, 0.0),
+            Coord::new(50.0, 100.0, 50.0),
+            Coord::new(50.0, 0.0, 0.0),
+        ];
+        let cliff =
+            Heightfield::with_breaklines(&points, &[[0, 1], [2, 3]], square_base(100.0), 20);
+
+        for column in 0..=20 {
+            assert!((cliff.height(column, 12) - 50.0).abs() < 1e-3);
+            assert!(cliff.height(column, 8).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn large_grids_use_wide_indices() {
+        let (columns, rows) = (400, 200);
+        let heightfield =
+            Heightfield::from_heights(square_base(100.0), columns, rows, vec![0.0; columns * rows]);
+        let indices = heightfield.indices();
+
+        assert!(matches!(indices, Indices::U32(_)));
+        assert_eq!(indices.len(), (columns - 1) * (rows - 1) * 6);
+        assert_eq!(indices.iter().max(), Some(columns * rows - 1));
+        // The last cell is made of the last four vertices
+        let last: Vec<usize> = indices.iter().skip(indices.len() - 6).collect();
+        assert_eq!(
+            last,
+            vec![
+                79_598, 79_599, 79_998, //
+                79_599, 79_999, 79_998
+            ]
+        );
+    }
+
+    #[test]
+    fn rectangular_base_keeps_cells_square() {
+        let points = vec![Coord::new(0.0, 0.0, 0.0)];
+        let base = Rectangle {
+            origin: Coord::new(-10.0, 20.0, 0.0),
+            x_size: 200.0,
+            y_size: 100.0,
+        };
+        let heightfield = Heightfield::new(&points, base, 20);
+
+        assert_eq!(heightfield.columns(), 21);
+        assert_eq!(heightfield.rows(), 11);
+        assert_eq!(heightfield.x_step(), heightfield.y_step());
+        assert_eq!(heightfield.position(20, 10), Coord::new(190.0, 120.0, 0.0));
+    }
+
+    #[test]
+    fn nearest_sample_is_clamped_to_grid() {
+        let points = vec![Coord::new(0.0, 0.0, 0.0)];
+        let heightfield = Heightfield::new(&points, square_base(100.0), 10);
+
+        assert_eq!(heightfield.nearest(52.0, 19.0), (5, 2));
+        assert_eq!(heightfield.nearest(-30.0, 500.0), (0, 10));
+    }
+
+    #[test]
+    fn samples_pass_through_control_points() {
+        let points = vec![
+            Coord::new(0.0, 0.0, 0.0),
+            Coord::new(50.0, 50.0, 80.0),
+            Coord::new(100.0, 100.0, 0.0),
+        ];
+        let heightfield = Heightfield::new(&points, square_base(100.0), 10);
+
+        assert_eq!(heightfield.height(5, 5), 80.0);
+        assert_eq!(heightfield.height(0, 0), 0.0);
+        let between = heightfield.height(3, 3);
+        assert!(between > 0.0 && between < 80.0);
+    }
+
+    #[test]
+    fn interpolation_stays_within_control_range() {
+        let points = vec![
+            Coord::new(10.0, 10.0, -20.0),
+            Coord::new(90.0, 30.0, 60.0),
+            Coord::new(40.0, 80.0, 35.0),
+        ];
+        let heightfield = Heightfield::new(&points, square_base(100.0), 25);
+
+        for height in heightfield.heights() {
+            assert!(*height >= -20.0 && *height <= 60.0);
+        }
+    }
+
+    #[test]
+    fn planes_are_sampled_exactly() {
+        // z = 2x + 0.5y + 3 on a 10 by 10 grid of 10 unit cells
+        let heights = (0..11)
+            .flat_map(|row| {
+                (0..11).map(move |column| 20.0 * column as f32 + 5.0 * row as f32 + 3.0)
+            })
+            .collect();
+        let heightfield = Heightfield::from_heights(square_base(100.0), 11, 11, heights);
+
+        for &(x, y) in [(0.0, 0.0), (12.5, 71.0), (43.0, 47.0), (100.0, 100.0)].iter() {
+            let height = heightfield.height_at(x, y).unwrap();
+            assert!((height - (2.0 * x + 0.5 * y + 3.0)).abs() < 1e-3);
+            let (dx, dy) = heightfield.gradient_at(x, y).unwrap();
+            assert!((dx - 2.0).abs() < 1e-4 && (dy - 0.5).abs() < 1e-4);
+        }
+        let slope = heightfield.slope_at(50.0, 50.0).unwrap();
+        assert!((slope - 4.25f32.sqrt()).abs() < 1e-4);
+        let normal = heightfield.normal_at(50.0, 50.0).unwrap();
+        let length = (normal.x().powi(2) + normal.y().powi(2) + normal.z().powi(2)).sqrt();
+        assert!((length - 1.0).abs() < 1e-5);
+        assert!(*normal.x() < 0.0 && *normal.y() < 0.0 && *normal.z() > 0.0);
+        // Every vertex of a plane faces the same way as the plane, along the edges too
+        for vertex in heightfield.vertices().iter() {
+            let (x, y, z) = vertex.normal;
+            assert!((x - normal.x()).abs() < 1e-5);
+            assert!((y - normal.y()).abs() < 1e-5);
+            assert!((z - normal.z()).abs() < 1e-5);
+        }
+
+        assert_eq!(heightfield.height_at(-0.1, 50.0), None);
+        assert_eq!(heightfield.slope_at(50.0, 100.5), None);
+        assert_eq!(heightfield.height_at(f32::NAN, 50.0), None);
+    }
+
+    #[test]
+    fn barycentric_weights_follow_the_mesh_triangles() {
+        let heights = vec![0.0, 10.0, 20.0, 50.0];
+        let heightfield = Heightfield::from_heights(square_base(10.0), 2, 2, heights);
+
+        // Lower left triangle only uses the three corners it shares with the mesh
+        let lower = heightfield.barycentric(2.0, 3.0).unwrap();
+        assert_eq!(
+            lower.iter().map(|sample| sample.0).collect::<Vec<_>>(),
+            vec![(0, 0), (1, 0), (0, 1)]
+        );
+        assert!((lower.iter().map(|sample| sample.1).sum::<f32>() - 1.0).abs() < 1e-6);
+        assert!((heightfield.height_at(2.0, 3.0).unwrap() - 8.0).abs() < 1e-4);
+
+        let upper = heightfield.barycentric(8.0, 9.0).unwrap();
+        assert!(upper.iter().any(|sample| sample.0 == (1, 1)));
+        assert!((heightfield.height_at(10.0, 10.0).unwrap() - 50.0).abs() < 1e-4);
+        // On the diagonal, both triangles agree
+        assert!((heightfield.height_at(5.0, 5.0).unwrap() - 15.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn resampling_interpolates_onto_a_coarser_grid() {
+        // A ramp rising one unit per unit towards the east, 100 by 50
+        let heights = (0..51)
+            .flat_map(|_| (0..101).map(|column| column as f32))
+            .collect();
+        let heightfield = Heightfield::from_heights(
+            Rectangle {
+                origin: Coord::new(-50.0, 10.0, 0.0),
+                x_size: 100.0,
+                y_size: 50.0,
+            },
+            101,
+            51,
+            heights,
+        );
+        let coarse = heightfield.resample(10);
+
+        assert_eq!((coarse.columns(), coarse.rows()), (11, 6));
+        assert_eq!(coarse.base, heightfield.base);
+        for row in 0..coarse.rows() {
+            for column in 0..coarse.columns() {
+                let expected = column as f32 * 10.0;
+                assert!((coarse.height(column, row) - expected).abs() < 1e-3);
+            }
+        }
+    }
+}
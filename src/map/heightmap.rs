@@ -0,0 +1,184 @@
+//! Reading terrain from greyscale heightmap images, such as DEM exports or hand painted terrain.
+//!
+//! Every pixel becomes a sample of the heightfield, with black at height zero and white at the
+//! height scale, whether the image has 8 or 16 bits per channel. Colour images are read by their
+//! luminance. The first row of the image is the north edge of the map, as in the PGM frames
+//! written by `mod1-sim`.
+
+use super::units::Normalisation;
+use super::{Heightfield, Rectangle};
+use crate::render::Coord;
+use image::{ColorType, GenericImageView, ImageError};
+use std::fmt;
+use std::path::Path;
+
+/// Extensions of the image files read as heightmaps rather than as map files.
+pub const HEIGHTMAP_EXTENSIONS: [&str; 3] = ["png", "tif", "tiff"];
+
+/// How the pixels of a heightmap are turned into terrain.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeightmapOptions {
+    /// Distance between two neighbouring pixels.
+    pub spacing: f32,
+    /// Height of a white pixel. The default makes each grey level of an 8 bit image one unit.
+    pub height_scale: f32,
+    /// Number of cells along the longest side of the map, or `None` for one sample per pixel.
+    pub resolution: Option<usize>,
+    pub normalisation: Normalisation,
+}
+
+impl Default for HeightmapOptions {
+    fn default() -> Self {
+        Self {
+            spacing: 1.0,
+            height_scale: 255.0,
+            resolution: None,
+            normalisation: Normalisation::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum HeightmapError {
+    /// The image could not be read or decoded.
+    Image { file: String, error: ImageError },
+    /// An image less than two pixels wide or high, which has no cells.
+    TooSmall { file: String },
+}
+
+impl fmt::Display for HeightmapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeightmapError::Image { file, error } => write!(f, "{}: {}", file, error),
+            HeightmapError::TooSmall { file } => {
+                write!(f, "{}: heightmap must be at least 2 by 2 pixels", file)
+            }
+        }
+    }
+}
+
+impl std::error::Error for HeightmapError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HeightmapError::Image { error, .. } => Some(error),
+            HeightmapError::TooSmall { .. } => None,
+        }
+    }
+}
+
+/// Whether `path` is named like an image to be read as a heightmap.
+pub fn is_heightmap<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref()
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| {
+            HEIGHTMAP_EXTENSIONS
+                .iter()
+                .any(|known| extension.eq_ignore_ascii_case(known))
+        })
+        .unwrap_or(false)
+}
+
+/// Read the heightmap at `path` as a heightfield, with the spacing and height scale of `options`
+/// but before any normalisation or resampling.
+pub fn load<P: AsRef<Path>>(
+    path: P,
+    options: &HeightmapOptions,
+) -> Result<Heightfield, HeightmapError> {
+    let file = path.as_ref().display().to_string();
+    let image = image::open(&path).map_err(|error| HeightmapError::Image {
+        file: file.clone(),
+        error,
+    })?;
+    let (columns, rows) = (image.width() as usize, image.height() as usize);
+    if columns < 2 || rows < 2 {
+        return Err(HeightmapError::TooSmall { file });
+    }
+    // Grey levels row by row from the top of the image, and the level of white
+    let (levels, white): (Vec<f32>, f32) = match image.color() {
+        ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => {
+            let levels = image.into_luma16().into_raw();
+            (levels.into_iter().map(f32::from).collect(), u16::MAX as f32)
+        }
+        _ => {
+            let levels = image.into_luma8().into_raw();
+            (levels.into_iter().map(f32::from).collect(), u8::MAX as f32)
+        }
+    };
+
+    let mut heights = Vec::with_capacity(columns * rows);
+    for row in levels.chunks(columns).rev() {
+        heights.extend(row.iter().map(|level| level * options.height_scale / white));
+    }
+    let base = Rectangle {
+        origin: Coord::new(0.0, 0.0, 0.0),
+        x_size: (columns - 1) as f32 * options.spacing,
+        y_size: (rows - 1) as f32 * options.spacing,
+    };
+    Ok(Heightfield::from_heights(base, columns, rows, heights))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, ImageBuffer, Luma};
+    use std::path::PathBuf;
+
+    fn temporary(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("mod1-heightmap-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn eight_bit_levels_are_heights_by_default() -> Result<(), HeightmapError> {
+        // Dark in the south west, bright in the north east
+        let image = GrayImage::from_fn(3, 2, |x, y| Luma([(x * 10 + (1 - y) * 100) as u8]));
+        let path = temporary("8bit.png");
+        image.save(&path).unwrap();
+
+        let heightfield = load(&path, &HeightmapOptions::default())?;
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((heightfield.columns(), heightfield.rows()), (3, 2));
+        assert_eq!(
+            heightfield.heights(),
+            &[0.0, 10.0, 20.0, 100.0, 110.0, 120.0]
+        );
+        assert_eq!(heightfield.base.x_size, 2.0);
+        Ok(())
+    }
+
+    #[test]
+    fn sixteen_bit_images_keep_their_precision() -> Result<(), HeightmapError> {
+        let image: ImageBuffer<Luma<u16>, Vec<u16>> =
+            ImageBuffer::from_fn(2, 2, |x, _| Luma([if x == 0 { 1 } else { u16::MAX }]));
+        let options = HeightmapOptions {
+            spacing: 30.0,
+            height_scale: 65535.0,
+            ..HeightmapOptions::default()
+        };
+        for name in ["16bit.png", "16bit.tif"].iter() {
+            let path = temporary(name);
+            image.save(&path).unwrap();
+            let heightfield = load(&path, &options)?;
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(heightfield.heights(), &[1.0, 65535.0, 1.0, 65535.0]);
+            assert_eq!(heightfield.base.y_size, 30.0);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn problems_are_reported_with_the_file() {
+        let path = temporary("line.png");
+        GrayImage::new(5, 1).save(&path).unwrap();
+        let error = load(&path, &HeightmapOptions::default()).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(error, HeightmapError::TooSmall { .. }));
+
+        let error = load("src/map/missing.png", &HeightmapOptions::default()).unwrap_err();
+        assert!(error.to_string().starts_with("src/map/missing.png: "));
+
+        assert!(is_heightmap("dem/alps.TIF"));
+        assert!(is_heightmap("painted.png"));
+        assert!(!is_heightmap("src/map/demo_a.mod1"));
+    }
+}
@@ -0,0 +1,316 @@
+//! ESRI ASCII grids, as exported by most GIS software.
+//!
+//! A header of `key value` lines gives `ncols`, `nrows`, the lower left corner as `xllcorner`
+//! and `yllcorner` (or the centre of the lower left cell as `xllcenter` and `yllcenter`),
+//! `cellsize`, and optionally the `nodata_value` marking cells without a height. The heights
+//! follow, row by row from the north, separated by whitespace. Each height becomes a sample of
+//! the heightfield at the centre of its cell.
+//!
+//! Heightfields are written the same way, which requires their samples to be as far apart along
+//! x as along y.
+
+use super::tokens;
+use crate::map::{Heightfield, Location, MapParseError, Rectangle};
+use crate::render::Coord;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+
+const HEADER_KEYS: &str =
+    "\"ncols\", \"nrows\", \"xllcorner\", \"yllcorner\", \"cellsize\" or \"nodata_value\"";
+
+/// Read and parse the grid at `filename`.
+pub fn open_asc(filename: &str) -> Result<Heightfield, MapParseError> {
+    let file = File::open(filename).map_err(|error| MapParseError::Io {
+        file: filename.to_string(),
+        error,
+    })?;
+    read_asc(BufReader::new(file), filename)
+}
+
+/// Parse the grid `reader`, reporting errors as being in `file`.
+///
+/// Cells holding the `nodata_value` are given the height of the lowest cell with data.
+pub fn read_asc<R: BufRead>(reader: R, file: &str) -> Result<Heightfield, MapParseError> {
+    let mut columns = None;
+    let mut rows = None;
+    let mut x = None;
+    let mut y = None;
+    // Whether `x` and `y` are the centre of the lower left cell rather than its corner
+    let mut centered = false;
+    let mut cell_size = None;
+    let mut nodata = None;
+    let mut values = Vec::new();
+    let mut last = Location {
+        file: file.to_string(),
+        line: 1,
+        column: 1,
+    };
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|error| MapParseError::Io {
+            file: file.to_string(),
+            error,
+        })?;
+        let at = |column: usize| Location {
+            file: file.to_string(),
+            line: index + 1,
+            column,
+        };
+        let tokens = tokens(&line);
+        let header = values.is_empty()
+            && tokens
+                .first()
+                .is_some_and(|(_, token)| token.starts_with(char::is_alphabetic));
+        if header {
+            let (key_column, key) = tokens[0];
+            let (value_column, value) = match tokens.get(1) {
+                Some(&(column, value)) => (column, value),
+                None => (key_column + key.chars().count(), ""),
+            };
+            if let Some(&(column, found)) = tokens.get(2) {
+                return Err(MapParseError::Trailing {
+                    location: at(column),
+                    found: found.to_string(),
+                });
+            }
+            let number = || {
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|value| value.is_finite())
+                    .ok_or_else(|| MapParseError::BadFloat {
+                        location: at(value_column),
+                        found: value.to_string(),
+                    })
+            };
+            let count = || match value.parse::<usize>() {
+                Ok(count) if count >= 2 => Ok(count),
+                _ => Err(MapParseError::BadSetting {
+                    location: at(value_column),
+                    expected: "a whole number of at least 2",
+                    found: value.to_string(),
+                }),
+            };
+            match key.to_lowercase().as_str() {
+                "ncols" => columns = Some(count()?),
+                "nrows" => rows = Some(count()?),
+                "xllcorner" => x = Some(number()?),
+                "yllcorner" => y = Some(number()?),
+                "xllcenter" => {
+                    x = Some(number()?);
+                    centered = true;
+                }
+                "yllcenter" => {
+                    y = Some(number()?);
+                    centered = true;
+                }
+                "cellsize" => match number()? {
+                    size if size > 0.0 => cell_size = Some(size),
+                    _ => {
+                        return Err(MapParseError::BadSetting {
+                            location: at(value_column),
+                            expected: "a positive cell size",
+                            found: value.to_string(),
+                        })
+                    }
+                },
+                "nodata_value" => nodata = Some(number()? as f32),
+                _ => {
+                    return Err(MapParseError::BadSetting {
+                        location: at(key_column),
+                        expected: HEADER_KEYS,
+                        found: key.to_string(),
+                    })
+                }
+            }
+            continue;
+        }
+
+        for (column, token) in tokens {
+            if let (Some(columns), Some(rows)) = (columns, rows) {
+                if values.len() == columns * rows {
+                    return Err(MapParseError::Trailing {
+                        location: at(column),
+                        found: token.to_string(),
+                    });
+                }
+            }
+            let value = token
+                .parse::<f32>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| MapParseError::BadFloat {
+                    location: at(column),
+                    found: token.to_string(),
+                })?;
+            values.push(value);
+            last = at(column);
+        }
+    }
+
+    let missing = |key: &'static str| MapParseError::BadSetting {
+        location: last.clone(),
+        expected: key,
+        found: "the end of the header".to_string(),
+    };
+    let columns = columns.ok_or_else(|| missing("a \"ncols\" line"))?;
+    let rows = rows.ok_or_else(|| missing("a \"nrows\" line"))?;
+    let x = x.ok_or_else(|| missing("a \"xllcorner\" line"))?;
+    let y = y.ok_or_else(|| missing("a \"yllcorner\" line"))?;
+    let cell_size = cell_size.ok_or_else(|| missing("a \"cellsize\" line"))?;
+    if values.len() < columns * rows {
+        return Err(MapParseError::Arity {
+            location: last,
+            expected: columns * rows,
+            found: values.len(),
+        });
+    }
+
+    let is_data = |value: &f32| Some(*value) != nodata;
+    let lowest = values
+        .iter()
+        .filter(|value| is_data(value))
+        .cloned()
+        .fold(f32::INFINITY, f32::min);
+    if lowest == f32::INFINITY {
+        return Err(MapParseError::Empty {
+            file: file.to_string(),
+        });
+    }
+    let mut heights = Vec::with_capacity(values.len());
+    for row in values.chunks(columns).rev() {
+        heights.extend(
+            row.iter()
+                .map(|value| if is_data(value) { *value } else { lowest }),
+        );
+    }
+
+    let offset = if centered { 0.0 } else { cell_size / 2.0 };
+    let base = Rectangle {
+        origin: Coord::new((x + offset) as f32, (y + offset) as f32, 0.0),
+        x_size: ((columns - 1) as f64 * cell_size) as f32,
+        y_size: ((rows - 1) as f64 * cell_size) as f32,
+    };
+    Ok(Heightfield::from_heights(base, columns, rows, heights))
+}
+
+/// Write `heightfield` as an ESRI ASCII grid, with a cell centred on every sample.
+pub fn write_asc<W: Write>(writer: &mut W, heightfield: &Heightfield) -> io::Result<()> {
+    let (x_step, y_step) = (heightfield.x_step(), heightfield.y_step());
+    if (x_step - y_step).abs() > x_step.abs().max(y_step.abs()) * 1e-4 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "ASCII grids need samples as far apart along x as along y",
+        ));
+    }
+    let origin = heightfield.base.origin;
+    writeln!(writer, "ncols {}", heightfield.columns())?;
+    writeln!(writer, "nrows {}", heightfield.rows())?;
+    writeln!(writer, "xllcenter {}", origin.x())?;
+    writeln!(writer, "yllcenter {}", origin.y())?;
+    writeln!(writer, "cellsize {}", x_step)?;
+    for row in heightfield.heights().chunks(heightfield.columns()).rev() {
+        let row: Vec<String> = row.iter().map(|height| height.to_string()).collect();
+        writeln!(writer, "{}", row.join(" "))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(text: &str) -> Result<Heightfield, MapParseError> {
+        read_asc(text.as_bytes(), "test.asc")
+    }
+
+    fn message(text: &str) -> String {
+        read(text).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn grids_are_read_with_north_first() {
+        let heightfield = read(
+            "ncols 3\nNROWS 2\nxllcorner 1000\nyllcorner 2000\ncellsize 10\n\
+             NODATA_value -9999\n\
+             10 11 12\n\
+             1 -9999 3\n",
+        )
+        .unwrap();
+
+        assert_eq!((heightfield.columns(), heightfield.rows()), (3, 2));
+        // The southern row comes first, with the missing height filled from the lowest
+        assert_eq!(heightfield.heights(), &[1.0, 1.0, 3.0, 10.0, 11.0, 12.0]);
+        assert_eq!(heightfield.base.origin, Coord::new(1005.0, 2005.0, 0.0));
+        assert_eq!(heightfield.base.x_size, 20.0);
+        assert_eq!(heightfield.base.y_size, 10.0);
+    }
+
+    #[test]
+    fn cell_centres_and_wrapped_rows_are_accepted() {
+        let heightfield =
+            read("ncols 2\nnrows 2\nxllcenter 0\nyllcenter 0\ncellsize 5\n1 2 3\n4\n").unwrap();
+        assert_eq!(heightfield.heights(), &[3.0, 4.0, 1.0, 2.0]);
+        assert_eq!(heightfield.base.origin, Coord::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn written_grids_read_back_the_same() {
+        let base = Rectangle {
+            origin: Coord::new(-50.0, 12.5, 0.0),
+            x_size: 20.0,
+            y_size: 10.0,
+        };
+        let heightfield =
+            Heightfield::from_heights(base, 5, 3, (0..15).map(|i| i as f32 * 0.3).collect());
+        let mut output = Vec::new();
+        write_asc(&mut output, &heightfield).unwrap();
+        let written = read(std::str::from_utf8(&output).unwrap()).unwrap();
+        assert_eq!(written.heights(), heightfield.heights());
+        assert_eq!(written.base, heightfield.base);
+
+        let stretched = Heightfield::from_heights(base, 3, 3, vec![0.0; 9]);
+        assert!(write_asc(&mut Vec::new(), &stretched).is_err());
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        let header = "ncols 2\nnrows 2\nxllcorner 0\nyllcorner 0\ncellsize 1\n";
+        assert_eq!(
+            message(&format!("{}1 2\n3 x\n", header)),
+            "test.asc:7:3: expected number, found \"x\""
+        );
+        assert_eq!(
+            message(&format!("{}1 nan\n3 4\n", header)),
+            "test.asc:6:3: expected number, found \"nan\""
+        );
+        assert_eq!(
+            message("ncols 2\nnrows 2\nxllcorner inf\n"),
+            "test.asc:3:11: expected number, found \"inf\""
+        );
+        assert_eq!(
+            message(&format!("{}1 2\n3\n", header)),
+            "test.asc:7:1: expected 4 values, found 3"
+        );
+        assert_eq!(
+            message(&format!("{}1 2\n3 4 5\n", header)),
+            "test.asc:7:5: expected end of line, found \"5\""
+        );
+        assert_eq!(
+            message("ncols 2\nrows 2\n"),
+            format!("test.asc:2:1: expected {}, found \"rows\"", HEADER_KEYS)
+        );
+        assert_eq!(
+            message("ncols 1\n"),
+            "test.asc:1:7: expected a whole number of at least 2, found \"1\""
+        );
+        assert_eq!(
+            message("ncols 2\nnrows 2\nxllcorner 0\nyllcorner 0\n1 2\n3 4\n"),
+            "test.asc:6:3: expected a \"cellsize\" line, found \"the end of the header\""
+        );
+        assert_eq!(
+            message(&format!("{}nodata_value 0\n0 0\n0 0\n", header)),
+            "test.asc: no points in map file"
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Reading terrain from, and writing it to, formats other programs understand.
+//!
+//! ESRI ASCII grids (`.asc`) are read as heightfields and XYZ point clouds (`.xyz`) as control
+//! points, so that GIS exports can be simulated as they are, and heightfields can be written back
+//! out as ASCII grids. Terrain, and optionally the water on top of it, can be written as Wavefront
+//! OBJ or PLY meshes to be opened in Blender and other modelling tools. Meshes are written in
+//! world coordinates, with z pointing up. Map files can be written back out as `.mod1` files.
+
+pub mod asc;
+pub mod mod1;
+pub mod obj;
+pub mod ply;
+pub mod xyz;
+
+pub use asc::{open_asc, read_asc, write_asc};
+pub use mod1::write_mod1;
+pub use obj::write_obj;
+pub use ply::write_ply;
+pub use xyz::{open_xyz, read_xyz};
+
+use super::heightmap::is_heightmap;
+use super::Heightfield;
+use crate::render::Coord;
+use crate::water::WaterGrid;
+use std::path::Path;
+
+/// Kinds of file terrain can be read from, told apart by their extension.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    /// A `.mod1` map file, or any file with an extension not listed here.
+    Mod1,
+    /// A greyscale PNG or TIFF image.
+    Heightmap,
+    Asc,
+    Xyz,
+}
+
+impl Format {
+    pub fn of<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        match extension.as_str() {
+            "asc" => Format::Asc,
+            "xyz" => Format::Xyz,
+            _ if is_heightmap(path) => Format::Heightmap,
+            _ => Format::Mod1,
+        }
+    }
+}
+
+/// Triangles of a single object, indexing into its own vertices.
+struct Mesh {
+    name: &'static str,
+    vertices: Vec<Coord>,
+    triangles: Vec<[usize; 3]>,
+}
+
+/// The terrain of `heightfield`, followed by the wet part of `water` if given.
+fn meshes(heightfield: &Heightfield, water: Option<&WaterGrid>) -> Vec<Mesh> {
+    let indices = heightfield.indices().iter().collect::<Vec<_>>();
+    let mut meshes = vec![Mesh {
+        name: "terrain",
+        vertices: heightfield.vertices(),
+        triangles: indices
+            .chunks(3)
+            .map(|corners| [corners[0], corners[1], corners[2]])
+            .collect(),
+    }];
+
+    if let Some(water) = water {
+        // Only keep the vertices of the water surface that wet cells use
+        let surface = water.vertices(heightfield);
+        let mut renumbered = vec![None; surface.len()];
+        let mut vertices = Vec::new();
+        let mut triangles = Vec::new();
+        let indices = water.indices(0).iter().collect::<Vec<_>>();
+        for corners in indices.chunks(3) {
+            let mut triangle = [0; 3];
+            for (corner, &index) in triangle.iter_mut().zip(corners.iter()) {
+                *corner = *renumbered[index].get_or_insert_with(|| {
+                    vertices.push(surface[index]);
+                    vertices.len() - 1
+                });
+            }
+            triangles.push(triangle);
+        }
+        meshes.push(Mesh {
+            name: "water",
+            vertices,
+            triangles,
+        });
+    }
+    meshes
+}
+
+/// Values on a line separated by whitespace or commas, with the column each starts at.
+pub(crate) fn tokens(line: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (column, (position, character)) in line.char_indices().enumerate() {
+        let separator = character.is_whitespace() || character == ',';
+        match (start, separator) {
+            (None, false) => start = Some((column + 1, position)),
+            (Some((start_column, start_position)), true) => {
+                tokens.push((start_column, &line[start_position..position]));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    if let Some((start_column, start_position)) = start {
+        tokens.push((start_column, &line[start_position..]));
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Rectangle;
+
+    #[test]
+    fn formats_are_told_apart_by_extension() {
+        assert_eq!(Format::of("dem/alps.ASC"), Format::Asc);
+        assert_eq!(Format::of("survey.xyz"), Format::Xyz);
+        assert_eq!(Format::of("painted.png"), Format::Heightmap);
+        assert_eq!(Format::of("src/map/demo_a.mod1"), Format::Mod1);
+        assert_eq!(Format::of("README"), Format::Mod1);
+    }
+
+    #[test]
+    fn values_are_split_on_whitespace_and_commas() {
+        assert_eq!(
+            tokens("  1.5, 2\t-3 ,,4"),
+            vec![(3, "1.5"), (8, "2"), (10, "-3"), (15, "4")]
+        );
+        assert!(tokens("   ").is_empty());
+    }
+
+    #[test]
+    fn only_wet_water_is_meshed() {
+        let base = Rectangle {
+            origin: Coord::new(0.0, 0.0, 0.0),
+            x_size: 20.0,
+            y_size: 20.0,
+        };
+        let heightfield = Heightfield::from_heights(base, 3, 3, vec![0.0; 9]);
+        let mut water = WaterGrid::new(&heightfield);
+        water.depths_mut()[0] = 1.0;
+
+        let meshes = meshes(&heightfield, Some(&water));
+        assert_eq!(meshes.len(), 2);
+        assert_eq!(meshes[0].vertices.len(), 9);
+        assert_eq!(meshes[0].triangles.len(), 8);
+        // The cell in the south west corner is the only one touching the wet sample
+        assert_eq!(meshes[1].triangles.len(), 2);
+        assert_eq!(meshes[1].vertices.len(), 4);
+        assert!(meshes[1]
+            .vertices
+            .iter()
+            .all(|vertex| vertex.is_water > 0.5));
+    }
+}
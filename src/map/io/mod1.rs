@@ -0,0 +1,142 @@
+//! Writing `.mod1` map files, in the syntax they are read in.
+//!
+//! Files are written canonically: the border and normalisation directives that differ from the
+//! defaults, then the sinks, then every control point on a line of its own, and finally the
+//! breaklines, joined into as few lines as possible. Numbers are written with as many digits as
+//! needed to read them back exactly, so parsing a written file gives back the same map file.
+
+use crate::map::units::DEFAULT_WORLD_SIZE;
+use crate::map::{Border, MapFile, Normalisation};
+use crate::water::Sink;
+use std::io::{self, Write};
+
+/// Write the control points, breaklines, sinks and directives of `map_file` as a `.mod1` file.
+/// Its warnings are left out.
+pub fn write_mod1<W: Write>(writer: &mut W, map_file: &MapFile) -> io::Result<()> {
+    let border = &map_file.border;
+    let default_border = Border::default();
+    if *border == Border::none() {
+        writeln!(writer, "border none")?;
+    } else {
+        if border.padding != default_border.padding {
+            writeln!(writer, "border padding {}", border.padding)?;
+        }
+        if border.height != default_border.height {
+            writeln!(writer, "border height {}", border.height)?;
+        }
+        if border.falloff != default_border.falloff {
+            writeln!(writer, "border falloff {}", border.falloff)?;
+        }
+    }
+
+    let normalisation = &map_file.normalisation;
+    match normalisation.world_size {
+        Some(world_size) if world_size == DEFAULT_WORLD_SIZE => writeln!(writer, "normalise")?,
+        Some(world_size) => writeln!(writer, "normalise {}", world_size)?,
+        None => (),
+    }
+    if normalisation.exaggeration != Normalisation::default().exaggeration {
+        writeln!(writer, "exaggeration {}", normalisation.exaggeration)?;
+    }
+    if let Some(unit) = &normalisation.unit {
+        writeln!(writer, "units {}", unit)?;
+    }
+
+    for sink in &map_file.sinks {
+        match sink {
+            Sink::Edges => writeln!(writer, "sink edges")?,
+            Sink::Point(x, y) => writeln!(writer, "sink ({}, {})", x, y)?,
+        }
+    }
+
+    for point in &map_file.points {
+        writeln!(writer, "({}, {}, {})", point.x(), point.y(), point.z())?;
+    }
+
+    // Points are written again in breaklines, where they are found to be the same as before
+    let point = |index: usize| {
+        let point = &map_file.points[index];
+        format!("({}, {}, {})", point.x(), point.y(), point.z())
+    };
+    let mut end = None;
+    for &[start, next] in &map_file.breaklines {
+        if end == Some(start) {
+            write!(writer, " {}", point(next))?;
+        } else {
+            if end.is_some() {
+                writeln!(writer)?;
+            }
+            write!(writer, "breakline {} {}", point(start), point(next))?;
+        }
+        end = Some(next);
+    }
+    if end.is_some() {
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{Falloff, MapParseError};
+    use crate::render::Coord;
+
+    fn round_trip(map_file: &MapFile) -> Result<MapFile, MapParseError> {
+        let mut output = Vec::new();
+        write_mod1(&mut output, map_file).unwrap();
+        MapFile::parse(output.as_slice(), "written.mod1")
+    }
+
+    #[test]
+    fn demo_maps_survive_a_round_trip() -> Result<(), MapParseError> {
+        let mut count = 0;
+        for entry in std::fs::read_dir("src/map").unwrap() {
+            let path = entry.unwrap().path();
+            let name = path.to_str().unwrap();
+            if !name.contains("demo_") || !name.ends_with(".mod1") {
+                continue;
+            }
+            let mut map_file = MapFile::open(name)?;
+            map_file.warnings.clear();
+            assert_eq!(round_trip(&map_file)?, map_file, "{}", name);
+            count += 1;
+        }
+        assert!(count >= 9);
+        Ok(())
+    }
+
+    #[test]
+    fn every_directive_is_written() -> Result<(), MapParseError> {
+        let map_file = MapFile::parse(
+            "border padding 0.125\nborder height -3.5\nborder falloff smooth\n\
+             normalise 400\nexaggeration 1.5\nunits ft\n\
+             sink edges\nsink (0.1, -2e-7)\n\
+             (0.1, 0.2, 0.3) (-0, 1e9, 16777217)\n\
+             breakline (0,0,1) (5,0,1) (5,5,1)\n\
+             breakline (10,0,1) (10,5,1)\n"
+                .as_bytes(),
+            "test.mod1",
+        )?;
+        let written = round_trip(&map_file)?;
+        assert_eq!(written, map_file);
+        assert_eq!(written.border.falloff, Falloff::Smooth);
+        assert_eq!(written.points[1], Coord::new(0.0, 1e9, 16777216.0));
+
+        let mut output = Vec::new();
+        write_mod1(&mut output, &map_file).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("\nbreakline (0, 0, 1) (5, 0, 1) (5, 5, 1)\n"));
+        assert!(output.ends_with("\nbreakline (10, 0, 1) (10, 5, 1)\n"));
+
+        for border in [Border::none(), Border::default()].iter() {
+            let map_file = MapFile {
+                points: vec![Coord::new(1.0, 2.0, 3.0)],
+                border: *border,
+                ..MapFile::default()
+            };
+            assert_eq!(round_trip(&map_file)?, map_file);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+//! Wavefront OBJ meshes, with the terrain and water as separate objects.
+
+use super::meshes;
+use crate::map::Heightfield;
+use crate::water::WaterGrid;
+use std::io::{self, Write};
+
+/// Write the terrain of `heightfield`, and the wet part of `water` if given, as an OBJ file.
+pub fn write_obj<W: Write>(
+    writer: &mut W,
+    heightfield: &Heightfield,
+    water: Option<&WaterGrid>,
+) -> io::Result<()> {
+    writeln!(writer, "# mod1")?;
+    // Vertices are numbered from 1 across the whole file
+    let mut first = 1;
+    for mesh in meshes(heightfield, water) {
+        writeln!(writer, "o {}", mesh.name)?;
+        for vertex in &mesh.vertices {
+            writeln!(writer, "v {} {} {}", vertex.x(), vertex.y(), vertex.z())?;
+        }
+        for triangle in &mesh.triangles {
+            writeln!(
+                writer,
+                "f {} {} {}",
+                triangle[0] + first,
+                triangle[1] + first,
+                triangle[2] + first
+            )?;
+        }
+        first += mesh.vertices.len();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Rectangle;
+    use crate::render::Coord;
+
+    #[test]
+    fn water_follows_the_terrain_in_one_numbering() -> io::Result<()> {
+        let base = Rectangle {
+            origin: Coord::new(0.0, 0.0, 0.0),
+            x_size: 10.0,
+            y_size: 10.0,
+        };
+        let heightfield = Heightfield::from_heights(base, 2, 2, vec![0.0, 1.0, 2.0, 3.0]);
+        let mut water = WaterGrid::new(&heightfield);
+        water.depths_mut()[0] = 0.5;
+
+        let mut output = Vec::new();
+        write_obj(&mut output, &heightfield, Some(&water))?;
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[1], "o terrain");
+        assert_eq!(
+            &lines[2..6],
+            &["v 0 0 0", "v 10 0 1", "v 0 10 2", "v 10 10 3"]
+        );
+        assert_eq!(lines[8], "o water");
+        assert_eq!(lines[9], "v 0 0 0.5");
+        let faces = lines
+            .iter()
+            .filter(|line| line.starts_with("f "))
+            .collect::<Vec<_>>();
+        assert_eq!(faces.len(), 4);
+        // Every face of the water object refers to its own vertices, after the terrain's four
+        assert!(faces[2..].iter().all(|face| face
+            .split_whitespace()
+            .skip(1)
+            .all(|index| (5..=8).contains(&index.parse::<usize>().unwrap()))));
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+//! ASCII PLY meshes, with a `water` property telling the water surface from the terrain.
+
+use super::meshes;
+use crate::map::Heightfield;
+use crate::water::WaterGrid;
+use std::io::{self, Write};
+
+/// Write the terrain of `heightfield`, and the wet part of `water` if given, as an ASCII PLY file.
+pub fn write_ply<W: Write>(
+    writer: &mut W,
+    heightfield: &Heightfield,
+    water: Option<&WaterGrid>,
+) -> io::Result<()> {
+    let meshes = meshes(heightfield, water);
+    let vertices = meshes.iter().map(|mesh| mesh.vertices.len()).sum::<usize>();
+    let faces = meshes
+        .iter()
+        .map(|mesh| mesh.triangles.len())
+        .sum::<usize>();
+    writeln!(writer, "ply")?;
+    writeln!(writer, "format ascii 1.0")?;
+    writeln!(writer, "comment mod1")?;
+    writeln!(writer, "element vertex {}", vertices)?;
+    writeln!(writer, "property float x")?;
+    writeln!(writer, "property float y")?;
+    writeln!(writer, "property float z")?;
+    writeln!(writer, "property uchar water")?;
+    writeln!(writer, "element face {}", faces)?;
+    writeln!(writer, "property list uchar uint vertex_indices")?;
+    writeln!(writer, "end_header")?;
+
+    for mesh in &meshes {
+        let is_water = (mesh.name == "water") as u8;
+        for vertex in &mesh.vertices {
+            writeln!(
+                writer,
+                "{} {} {} {}",
+                vertex.x(),
+                vertex.y(),
+                vertex.z(),
+                is_water
+            )?;
+        }
+    }
+    // Vertices are numbered from 0 across all meshes
+    let mut first = 0;
+    for mesh in &meshes {
+        for triangle in &mesh.triangles {
+            writeln!(
+                writer,
+                "3 {} {} {}",
+                triangle[0] + first,
+                triangle[1] + first,
+                triangle[2] + first
+            )?;
+        }
+        first += mesh.vertices.len();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Rectangle;
+    use crate::render::Coord;
+
+    #[test]
+    fn header_counts_every_mesh() -> io::Result<()> {
+        let base = Rectangle {
+            origin: Coord::new(0.0, 0.0, 0.0),
+            x_size: 10.0,
+            y_size: 10.0,
+        };
+        let heightfield = Heightfield::from_heights(base, 2, 2, vec![0.0; 4]);
+        let mut water = WaterGrid::new(&heightfield);
+        water.depths_mut()[3] = 2.0;
+
+        let mut output = Vec::new();
+        write_ply(&mut output, &heightfield, Some(&water))?;
+        let output = String::from_utf8(output).unwrap();
+        let (header, body) = output.split_at(output.find("end_header\n").unwrap() + 11);
+
+        assert!(header.starts_with("ply\nformat ascii 1.0\n"));
+        assert!(header.contains("element vertex 8\n"));
+        assert!(header.contains("element face 4\n"));
+        let lines = body.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 12);
+        assert_eq!(lines[0], "0 0 0 0");
+        assert!(lines[4..8].iter().all(|vertex| vertex.ends_with(" 1")));
+        assert!(lines[4..8].contains(&"10 10 2 1"));
+        // The faces of the water refer to the vertices after the terrain's four
+        assert!(lines[10..].iter().all(|face| face
+            .split_whitespace()
+            .skip(1)
+            .all(|index| index.parse::<usize>().unwrap() >= 4)));
+        Ok(())
+    }
+
+    #[test]
+    fn terrain_alone_can_be_written() -> io::Result<()> {
+        let base = Rectangle {
+            origin: Coord::new(0.0, 0.0, 0.0),
+            x_size: 10.0,
+            y_size: 10.0,
+        };
+        let heightfield = Heightfield::from_heights(base, 3, 2, vec![1.0; 6]);
+        let mut output = Vec::new();
+        write_ply(&mut output, &heightfield, None)?;
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("element vertex 6\n"));
+        assert!(output.contains("element face 4\n"));
+        Ok(())
+    }
+}
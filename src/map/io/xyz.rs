@@ -0,0 +1,149 @@
+//! XYZ point clouds: one control point per line as `x y z`, separated by whitespace or commas.
+//!
+//! Blank lines and anything after a `#` are ignored. Points repeated exactly are ignored with a
+//! warning, as in map files.
+
+use super::tokens;
+use crate::map::parse::Seen;
+use crate::map::{Location, MapFile, MapParseError, MapWarning};
+use crate::render::Coord;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+/// Read and parse the point cloud at `filename`.
+pub fn open_xyz(filename: &str) -> Result<MapFile, MapParseError> {
+    let file = File::open(filename).map_err(|error| MapParseError::Io {
+        file: filename.to_string(),
+        error,
+    })?;
+    read_xyz(BufReader::new(file), filename)
+}
+
+/// Parse the point cloud `reader`, reporting errors as being in `file`.
+pub fn read_xyz<R: BufRead>(reader: R, file: &str) -> Result<MapFile, MapParseError> {
+    let mut map_file = MapFile::default();
+    let mut seen = Seen::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|error| MapParseError::Io {
+            file: file.to_string(),
+            error,
+        })?;
+        let at = |column: usize| Location {
+            file: file.to_string(),
+            line: index + 1,
+            column,
+        };
+        let content = line.split('#').next().unwrap_or_default();
+        let tokens = tokens(content);
+        if tokens.is_empty() {
+            continue;
+        }
+        if let Some(&(column, found)) = tokens.get(3) {
+            return Err(MapParseError::Trailing {
+                location: at(column),
+                found: found.to_string(),
+            });
+        }
+        if tokens.len() < 3 {
+            return Err(MapParseError::Arity {
+                location: at(tokens[0].0),
+                expected: 3,
+                found: tokens.len(),
+            });
+        }
+
+        let mut values = [0.0; 3];
+        for (value, &(column, token)) in values.iter_mut().zip(tokens.iter()) {
+            *value = token
+                .parse::<f32>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| MapParseError::BadFloat {
+                    location: at(column),
+                    found: token.to_string(),
+                })?;
+        }
+        let location = at(tokens[0].0);
+        let point = Coord::new(values[0], values[1], values[2]);
+        if let (_, Some(first_line)) = map_file.add_point(&mut seen, point, location.clone())? {
+            map_file.warnings.push(MapWarning::RepeatedPoint {
+                location,
+                first_line,
+            });
+        }
+    }
+
+    if map_file.points.is_empty() {
+        return Err(MapParseError::Empty {
+            file: file.to_string(),
+        });
+    }
+    Ok(map_file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(text: &str) -> Result<MapFile, MapParseError> {
+        read_xyz(text.as_bytes(), "test.xyz")
+    }
+
+    #[test]
+    fn points_are_read_one_per_line() -> Result<(), MapParseError> {
+        let map_file = read(
+            "# survey\n\
+             512000.5 6100000 12.25\n\
+             \n\
+             512010,6100000,13   # comma separated\n\
+             512000.5 6100000 12.25\n",
+        )?;
+        assert_eq!(
+            map_file.points,
+            vec![
+                Coord::new(512000.5, 6100000.0, 12.25),
+                Coord::new(512010.0, 6100000.0, 13.0)
+            ]
+        );
+        assert_eq!(
+            map_file.warnings,
+            vec![MapWarning::RepeatedPoint {
+                location: Location {
+                    file: "test.xyz".to_string(),
+                    line: 5,
+                    column: 1,
+                },
+                first_line: 2,
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn malformed_lines_are_rejected() {
+        let message = |text: &str| read(text).unwrap_err().to_string();
+        assert_eq!(message("1 2\n"), "test.xyz:1:1: expected 3 values, found 2");
+        assert_eq!(
+            message("0 0 0\n1 2 3 4\n"),
+            "test.xyz:2:7: expected end of line, found \"4\""
+        );
+        assert_eq!(
+            message("1 two 3\n"),
+            "test.xyz:1:3: expected number, found \"two\""
+        );
+        assert_eq!(
+            message("0 0 0\n-inf 2 3\n"),
+            "test.xyz:2:1: expected number, found \"-inf\""
+        );
+        assert_eq!(
+            message("1 2 NaN\n"),
+            "test.xyz:1:5: expected number, found \"NaN\""
+        );
+        assert_eq!(
+            message("1 2 3\n1 2 4\n"),
+            "test.xyz:2:1: duplicate point at a different height, already defined on line 1"
+        );
+        assert_eq!(message("# nothing\n"), "test.xyz: no points in map file");
+    }
+}
@@ -1,33 +1,28 @@
-use super::render::Coord;
-use crate::delauney::{delauney_triangulation, Triangle, Vec2};
-use std::fmt;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader};
+pub mod border;
+pub mod heightfield;
+pub mod heightmap;
+pub mod io;
+pub mod parse;
+pub mod units;
 
-macro_rules! get_edge_val {
-    ($minmax:ident, $vec:ident, $axis:ident) => {
-        *$vec
-            .iter()
-            .$minmax(|vertex| *vertex.$axis() as i32)
-            .unwrap()
-            .$axis();
-    };
-}
+pub use border::{Border, Falloff};
+pub use heightfield::Heightfield;
+pub use heightmap::{HeightmapError, HeightmapOptions};
+pub use parse::{Location, MapFile, MapParseError, MapWarning};
+pub use units::{Normalisation, Units};
 
-macro_rules! find_index {
-    ($vertex:ident, $vertices:ident, $triangle:ident) => {
-        $vertices
-            .iter()
-            .position(|&coord| {
-                coord.position.0 == $triangle.$vertex.x && coord.position.1 == $triangle.$vertex.y
-            })
-            .unwrap()
-    };
-}
+use super::delauney::{constrained_delauney_triangulation, Vec2, Voronoi};
+use super::render::{CameraBuilder, CameraMatrix, Coord, Indices};
+use crate::water::Sink;
+use std::fmt;
+use std::path::Path;
 
 const SCALE_FACTOR: f32 = 6.0;
 
-#[derive(Debug)]
+/// Number of heightfield cells along the longest side of the map, unless specified otherwise.
+pub const DEFAULT_RESOLUTION: usize = 100;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Rectangle {
     pub origin: Coord,
     pub x_size: f32,
@@ -35,134 +30,311 @@ pub struct Rectangle {
 }
 
 pub struct Map {
+    /// Control points in world coordinates, followed by the corners of the border if any. Maps
+    /// read from grids, such as heightmaps, have none.
+    pub points: Vec<Coord>,
+    /// Segments between control points that the terrain is not smoothed across, as indices into
+    /// `points`.
+    pub breaklines: Vec<[usize; 2]>,
+    /// Delaunay triangulation of the control points, with every breakline along triangle edges,
+    /// as indices into `points`.
+    pub triangles: Vec<[u32; 3]>,
+    pub sinks: Vec<Sink>,
+    /// Conversion between the coordinates of the map file and the world coordinates everything
+    /// else is in.
+    pub units: Units,
+    /// Problems in the map file that were worked around, such as repeated points.
+    pub warnings: Vec<MapWarning>,
+    pub heightfield: Heightfield,
     pub vertices: Vec<Coord>,
     center: Coord,
-    pub indices: Vec<u16>,
+    pub indices: Indices,
     pub scale: usize,
     pub elevation_max: f32,
     pub base: Rectangle,
+    /// The map file the map was read from, without its warnings, to be written back out. Maps
+    /// read from grids keep only their normalisation.
+    map_file: MapFile,
 }
 
 impl Map {
-    pub fn new_from_file(filename: &str) -> Result<Self, io::Error> {
-        let reader = BufReader::new(File::open(filename)?);
-        let mut vertices: Vec<Coord> = Vec::new();
-        for line in reader.lines() {
-            if let Ok(new_coord) = line.unwrap().parse::<Coord>() {
-                vertices.push(new_coord);
-            } else {
-                return Err(io::Error::new(io::ErrorKind::Other, "Invalid Coord!"));
-            }
-        }
-        let (center, scale, base) = Map::add_edges(&mut vertices);
-        // "Normalize" elevations to
-        // for vertex in vertices.iter_mut() {
-        //     *(*vertex).z_mut() /= 4.0;
-        // }
-        let elevation_max = *vertices
+    pub fn new_from_file(filename: &str) -> Result<Self, MapParseError> {
+        Map::new_from_file_with_resolution(filename, DEFAULT_RESOLUTION)
+    }
+
+    /// Read control points from `filename`, and resample them onto a heightfield with
+    /// `resolution` cells along the longest side of the map.
+    pub fn new_from_file_with_resolution(
+        filename: &str,
+        resolution: usize,
+    ) -> Result<Self, MapParseError> {
+        Ok(Map::from_map_file(MapFile::open(filename)?, resolution))
+    }
+
+    /// Place the control points of `map_file` in the world, surround them with its border, and
+    /// resample them onto a heightfield with `resolution` cells along the longest side of the map.
+    pub fn from_map_file(mut map_file: MapFile, resolution: usize) -> Self {
+        let warnings = std::mem::take(&mut map_file.warnings);
+        let units = Units::new(&map_file.normalisation, &map_file.points);
+        let breaklines = map_file.breaklines.clone();
+        let sinks = map_file
+            .sinks
             .iter()
-            .max_by_key(|&vertex| *vertex.z() as i32)
-            .unwrap()
-            .z();
-        let elevation_max = if elevation_max <= 0.0 {
-            10.0
-        } else {
-            elevation_max
-        };
+            .map(|&sink| units.sink_to_world(sink))
+            .collect();
+        let mut border = map_file.border;
 
-        let points: Vec<Vec2> = vertices.iter().map(|&coord| coord.vec2()).collect();
-        let triangulation = delauney_triangulation(
-            &points,
-            Triangle::new(
-                Vec2::new(0.0, 200.0 * scale as f32),
-                Vec2::new(200.0 * scale as f32, -(200.0 * scale as f32)),
-                Vec2::new(-(200.0 * scale as f32), -(200.0 * scale as f32)),
-            ),
+        // Triangulate in the coordinates of the file, where the parser checked that breaklines do
+        // not cross. Placing the points in the world only scales and moves them, but rounding
+        // could make breaklines that meet appear to cross.
+        let mut points = map_file.points.clone();
+        let (base, inner) = Map::add_edges(&mut points, &border);
+        let flat: Vec<Vec2> = points
+            .iter()
+            .map(|point| Vec2::new(*point.x(), *point.y()))
+            .collect();
+        let triangles = constrained_delauney_triangulation(&flat, &breaklines)
+            .expect("crossing breaklines are rejected while parsing");
+        let points: Vec<Coord> = points.iter().map(|point| units.to_world(point)).collect();
+        let (base, inner) = (
+            units.rectangle_to_world(&base),
+            units.rectangle_to_world(&inner),
         );
-        let indices = Map::calculate_indices(triangulation, &vertices);
+        border.height = units.to_world_height(border.height);
+        let elevation_max = points
+            .iter()
+            .map(|vertex| *vertex.z())
+            .fold(f32::NEG_INFINITY, f32::max);
+
+        let mut heightfield = Heightfield::with_breaklines(&points, &breaklines, base, resolution);
+        border.apply(&mut heightfield, inner);
+        let (center, scale) = Map::extent(&base);
+
+        Self {
+            points,
+            breaklines,
+            triangles,
+            sinks,
+            units,
+            warnings,
+            vertices: heightfield.vertices(),
+            indices: heightfield.indices(),
+            heightfield,
+            center,
+            scale,
+            elevation_max: Map::displayed_elevation_max(elevation_max),
+            base,
+            map_file,
+        }
+    }
+
+    /// Read terrain from the greyscale heightmap image at `path`, one sample per pixel unless
+    /// `options` asks for fewer.
+    pub fn from_heightmap<P: AsRef<Path>>(
+        path: P,
+        options: &HeightmapOptions,
+    ) -> Result<Self, HeightmapError> {
+        let heightfield = heightmap::load(path, options)?;
+        Ok(Map::from_heightfield(
+            heightfield,
+            &options.normalisation,
+            options.resolution,
+        ))
+    }
+
+    /// Terrain sampled on a grid, such as a heightmap or a DEM, placed in the world as
+    /// `normalisation` asks. The grid is kept as it is unless `resolution` asks for fewer cells
+    /// along its longest side.
+    pub fn from_heightfield(
+        heightfield: Heightfield,
+        normalisation: &Normalisation,
+        resolution: Option<usize>,
+    ) -> Self {
+        let source = heightfield.base;
+        let corners = [
+            source.origin,
+            Coord::new(
+                *source.origin.x() + source.x_size,
+                *source.origin.y() + source.y_size,
+                0.0,
+            ),
+        ];
+        let units = Units::new(normalisation, &corners);
+        let base = units.rectangle_to_world(&source);
+        let heights = heightfield
+            .heights()
+            .iter()
+            .map(|&height| units.to_world_height(height))
+            .collect();
+        let heightfield =
+            Heightfield::from_heights(base, heightfield.columns(), heightfield.rows(), heights);
+        let heightfield = match resolution {
+            Some(resolution) if resolution < heightfield.columns().max(heightfield.rows()) - 1 => {
+                heightfield.resample(resolution)
+            }
+            _ => heightfield,
+        };
 
-        // Scaling may be useful to add at some point, to enable a wider variety of maps
-        Ok(Self {
-            vertices,
+        let (center, scale) = Map::extent(&base);
+        let elevation_max = heightfield
+            .heights()
+            .iter()
+            .cloned()
+            .fold(f32::NEG_INFINITY, f32::max);
+        Self {
+            points: Vec::new(),
+            breaklines: Vec::new(),
+            triangles: Vec::new(),
+            sinks: Vec::new(),
+            units,
+            warnings: Vec::new(),
+            vertices: heightfield.vertices(),
+            indices: heightfield.indices(),
+            heightfield,
             center,
-            indices,
             scale,
-            elevation_max,
+            elevation_max: Map::displayed_elevation_max(elevation_max),
             base,
-        })
+            map_file: MapFile {
+                border: Border::none(),
+                normalisation: normalisation.clone(),
+                ..MapFile::default()
+            },
+        }
     }
 
     pub fn center(&self) -> Coord {
-        return self.center;
+        self.center
     }
 
-    fn add_edges(vertices: &mut Vec<Coord>) -> (Coord, usize, Rectangle) {
-        let max_x = get_edge_val!(max_by_key, vertices, x);
-        let min_x = get_edge_val!(min_by_key, vertices, x);
-        let max_y = get_edge_val!(max_by_key, vertices, y);
-        let min_y = get_edge_val!(min_by_key, vertices, y);
-        let y_range = if max_y - min_y == 0.0 {
-            10.0
-        } else {
-            (max_y - min_y) / 2.0
+    /// Terrain elevation at (`x`, `y`), interpolated over the heightfield triangle containing it,
+    /// or `None` outside the map.
+    pub fn height_at(&self, x: f32, y: f32) -> Option<f32> {
+        self.heightfield.height_at(x, y)
+    }
+
+    /// Upward unit normal of the terrain at (`x`, `y`), or `None` outside the map.
+    pub fn normal_at(&self, x: f32, y: f32) -> Option<Coord> {
+        self.heightfield.normal_at(x, y)
+    }
+
+    /// Steepness of the terrain at (`x`, `y`) as rise over run, or `None` outside the map.
+    pub fn slope_at(&self, x: f32, y: f32) -> Option<f32> {
+        self.heightfield.slope_at(x, y)
+    }
+
+    /// Voronoi cells of the control points, with the map edges, in the order of `points`.
+    pub fn voronoi(&self) -> Voronoi {
+        let flat: Vec<Vec2> = self
+            .points
+            .iter()
+            .map(|point| Vec2::new(*point.x(), *point.y()))
+            .collect();
+        Voronoi::new(&flat)
+    }
+
+    /// Camera looking down at the whole map from its southern side, aimed at its center halfway
+    /// up its highest point.
+    pub fn camera(&self) -> CameraMatrix {
+        let distance = 5.0 * self.scale as f32;
+        CameraBuilder::new()
+            .zoom(1.0)
+            .position(Coord::new(
+                *self.center.x(),
+                *self.center.y() - distance,
+                self.elevation_max / 2.0 + distance,
+            ))
+            .direction(Coord::new(0.0, 1.0, -1.0))
+            .up(Coord::new(0.0, 1.0, 0.0))
+            .build()
+    }
+
+    /// Add the corners of `border` around `vertices`, returning the extent of the map along with
+    /// the extent of the control points alone.
+    fn add_edges(vertices: &mut Vec<Coord>, border: &Border) -> (Rectangle, Rectangle) {
+        let (mut min_x, mut min_y) = (f32::INFINITY, f32::INFINITY);
+        let (mut max_x, mut max_y) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for vertex in vertices.iter() {
+            min_x = min_x.min(*vertex.x());
+            min_y = min_y.min(*vertex.y());
+            max_x = max_x.max(*vertex.x());
+            max_y = max_y.max(*vertex.y());
+        }
+        let inner = Rectangle {
+            origin: Coord::new(min_x, min_y, 0.0),
+            x_size: max_x - min_x,
+            y_size: max_y - min_y,
         };
-        let x_range = if max_x - min_x == 0.0 {
-            10.0
-        } else {
-            (max_x - min_x) / 2.0
+        let padding = |extent: f32| {
+            if extent == 0.0 {
+                border::DEGENERATE_PADDING
+            } else {
+                extent * border.padding.max(0.0)
+            }
         };
-        vertices.push(Coord::new(
-            min_x as f32 - x_range,
-            min_y as f32 - y_range,
-            0.0,
-        ));
-        vertices.push(Coord::new(
-            min_x as f32 - x_range,
-            max_y as f32 + y_range,
-            0.0,
-        ));
-        vertices.push(Coord::new(
-            max_x as f32 + x_range,
-            min_y as f32 - y_range,
-            0.0,
-        ));
-        vertices.push(Coord::new(
-            max_x as f32 + x_range,
-            max_y as f32 + y_range,
-            0.0,
-        ));
-        let max_x = get_edge_val!(max_by_key, vertices, x);
-        let min_x = get_edge_val!(min_by_key, vertices, x);
-        let max_y = get_edge_val!(max_by_key, vertices, y);
-        let min_y = get_edge_val!(min_by_key, vertices, y);
-        let y_range = max_y - min_y;
-        let x_range = max_x - min_x;
+        let x_range = padding(inner.x_size);
+        let y_range = padding(inner.y_size);
+        let (min_x, max_x) = (min_x - x_range, max_x + x_range);
+        let (min_y, max_y) = (min_y - y_range, max_y + y_range);
+        if !border.is_none() {
+            vertices.push(Coord::new(min_x, min_y, border.height));
+            vertices.push(Coord::new(min_x, max_y, border.height));
+            vertices.push(Coord::new(max_x, min_y, border.height));
+            vertices.push(Coord::new(max_x, max_y, border.height));
+        }
+        let base = Rectangle {
+            origin: Coord::new(min_x, min_y, 0.0),
+            x_size: max_x - min_x,
+            y_size: max_y - min_y,
+        };
+        (base, inner)
+    }
+
+    /// Write the map as a `.mod1` file, with exactly the control points, breaklines, sinks and
+    /// directives it was read with. Maps read from grids are written with a control point for
+    /// every sample of their heightfield, in the units of the grid.
+    pub fn write_mod1<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        if !self.map_file.points.is_empty() {
+            return io::write_mod1(writer, &self.map_file);
+        }
+        let map_file = MapFile {
+            points: self
+                .heightfield
+                .vertices()
+                .iter()
+                .map(|vertex| self.units.to_map(vertex))
+                .collect(),
+            ..self.map_file.clone()
+        };
+        io::write_mod1(writer, &map_file)
+    }
+
+    /// Center and scale of a map covering `base`.
+    fn extent(base: &Rectangle) -> (Coord, usize) {
         (
-            Coord::new((max_x + min_x) / 2.0, (max_y + min_y) / 2.0, 0.0),
-            ((x_range + y_range) / (2.0 * SCALE_FACTOR)) as usize,
-            Rectangle {
-                origin: Coord::new(min_x, min_y, 0.0),
-                x_size: x_range,
-                y_size: y_range,
-            },
+            Coord::new(
+                *base.origin.x() + base.x_size / 2.0,
+                *base.origin.y() + base.y_size / 2.0,
+                0.0,
+            ),
+            ((base.x_size + base.y_size) / (2.0 * SCALE_FACTOR)) as usize,
         )
     }
 
-    fn calculate_indices(triangulation: Vec<Triangle>, vertices: &Vec<Coord>) -> Vec<u16> {
-        let mut indices = Vec::new();
-        for triangle in triangulation.iter() {
-            indices.push(find_index!(a, vertices, triangle) as u16);
-            indices.push(find_index!(b, vertices, triangle) as u16);
-            indices.push(find_index!(c, vertices, triangle) as u16);
+    /// Highest elevation to frame the map by, for terrain peaking at `elevation_max`.
+    fn displayed_elevation_max(elevation_max: f32) -> f32 {
+        if elevation_max <= 0.0 {
+            10.0
+        } else {
+            elevation_max
         }
-        indices
     }
 }
 
 impl fmt::Display for Map {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for vertex in self.vertices.iter() {
-            writeln!(f, "{}", vertex)?;
+        for point in self.points.iter() {
+            writeln!(f, "{}", point)?;
         }
         writeln!(f, "center: {}", self.center)?;
         writeln!(f, "scale: {}", self.scale)
@@ -172,6 +344,7 @@ impl fmt::Display for Map {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::water::WaterGrid;
     #[test]
     fn read_file() -> Result<(), std::io::Error> {
         use std::fs::File;
@@ -186,7 +359,7 @@ mod tests {
     }
 
     #[test]
-    fn create_map_from_file() -> Result<(), std::io::Error> {
+    fn create_map_from_file() -> Result<(), MapParseError> {
         let map = Map::new_from_file("src/map/demo_c.mod1")?;
 
         print!("{}", map);
@@ -194,10 +367,279 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Invalid Coord!")]
+    fn map_is_resampled_onto_heightfield() -> Result<(), MapParseError> {
+        let map = Map::new_from_file_with_resolution("src/map/demo_d.mod1", 40)?;
+
+        assert_eq!(map.points.len(), 16 + 4);
+        assert_eq!(map.heightfield.columns(), 41);
+        // demo_d is slightly wider than it is deep
+        assert_eq!(map.heightfield.rows(), 39);
+        assert_eq!(map.vertices.len(), 41 * 39);
+        assert_eq!(map.indices.len(), 40 * 38 * 6);
+        Ok(())
+    }
+
+    #[test]
+    fn fine_heightfields_are_not_limited_by_16_bit_indices() -> Result<(), MapParseError> {
+        let map = Map::new_from_file_with_resolution("src/map/demo_d.mod1", 300)?;
+
+        assert!(map.vertices.len() > u16::MAX as usize);
+        assert!(matches!(map.indices, Indices::U32(_)));
+        assert_eq!(map.indices.iter().max(), Some(map.vertices.len() - 1));
+        Ok(())
+    }
+
+    #[test]
+    fn sinks_are_read_from_file() -> Result<(), MapParseError> {
+        let map = Map::new_from_file_with_resolution("src/map/demo_g.mod1", 20)?;
+
+        assert_eq!(map.points.len(), 5 + 4);
+        assert_eq!(map.sinks, vec![Sink::Point(100.0, 100.0)]);
+        Ok(())
+    }
+
+    #[test]
+    fn several_points_per_line_with_comments() -> Result<(), MapParseError> {
+        let map = Map::new_from_file_with_resolution("src/map/demo_h.mod1", 20)?;
+
+        assert_eq!(map.points.len(), 13 + 4);
+        assert_eq!(map.elevation_max, 90.0);
+        Ok(())
+    }
+
+    #[test]
+    fn control_points_are_triangulated_by_index() -> Result<(), MapParseError> {
+        let map = Map::new_from_file_with_resolution("src/map/demo_c.mod1", 20)?;
+
+        let mut used = vec![false; map.points.len()];
+        for &index in map.triangles.iter().flatten() {
+            used[index as usize] = true;
+        }
+        assert!(used.iter().all(|&used| used));
+        // The four corners added around the map make up the hull
+        assert_eq!(map.triangles.len(), 2 * map.points.len() - 4 - 2);
+        assert!(map.warnings.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn breaklines_meeting_survive_normalisation() -> Result<(), MapParseError> {
+        // Scaled down in f32, the end of the second breakline would appear to cross the first
+        let file = "normalise 7\n\
+                    (0,0,1) (90,0,0) (0,60,0) (90,60,0)\n\
+                    breakline (0,0,1) (90,30,1)\n\
+                    breakline (3,1,1) (3,59,1)\n";
+        let map = Map::from_map_file(MapFile::parse(file.as_bytes(), "touching.mod1")?, 20);
+        assert_eq!(map.breaklines.len(), 2);
+        assert!((*map.points[1].x() - 7.0).abs() < 1e-5);
+        // The first breakline is split where the second meets it
+        let joined = |a: u32, b: u32| {
+            map.triangles
+                .iter()
+                .any(|t| t.contains(&a) && t.contains(&b))
+        };
+        let (start, end, junction) = (
+            map.breaklines[0][0],
+            map.breaklines[0][1],
+            map.breaklines[1][0],
+        );
+        assert!(joined(start as u32, junction as u32) && joined(junction as u32, end as u32));
+        Ok(())
+    }
+
+    #[test]
+    fn border_is_configurable() -> Result<(), MapParseError> {
+        let mut map_file = MapFile::open("src/map/demo_d.mod1")?;
+        let count = map_file.points.len();
+
+        map_file.border = Border::none();
+        let map = Map::from_map_file(map_file.clone(), 20);
+        assert_eq!(map.points.len(), count);
+        for point in map.points.iter() {
+            assert!(*point.x() >= *map.base.origin.x() && *point.y() >= *map.base.origin.y());
+        }
+        let unbordered = map.base;
+
+        // Fractional coordinates are not rounded off the edges of the map
+        let file = "border none\n(0,0,0) (10.7,0,5) (10.2,5,3) (3,8.9,1) (4,8.1,2)\n";
+        let map = Map::from_map_file(MapFile::parse(file.as_bytes(), "fraction.mod1")?, 20);
+        assert_eq!((map.base.x_size, map.base.y_size), (10.7, 8.9));
+        assert!(map.height_at(10.7, 0.0).is_some());
+        assert!(map.height_at(3.0, 8.9).is_some());
+
+        map_file.border = Border {
+            padding: 1.0,
+            height: -20.0,
+            falloff: Falloff::Smooth,
+        };
+        let map = Map::from_map_file(map_file, 20);
+        assert_eq!(map.points.len(), count + 4);
+        assert!((map.base.x_size - 3.0 * unbordered.x_size).abs() < 1e-3);
+        let heightfield = &map.heightfield;
+        let (columns, rows) = (heightfield.columns(), heightfield.rows());
+        for &(column, row) in [(0, 0), (columns / 2, 0), (columns - 1, rows / 2)].iter() {
+            assert_eq!(heightfield.height(column, row), -20.0);
+        }
+        assert_eq!(
+            map.height_at(*map.center().x(), *map.base.origin.y()),
+            Some(-20.0)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn maps_are_normalised_into_the_world_box() -> Result<(), MapParseError> {
+        // demo_b rises to 200 over 100 units
+        let mut map_file = MapFile::open("src/map/demo_b.mod1")?;
+        map_file.normalisation = Normalisation {
+            world_size: Some(400.0),
+            exaggeration: 0.25,
+            unit: None,
+        };
+        let map = Map::from_map_file(map_file, 20);
+
+        assert_eq!(map.points[0], Coord::new(0.0, 0.0, 150.0));
+        assert_eq!(map.points[1], Coord::new(400.0, 400.0, 200.0));
+        assert_eq!(map.elevation_max, 200.0);
+        assert_eq!(map.base.x_size, 800.0);
+        assert_eq!(
+            map.units.to_map(&map.points[1]),
+            Coord::new(100.0, 100.0, 200.0)
+        );
+        // The border stays at sea level
+        assert_eq!(map.height_at(-200.0, 600.0), Some(0.0));
+        Ok(())
+    }
+
+    #[test]
+    fn heightmaps_are_read_as_terrain() -> Result<(), HeightmapError> {
+        let options = HeightmapOptions {
+            spacing: 2.0,
+            ..HeightmapOptions::default()
+        };
+        let map = Map::from_heightmap("src/map/demo_heightmap.png", &options)?;
+
+        assert!(map.points.is_empty() && map.triangles.is_empty());
+        assert_eq!(map.heightfield.columns(), 97);
+        assert_eq!(map.base.x_size, 192.0);
+        assert_eq!(map.vertices.len(), 97 * 97);
+        assert_eq!(
+            map.elevation_max,
+            map.heightfield
+                .heights()
+                .iter()
+                .cloned()
+                .fold(0.0, f32::max)
+        );
+        // Every grey level is one unit high
+        for &height in map.heightfield.heights().iter() {
+            assert_eq!(height, height.round());
+        }
+        assert!(map.height_at(96.0, 96.0).is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn maps_are_written_back_as_they_were_read() -> Result<(), MapParseError> {
+        // demo_i has breaklines, one of them closed by coming back to its first point
+        let map_file = MapFile::open("src/map/demo_i.mod1")?;
+        let map = Map::from_map_file(map_file.clone(), 40);
+        let mut output = Vec::new();
+        map.write_mod1(&mut output).unwrap();
+        assert_eq!(MapFile::parse(output.as_slice(), "written.mod1")?, map_file);
+
+        // Grids become one control point per sample, in the units of the grid
+        let base = Rectangle {
+            origin: Coord::new(1000.0, 2000.0, 0.0),
+            x_size: 30.0,
+            y_size: 30.0,
+        };
+        let grid = Heightfield::from_heights(base, 2, 2, vec![1.0, 2.0, 3.0, 4.0]);
+        let normalisation = Normalisation {
+            world_size: Some(300.0),
+            ..Normalisation::default()
+        };
+        let map = Map::from_heightfield(grid, &normalisation, None);
+        let mut output = Vec::new();
+        map.write_mod1(&mut output).unwrap();
+        let written = MapFile::parse(output.as_slice(), "written.mod1")?;
+        assert!(written.border.is_none());
+        assert_eq!(written.normalisation, normalisation);
+        assert_eq!(written.points[3], Coord::new(1030.0, 2030.0, 4.0));
+        Ok(())
+    }
+
+    #[test]
+    fn terrain_can_be_queried_anywhere_on_the_map() -> Result<(), MapParseError> {
+        let map = Map::new_from_file_with_resolution("src/map/demo_d.mod1", 40)?;
+
+        for &(column, row) in [(0, 0), (7, 12), (40, 38)].iter() {
+            let sample = map.heightfield.position(column, row);
+            let height = map.height_at(*sample.x(), *sample.y()).unwrap();
+            assert!((height - sample.z()).abs() < 1e-3);
+        }
+        let center = map.center();
+        let (x, y) = (*center.x(), *center.y());
+        assert!(map.slope_at(x, y).unwrap() >= 0.0);
+        assert!(*map.normal_at(x, y).unwrap().z() > 0.0);
+
+        let corner = map.base.origin;
+        assert_eq!(map.height_at(*corner.x() - 1.0, *corner.y()), None);
+        assert!(map.normal_at(*corner.x() - 1.0, *corner.y()).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn every_control_point_has_a_voronoi_cell() -> Result<(), MapParseError> {
+        let map = Map::new_from_file("src/map/demo_c.mod1")?;
+        let voronoi = map.voronoi();
+        assert_eq!(voronoi.cells.len(), map.points.len());
+        let min = Vec2::new(*map.base.origin.x(), *map.base.origin.y());
+        let max = Vec2::new(min.x + map.base.x_size, min.y + map.base.y_size);
+        let total: f32 = (0..map.points.len())
+            .map(|i| voronoi.clipped_area(i, min, max))
+            .sum();
+        let expected = map.base.x_size * map.base.y_size;
+        assert!((total - expected).abs() < expected * 1e-3);
+        Ok(())
+    }
+
+    #[test]
+    fn breaklines_hold_back_water() -> Result<(), MapParseError> {
+        let map = Map::new_from_file_with_resolution("src/map/demo_i.mod1", 100)?;
+
+        assert_eq!(map.points.len(), 14 + 4);
+        assert_eq!(map.breaklines.len(), 4 + 1 + 1);
+        for &[a, b] in map.breaklines.iter() {
+            let (a, b) = (a as u32, b as u32);
+            assert!(map
+                .triangles
+                .iter()
+                .any(|t| t.contains(&a) && t.contains(&b)));
+        }
+
+        // Flooding the plain stops at the levee, 20 high, which would sag to 15 between its
+        // corners without breaklines
+        let town = map.heightfield.nearest(100.0, 60.0);
+        let mut water = WaterGrid::new(&map.heightfield);
+        water.flood(&map.heightfield, 17.0);
+        assert_eq!(water.depth(town.0, town.1), 0.0);
+        assert!(water.depth(0, 0) > 0.0);
+
+        let smooth = Heightfield::new(&map.points, map.base, 100);
+        let mut water = WaterGrid::new(&smooth);
+        water.flood(&smooth, 17.0);
+        assert!(water.depth(town.0, town.1) > 0.0);
+        Ok(())
+    }
+
+    #[test]
     fn invalid_map() {
-        let map = Map::new_from_file("src/map/invalid_a.mod1").unwrap();
+        let error = Map::new_from_file("src/map/invalid_a.mod1").err().unwrap();
 
-        print!("{}", map);
+        assert_eq!(
+            error.to_string(),
+            "src/map/invalid_a.mod1:5:6: expected number, found \"50b\""
+        );
     }
 }
@@ -0,0 +1,907 @@
+//! Reading control points and sinks from `.mod1` map files.
+//!
+//! Every line of a map file holds either any number of control points `(x, y, z)` separated by
+//! whitespace, a sink declared as `sink edges` or `sink (x, y)`, or a breakline declared as
+//! `breakline` followed by two or more control points. Anything after a `#` is a comment, and
+//! blank lines are ignored. Problems are reported with the file, line and column they
+//! were found at.
+//!
+//! The margin around the map is set by `border` directives, usually at the top of the file:
+//! `border none`, `border padding 0.25`, `border height -5` or `border falloff smooth`. Likewise,
+//! `normalise` fits the map into the world box, of the default size or the one given, as in
+//! `normalise 400`, `exaggeration 2` makes the terrain twice as steep, and `units m` names the
+//! unit of the file for reporting.
+//!
+//! No two control points may share the same x and y: a point repeating an earlier one exactly is
+//! dropped with a warning, and one with a different height is an error. Breaklines may go through
+//! earlier points, but may not cross each other.
+
+use super::border::{Border, Falloff};
+use super::units::{Normalisation, DEFAULT_WORLD_SIZE};
+use crate::delauney::predicates::segments_cross;
+use crate::render::Coord;
+use crate::water::Sink;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::str::FromStr;
+
+/// Where in a map file something was found. Lines and columns start at 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.file.is_empty() {
+            write!(f, "{}:{}", self.line, self.column)
+        } else {
+            write!(f, "{}:{}:{}", self.file, self.line, self.column)
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MapParseError {
+    /// A coordinate missing its opening or closing parenthesis. `found` is `None` at the end of
+    /// the line.
+    Parenthesis {
+        location: Location,
+        expected: char,
+        found: Option<char>,
+    },
+    /// A coordinate with the wrong number of values.
+    Arity {
+        location: Location,
+        expected: usize,
+        found: usize,
+    },
+    /// A value that is not a number.
+    BadFloat { location: Location, found: String },
+    /// A sink that is neither `edges` nor a point.
+    BadSink { location: Location, found: String },
+    /// A border or normalisation directive that is not understood, with what was `expected`
+    /// instead.
+    BadSetting {
+        location: Location,
+        expected: &'static str,
+        found: String,
+    },
+    /// Anything following a sink on the same line.
+    Trailing { location: Location, found: String },
+    /// A control point at the same x and y as an earlier one on line `first_line`, but at a
+    /// different height.
+    DuplicatePoint {
+        location: Location,
+        first_line: usize,
+    },
+    /// A breakline with fewer than two distinct points.
+    ShortBreakline { location: Location },
+    /// A breakline crossing the one declared on line `first_line`.
+    CrossingBreaklines {
+        location: Location,
+        first_line: usize,
+    },
+    /// A file without any control points.
+    Empty { file: String },
+    /// The file could not be read.
+    Io { file: String, error: io::Error },
+}
+
+impl MapParseError {
+    /// The location of the problem, if it can be pinned down to a line.
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            MapParseError::Parenthesis { location, .. }
+            | MapParseError::Arity { location, .. }
+            | MapParseError::BadFloat { location, .. }
+            | MapParseError::BadSink { location, .. }
+            | MapParseError::BadSetting { location, .. }
+            | MapParseError::Trailing { location, .. }
+            | MapParseError::DuplicatePoint { location, .. }
+            | MapParseError::ShortBreakline { location }
+            | MapParseError::CrossingBreaklines { location, .. } => Some(location),
+            MapParseError::Empty { .. } | MapParseError::Io { .. } => None,
+        }
+    }
+
+    /// Place an error found by a `Cursor` on line `line` of `file`.
+    fn at_line(mut self, file: &str, line: usize) -> Self {
+        if let Some(location) = self.location_mut() {
+            location.file = file.to_string();
+            location.line = line;
+        }
+        self
+    }
+
+    fn location_mut(&mut self) -> Option<&mut Location> {
+        match self {
+            MapParseError::Parenthesis { location, .. }
+            | MapParseError::Arity { location, .. }
+            | MapParseError::BadFloat { location, .. }
+            | MapParseError::BadSink { location, .. }
+            | MapParseError::BadSetting { location, .. }
+            | MapParseError::Trailing { location, .. }
+            | MapParseError::DuplicatePoint { location, .. }
+            | MapParseError::ShortBreakline { location }
+            | MapParseError::CrossingBreaklines { location, .. } => Some(location),
+            MapParseError::Empty { .. } | MapParseError::Io { .. } => None,
+        }
+    }
+}
+
+impl fmt::Display for MapParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapParseError::Parenthesis {
+                location,
+                expected,
+                found: Some(found),
+            } => write!(
+                f,
+                "{}: expected \"{}\", found \"{}\"",
+                location, expected, found
+            ),
+            MapParseError::Parenthesis {
+                location,
+                expected,
+                found: None,
+            } => write!(
+                f,
+                "{}: expected \"{}\", found end of line",
+                location, expected
+            ),
+            MapParseError::Arity {
+                location,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}: expected {} values, found {}",
+                location, expected, found
+            ),
+            MapParseError::BadFloat { location, found } => {
+                write!(f, "{}: expected number, found \"{}\"", location, found)
+            }
+            MapParseError::BadSink { location, found } => write!(
+                f,
+                "{}: expected \"edges\" or a point (x, y), found \"{}\"",
+                location, found
+            ),
+            MapParseError::BadSetting {
+                location,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}: expected {}, found \"{}\"",
+                location, expected, found
+            ),
+            MapParseError::Trailing { location, found } => {
+                write!(f, "{}: expected end of line, found \"{}\"", location, found)
+            }
+            MapParseError::DuplicatePoint {
+                location,
+                first_line,
+            } => write!(
+                f,
+                "{}: duplicate point at a different height, already defined on line {}",
+                location, first_line
+            ),
+            MapParseError::ShortBreakline { location } => {
+                write!(
+                    f,
+                    "{}: expected at least 2 distinct points in breakline",
+                    location
+                )
+            }
+            MapParseError::CrossingBreaklines {
+                location,
+                first_line,
+            } => write!(
+                f,
+                "{}: breakline crosses the breakline on line {}",
+                location, first_line
+            ),
+            MapParseError::Empty { file } => write!(f, "{}: no points in map file", file),
+            MapParseError::Io { file, error } => write!(f, "{}: {}", file, error),
+        }
+    }
+}
+
+impl std::error::Error for MapParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MapParseError::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+/// Something odd about a map file that does not stop it from being read.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MapWarning {
+    /// A control point repeating the one on line `first_line` exactly, which was left out.
+    RepeatedPoint {
+        location: Location,
+        first_line: usize,
+    },
+}
+
+impl MapWarning {
+    pub fn location(&self) -> &Location {
+        match self {
+            MapWarning::RepeatedPoint { location, .. } => location,
+        }
+    }
+}
+
+impl fmt::Display for MapWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapWarning::RepeatedPoint {
+                location,
+                first_line,
+            } => write!(
+                f,
+                "{}: repeated point, already defined on line {}, ignored",
+                location, first_line
+            ),
+        }
+    }
+}
+
+/// Control points, breaklines and sinks read from a map file, along with anything worth warning
+/// about.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MapFile {
+    pub points: Vec<Coord>,
+    /// Segments that the terrain must not be smoothed across, as pairs of indices into `points`.
+    pub breaklines: Vec<[usize; 2]>,
+    pub sinks: Vec<Sink>,
+    pub border: Border,
+    pub normalisation: Normalisation,
+    pub warnings: Vec<MapWarning>,
+}
+
+/// Line, height and index of each point read so far, by the bits of its x and y.
+pub(crate) type Seen = HashMap<(u32, u32), (usize, f32, usize)>;
+
+impl MapFile {
+    /// Read and parse the map file at `filename`.
+    pub fn open(filename: &str) -> Result<Self, MapParseError> {
+        let file = File::open(filename).map_err(|error| MapParseError::Io {
+            file: filename.to_string(),
+            error,
+        })?;
+        MapFile::parse(BufReader::new(file), filename)
+    }
+
+    /// Parse the map file `reader`, reporting errors as being in `file`.
+    pub fn parse<R: BufRead>(reader: R, file: &str) -> Result<Self, MapParseError> {
+        let mut map_file = MapFile::default();
+        let mut seen = Seen::new();
+        // Line each breakline segment was declared on
+        let mut breakline_lines = Vec::new();
+
+        for (index, line) in reader.lines().enumerate() {
+            let number = index + 1;
+            let line = line.map_err(|error| MapParseError::Io {
+                file: file.to_string(),
+                error,
+            })?;
+            let fail = |error: MapParseError| error.at_line(file, number);
+            let here = |cursor: &Cursor| Location {
+                file: file.to_string(),
+                line: number,
+                column: cursor.column(),
+            };
+
+            let content = line.split('#').next().unwrap_or_default();
+            let mut cursor = Cursor::new(content);
+            if cursor.eat_word("sink") {
+                let sink = cursor.sink().map_err(fail)?;
+                cursor.end().map_err(fail)?;
+                map_file.sinks.push(sink);
+                continue;
+            }
+
+            if cursor.eat_word("border") {
+                cursor.border(&mut map_file.border).map_err(fail)?;
+                cursor.end().map_err(fail)?;
+                continue;
+            }
+
+            if cursor.eat_word("normalise") {
+                let world_size = if cursor.at_end() {
+                    DEFAULT_WORLD_SIZE
+                } else {
+                    cursor
+                        .number(|value| value > 0.0, "a positive world size")
+                        .map_err(fail)?
+                };
+                map_file.normalisation.world_size = Some(world_size);
+                cursor.end().map_err(fail)?;
+                continue;
+            }
+
+            if cursor.eat_word("exaggeration") {
+                map_file.normalisation.exaggeration = cursor
+                    .number(|value| value > 0.0, "a positive exaggeration")
+                    .map_err(fail)?;
+                cursor.end().map_err(fail)?;
+                continue;
+            }
+
+            if cursor.eat_word("units") {
+                cursor.skip_whitespace();
+                let location = here(&cursor);
+                let unit = cursor.word();
+                if unit.is_empty() {
+                    return Err(MapParseError::BadSetting {
+                        location,
+                        expected: "a unit",
+                        found: String::new(),
+                    });
+                }
+                map_file.normalisation.unit = Some(unit.to_string());
+                cursor.end().map_err(fail)?;
+                continue;
+            }
+
+            if cursor.eat_word("breakline") {
+                cursor.skip_whitespace();
+                let start = here(&cursor);
+                let mut previous: Option<usize> = None;
+                let mut segments = 0;
+                while !cursor.at_end() {
+                    let location = here(&cursor);
+                    let point = cursor.coord().map_err(fail)?;
+                    let (index, _) = map_file.add_point(&mut seen, point, location.clone())?;
+
+                    let segment = match previous.replace(index) {
+                        Some(previous) if previous != index => [previous, index],
+                        _ => continue,
+                    };
+                    let coords = |i: usize| {
+                        let point = &map_file.points[i];
+                        (*point.x() as f64, *point.y() as f64)
+                    };
+                    let crossed = map_file.breaklines.iter().position(|&[c, d]| {
+                        segments_cross(coords(segment[0]), coords(segment[1]), coords(c), coords(d))
+                    });
+                    if let Some(crossed) = crossed {
+                        return Err(MapParseError::CrossingBreaklines {
+                            location,
+                            first_line: breakline_lines[crossed],
+                        });
+                    }
+                    map_file.breaklines.push(segment);
+                    breakline_lines.push(number);
+                    segments += 1;
+                }
+                if segments == 0 {
+                    return Err(MapParseError::ShortBreakline { location: start });
+                }
+                continue;
+            }
+
+            while !cursor.at_end() {
+                let location = here(&cursor);
+                let point = cursor.coord().map_err(fail)?;
+                if let (_, Some(first_line)) =
+                    map_file.add_point(&mut seen, point, location.clone())?
+                {
+                    map_file.warnings.push(MapWarning::RepeatedPoint {
+                        location,
+                        first_line,
+                    });
+                }
+            }
+        }
+
+        if map_file.points.is_empty() {
+            return Err(MapParseError::Empty {
+                file: file.to_string(),
+            });
+        }
+        Ok(map_file)
+    }
+
+    /// Index of `point`, found at `location`, in `points`. It is added unless it repeats an earlier
+    /// point exactly, in which case the line of that point is returned too.
+    pub(crate) fn add_point(
+        &mut self,
+        seen: &mut Seen,
+        point: Coord,
+        location: Location,
+    ) -> Result<(usize, Option<usize>), MapParseError> {
+        // Adding zero turns -0.0 into 0.0, so that both count as the same position
+        let key = ((point.x() + 0.0).to_bits(), (point.y() + 0.0).to_bits());
+        if let Some(&(first_line, height, index)) = seen.get(&key) {
+            if height != *point.z() {
+                return Err(MapParseError::DuplicatePoint {
+                    location,
+                    first_line,
+                });
+            }
+            return Ok((index, Some(first_line)));
+        }
+        let index = self.points.len();
+        seen.insert(key, (location.line, *point.z(), index));
+        self.points.push(point);
+        Ok((index, None))
+    }
+}
+
+impl FromStr for Coord {
+    type Err = MapParseError;
+
+    /// Parse a single coordinate `(x, y, z)`. Errors are located on line 1 of an unnamed file.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut cursor = Cursor::new(s);
+        let coord = cursor.coord()?;
+        cursor.end()?;
+        Ok(coord)
+    }
+}
+
+/// Position within a single line of a map file.
+struct Cursor<'a> {
+    line: &'a str,
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(line: &'a str) -> Self {
+        Self { line, position: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.line[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    /// Column of the current position, counted in characters.
+    fn column(&self) -> usize {
+        self.line[..self.position].chars().count() + 1
+    }
+
+    fn location(&self) -> Location {
+        Location {
+            file: String::new(),
+            line: 1,
+            column: self.column(),
+        }
+    }
+
+    /// Skip over `word` if the line continues with it, followed by whitespace or nothing.
+    fn eat_word(&mut self, word: &str) -> bool {
+        self.skip_whitespace();
+        match self.rest().strip_prefix(word) {
+            Some(after) if after.is_empty() || after.starts_with(char::is_whitespace) => {
+                self.position += word.len();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), MapParseError> {
+        self.skip_whitespace();
+        match self.rest().chars().next() {
+            Some(found) if found == expected => {
+                self.position += found.len_utf8();
+                Ok(())
+            }
+            found => Err(MapParseError::Parenthesis {
+                location: self.location(),
+                expected,
+                found,
+            }),
+        }
+    }
+
+    /// A parenthesised list of exactly `arity` numbers separated by commas.
+    fn tuple(&mut self, arity: usize) -> Result<Vec<f32>, MapParseError> {
+        self.skip_whitespace();
+        let start = self.location();
+        self.expect('(')?;
+
+        let mut values = Vec::with_capacity(arity);
+        loop {
+            self.skip_whitespace();
+            let location = self.location();
+            let rest = self.rest();
+            let length = rest.find([',', ')']).unwrap_or(rest.len());
+            let token = rest[..length].trim_end();
+            // Infinities and NaN are not places on the map
+            let value = token
+                .parse::<f32>()
+                .ok()
+                .filter(|value| value.is_finite())
+                .ok_or_else(|| MapParseError::BadFloat {
+                    location,
+                    found: token.to_string(),
+                })?;
+            values.push(value);
+            self.position += length;
+
+            match self.rest().chars().next() {
+                Some(',') => self.position += 1,
+                Some(')') => {
+                    self.position += 1;
+                    break;
+                }
+                found => {
+                    return Err(MapParseError::Parenthesis {
+                        location: self.location(),
+                        expected: ')',
+                        found,
+                    })
+                }
+            }
+        }
+
+        if values.len() != arity {
+            return Err(MapParseError::Arity {
+                location: start,
+                expected: arity,
+                found: values.len(),
+            });
+        }
+        Ok(values)
+    }
+
+    fn coord(&mut self) -> Result<Coord, MapParseError> {
+        let values = self.tuple(3)?;
+        Ok(Coord::new(values[0], values[1], values[2]))
+    }
+
+    fn sink(&mut self) -> Result<Sink, MapParseError> {
+        if self.eat_word("edges") {
+            return Ok(Sink::Edges);
+        }
+        self.skip_whitespace();
+        if !self.rest().starts_with('(') {
+            return Err(MapParseError::BadSink {
+                location: self.location(),
+                found: self.rest().trim_end().to_string(),
+            });
+        }
+        let values = self.tuple(2)?;
+        Ok(Sink::Point(values[0], values[1]))
+    }
+
+    /// Apply a single border directive to `border`.
+    fn border(&mut self, border: &mut Border) -> Result<(), MapParseError> {
+        if self.eat_word("none") {
+            *border = Border::none();
+        } else if self.eat_word("padding") {
+            border.padding = self.number(|value| value >= 0.0, "a padding of at least 0")?;
+        } else if self.eat_word("height") {
+            border.height = self.number(f32::is_finite, "a height")?;
+        } else if self.eat_word("falloff") {
+            self.skip_whitespace();
+            let location = self.location();
+            let word = self.word();
+            border.falloff = word
+                .parse::<Falloff>()
+                .map_err(|_| MapParseError::BadSetting {
+                    location,
+                    expected: "\"interpolated\", \"linear\" or \"smooth\"",
+                    found: word.to_string(),
+                })?;
+        } else {
+            return Err(MapParseError::BadSetting {
+                location: self.location(),
+                expected: "\"none\", \"padding\", \"height\" or \"falloff\"",
+                found: self.word().to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// The next run of characters up to whitespace.
+    fn word(&mut self) -> &'a str {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let length = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        self.position += length;
+        &rest[..length]
+    }
+
+    /// A single number, which must pass `valid`.
+    fn number(
+        &mut self,
+        valid: fn(f32) -> bool,
+        expected: &'static str,
+    ) -> Result<f32, MapParseError> {
+        self.skip_whitespace();
+        let location = self.location();
+        let word = self.word();
+        match word.parse::<f32>() {
+            Ok(value) if !value.is_finite() => Err(MapParseError::BadFloat {
+                location,
+                found: word.to_string(),
+            }),
+            Ok(value) if valid(value) => Ok(value),
+            Ok(_) => Err(MapParseError::BadSetting {
+                location,
+                expected,
+                found: word.to_string(),
+            }),
+            Err(_) => Err(MapParseError::BadFloat {
+                location,
+                found: word.to_string(),
+            }),
+        }
+    }
+
+    /// Whether nothing but whitespace is left on the line.
+    fn at_end(&mut self) -> bool {
+        self.skip_whitespace();
+        self.rest().is_empty()
+    }
+
+    /// Check that nothing but whitespace is left on the line.
+    fn end(&mut self) -> Result<(), MapParseError> {
+        if self.at_end() {
+            Ok(())
+        } else {
+            Err(MapParseError::Trailing {
+                location: self.location(),
+                found: self.rest().trim_end().to_string(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<MapFile, MapParseError> {
+        MapFile::parse(text.as_bytes(), "test.mod1")
+    }
+
+    fn message(text: &str) -> String {
+        parse(text).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn points_and_sinks_are_read() {
+        let map_file = parse("(0,0,10)\n( 5 , -2.5 ,1e2 )\nsink edges\nsink (1, 2)\n").unwrap();
+
+        assert_eq!(
+            map_file.points,
+            vec![Coord::new(0.0, 0.0, 10.0), Coord::new(5.0, -2.5, 100.0)]
+        );
+        assert_eq!(map_file.sinks, vec![Sink::Edges, Sink::Point(1.0, 2.0)]);
+    }
+
+    #[test]
+    fn comments_blank_lines_and_whitespace_are_ignored() {
+        let text = "# A comment\n\n  (0,0,10)\t(10, 0, 20)(0,10,30)  # after points\n   \n\
+                    sink   edges # after a sink\n";
+        let map_file = parse(text).unwrap();
+
+        assert_eq!(
+            map_file.points,
+            vec![
+                Coord::new(0.0, 0.0, 10.0),
+                Coord::new(10.0, 0.0, 20.0),
+                Coord::new(0.0, 10.0, 30.0)
+            ]
+        );
+        assert_eq!(map_file.sinks, vec![Sink::Edges]);
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        assert_eq!(
+            message("(0,0,1)\n(100,50b,40)"),
+            "test.mod1:2:6: expected number, found \"50b\""
+        );
+        assert_eq!(
+            message("0,0,1)"),
+            "test.mod1:1:1: expected \"(\", found \"0\""
+        );
+        assert_eq!(
+            message("(0,0,1"),
+            "test.mod1:1:7: expected \")\", found end of line"
+        );
+        assert_eq!(
+            message("(0,0)"),
+            "test.mod1:1:1: expected 3 values, found 2"
+        );
+        assert_eq!(
+            message("(0,0,1) 2,2,2"),
+            "test.mod1:1:9: expected \"(\", found \"2\""
+        );
+        assert_eq!(
+            message("(0,0,1)\nsink edges (1,2)"),
+            "test.mod1:2:12: expected end of line, found \"(1,2)\""
+        );
+        assert_eq!(
+            message("(0,0,1)\nsink plughole"),
+            "test.mod1:2:6: expected \"edges\" or a point (x, y), found \"plughole\""
+        );
+        assert_eq!(
+            message("(0,0,1)\nsink (1,2,3)"),
+            "test.mod1:2:6: expected 2 values, found 3"
+        );
+    }
+
+    #[test]
+    fn values_must_be_finite() {
+        assert_eq!(
+            message("(inf,0,1) (1,1,1) (2,0,3)"),
+            "test.mod1:1:2: expected number, found \"inf\""
+        );
+        assert_eq!(
+            message("(0,0,1) (1, -inf, 1)"),
+            "test.mod1:1:13: expected number, found \"-inf\""
+        );
+        assert_eq!(
+            message("(nan,0,1) (1,1,1) (2,0,3)"),
+            "test.mod1:1:2: expected number, found \"nan\""
+        );
+        assert_eq!(
+            message("border height NaN\n(0,0,1)"),
+            "test.mod1:1:15: expected number, found \"NaN\""
+        );
+    }
+
+    #[test]
+    fn border_directives_are_read() {
+        assert_eq!(parse("(0,0,1)").unwrap().border, Border::default());
+        let map_file =
+            parse("border padding 0.25 # narrow\nborder height -5\nborder falloff smooth\n(0,0,1)")
+                .unwrap();
+        assert_eq!(
+            map_file.border,
+            Border {
+                padding: 0.25,
+                height: -5.0,
+                falloff: Falloff::Smooth,
+            }
+        );
+        assert!(parse("border none\n(0,0,1)").unwrap().border.is_none());
+
+        assert_eq!(
+            message("border wide\n(0,0,1)"),
+            "test.mod1:1:8: expected \"none\", \"padding\", \"height\" or \"falloff\", \
+             found \"wide\""
+        );
+        assert_eq!(
+            message("border padding -1"),
+            "test.mod1:1:16: expected a padding of at least 0, found \"-1\""
+        );
+        assert_eq!(
+            message("border height high"),
+            "test.mod1:1:15: expected number, found \"high\""
+        );
+        assert_eq!(
+            message("border falloff  cubic"),
+            "test.mod1:1:17: expected \"interpolated\", \"linear\" or \"smooth\", found \"cubic\""
+        );
+        assert_eq!(
+            message("border none 2"),
+            "test.mod1:1:13: expected end of line, found \"2\""
+        );
+    }
+
+    #[test]
+    fn normalisation_directives_are_read() {
+        assert_eq!(
+            parse("(0,0,1)").unwrap().normalisation,
+            Normalisation::default()
+        );
+        let map_file = parse("normalise\nexaggeration 2.5\nunits m\n(0,0,1)").unwrap();
+        assert_eq!(
+            map_file.normalisation,
+            Normalisation {
+                world_size: Some(DEFAULT_WORLD_SIZE),
+                exaggeration: 2.5,
+                unit: Some("m".to_string()),
+            }
+        );
+        let map_file = parse("normalise 400 # wider\n(0,0,1)").unwrap();
+        assert_eq!(map_file.normalisation.world_size, Some(400.0));
+
+        assert_eq!(
+            message("normalise 0"),
+            "test.mod1:1:11: expected a positive world size, found \"0\""
+        );
+        assert_eq!(
+            message("exaggeration -1"),
+            "test.mod1:1:14: expected a positive exaggeration, found \"-1\""
+        );
+        assert_eq!(
+            message("units"),
+            "test.mod1:1:6: expected a unit, found \"\""
+        );
+        assert_eq!(
+            message("units m s"),
+            "test.mod1:1:9: expected end of line, found \"s\""
+        );
+    }
+
+    #[test]
+    fn duplicate_points_are_rejected() {
+        assert_eq!(
+            message("(0,0,1)\n(5,5,5)\n(-0,0,3)"),
+            "test.mod1:3:1: duplicate point at a different height, already defined on line 1"
+        );
+        assert_eq!(
+            message("(0,0,1) (5,5,5) (5,5,6)"),
+            "test.mod1:1:17: duplicate point at a different height, already defined on line 1"
+        );
+    }
+
+    #[test]
+    fn repeated_points_are_merged_with_a_warning() {
+        let map_file = parse("(0,0,1) (5,5,5)\n(10,0,2)\n  (5,5,5) (-0,0,1)").unwrap();
+
+        assert_eq!(
+            map_file.points,
+            vec![
+                Coord::new(0.0, 0.0, 1.0),
+                Coord::new(5.0, 5.0, 5.0),
+                Coord::new(10.0, 0.0, 2.0)
+            ]
+        );
+        let warnings: Vec<String> = map_file.warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "test.mod1:3:3: repeated point, already defined on line 1, ignored",
+                "test.mod1:3:11: repeated point, already defined on line 1, ignored"
+            ]
+        );
+    }
+
+    #[test]
+    fn breaklines_are_read() {
+        let map_file = parse("(0,0,1)\nbreakline (10,0,5) (10,10,5)  (0,0,1) # closing\n").unwrap();
+
+        assert_eq!(map_file.points.len(), 3);
+        assert_eq!(map_file.breaklines, vec![[1, 2], [2, 0]]);
+        // Going back through an earlier point is not a repeat worth a warning
+        assert!(map_file.warnings.is_empty());
+
+        assert_eq!(
+            message("breakline (0,0,1)"),
+            "test.mod1:1:11: expected at least 2 distinct points in breakline"
+        );
+        assert_eq!(
+            message("breakline (5,5,1) (5,5,1)"),
+            "test.mod1:1:11: expected at least 2 distinct points in breakline"
+        );
+        assert_eq!(
+            message("breakline (0,0,1) (10,10,1)\nbreakline (0,10,1) (10,0,1)"),
+            "test.mod1:2:20: breakline crosses the breakline on line 1"
+        );
+        assert_eq!(
+            message("(0,0,1)\nbreakline (0,0,2) (10,10,1)"),
+            "test.mod1:2:11: duplicate point at a different height, already defined on line 1"
+        );
+    }
+
+    #[test]
+    fn files_need_points() {
+        let error = parse("sink edges\n").unwrap_err();
+        assert!(matches!(error, MapParseError::Empty { .. }));
+        assert_eq!(error.to_string(), "test.mod1: no points in map file");
+        assert!(error.location().is_none());
+    }
+}
@@ -0,0 +1,206 @@
+//! Placing the coordinates of a map file in the world the simulation runs in.
+//!
+//! Map files come in whatever units they were surveyed in: a few hundred units across for the
+//! sample maps, or metres over kilometres for real terrain. Normalising fits the control points
+//! into a box of a fixed size, so that the simulation and camera behave the same whatever the
+//! source, and heights can be exaggerated to bring out flat terrain. Water depths and volumes are
+//! converted back to the units of the map file for reporting.
+
+use super::Rectangle;
+use crate::render::Coord;
+use crate::water::Sink;
+
+/// Length of the longest side of the control points once normalised, unless specified otherwise.
+pub const DEFAULT_WORLD_SIZE: f32 = 200.0;
+
+/// How the coordinates of a map file are to be placed in the world.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Normalisation {
+    /// Length of the longest side of the control points in the world, or `None` to keep the
+    /// coordinates of the map file as they are.
+    pub world_size: Option<f32>,
+    /// How many times steeper the terrain is in the world than in the map file.
+    pub exaggeration: f32,
+    /// Name of the unit the map file is in, such as `m`, for reporting.
+    pub unit: Option<String>,
+}
+
+impl Default for Normalisation {
+    fn default() -> Self {
+        Self {
+            world_size: None,
+            exaggeration: 1.0,
+            unit: None,
+        }
+    }
+}
+
+/// Conversion between the coordinates of a map file and world coordinates.
+///
+/// Horizontal coordinates are shifted and scaled, and heights are scaled by the same factor times
+/// the exaggeration, so that an elevation of zero stays at zero.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Units {
+    /// Position in the map file that ends up at the world origin.
+    origin: (f32, f32),
+    /// World units per unit of the map file, along the ground and upwards.
+    horizontal: f32,
+    vertical: f32,
+    pub unit: Option<String>,
+}
+
+impl Default for Units {
+    fn default() -> Self {
+        Self {
+            origin: (0.0, 0.0),
+            horizontal: 1.0,
+            vertical: 1.0,
+            unit: None,
+        }
+    }
+}
+
+impl Units {
+    /// Conversion placing `points`, in the coordinates of a map file, as `normalisation` asks.
+    pub fn new(normalisation: &Normalisation, points: &[Coord]) -> Self {
+        let mut units = Self {
+            vertical: normalisation.exaggeration,
+            unit: normalisation.unit.clone(),
+            ..Self::default()
+        };
+        let world_size = match normalisation.world_size {
+            Some(world_size) if !points.is_empty() => world_size,
+            _ => return units,
+        };
+
+        let (mut min_x, mut min_y) = (f32::INFINITY, f32::INFINITY);
+        let (mut max_x, mut max_y) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for point in points {
+            min_x = min_x.min(*point.x());
+            min_y = min_y.min(*point.y());
+            max_x = max_x.max(*point.x());
+            max_y = max_y.max(*point.y());
+        }
+        let extent = (max_x - min_x).max(max_y - min_y);
+        if extent > 0.0 {
+            units.horizontal = world_size / extent;
+            units.vertical *= units.horizontal;
+        }
+        units.origin = (min_x, min_y);
+        units
+    }
+
+    /// World units per unit of the map file along the ground.
+    pub fn horizontal_scale(&self) -> f32 {
+        self.horizontal
+    }
+
+    /// World units per unit of the map file upwards, including the exaggeration.
+    pub fn vertical_scale(&self) -> f32 {
+        self.vertical
+    }
+
+    /// World position of the point (`x`, `y`) of the map file.
+    pub fn to_world_xy(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            (x - self.origin.0) * self.horizontal,
+            (y - self.origin.1) * self.horizontal,
+        )
+    }
+
+    /// World elevation of a height in the map file.
+    pub fn to_world_height(&self, z: f32) -> f32 {
+        z * self.vertical
+    }
+
+    pub fn to_world(&self, point: &Coord) -> Coord {
+        let (x, y) = self.to_world_xy(*point.x(), *point.y());
+        Coord::new(x, y, self.to_world_height(*point.z()))
+    }
+
+    /// Where `rectangle`, in the coordinates of the map file, lies in the world.
+    pub fn rectangle_to_world(&self, rectangle: &Rectangle) -> Rectangle {
+        Rectangle {
+            origin: self.to_world(&rectangle.origin),
+            x_size: rectangle.x_size * self.horizontal,
+            y_size: rectangle.y_size * self.horizontal,
+        }
+    }
+
+    /// Sink placed at the world position of a sink declared in the map file.
+    pub fn sink_to_world(&self, sink: Sink) -> Sink {
+        match sink {
+            Sink::Edges => Sink::Edges,
+            Sink::Point(x, y) => {
+                let (x, y) = self.to_world_xy(x, y);
+                Sink::Point(x, y)
+            }
+        }
+    }
+
+    /// Position in the map file of the world point `point`.
+    pub fn to_map(&self, point: &Coord) -> Coord {
+        Coord::new(
+            *point.x() / self.horizontal + self.origin.0,
+            *point.y() / self.horizontal + self.origin.1,
+            *point.z() / self.vertical,
+        )
+    }
+
+    /// A world water depth, in the units of the map file.
+    pub fn depth_to_map(&self, depth: f32) -> f32 {
+        depth / self.vertical
+    }
+
+    /// A world volume of water, in cubic units of the map file.
+    pub fn volume_to_map(&self, volume: f32) -> f32 {
+        volume / (self.horizontal * self.horizontal * self.vertical)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn survey_data_is_fitted_into_the_world_box() {
+        // Two kilometres by one, in metres, rising to 300 metres
+        let points = vec![
+            Coord::new(500_000.0, 6_000_000.0, 10.0),
+            Coord::new(502_000.0, 6_001_000.0, 300.0),
+        ];
+        let normalisation = Normalisation {
+            world_size: Some(200.0),
+            exaggeration: 3.0,
+            unit: Some("m".to_string()),
+        };
+        let units = Units::new(&normalisation, &points);
+
+        assert_eq!(units.to_world(&points[0]), Coord::new(0.0, 0.0, 3.0));
+        assert_eq!(units.to_world(&points[1]), Coord::new(200.0, 100.0, 90.0));
+        assert_eq!(units.to_map(&Coord::new(200.0, 100.0, 90.0)), points[1]);
+        assert_eq!(
+            units.sink_to_world(Sink::Point(501_000.0, 6_000_500.0)),
+            Sink::Point(100.0, 50.0)
+        );
+        // A world cell of 2 by 2 holding 0.3 of water is 20m by 20m, 1m deep
+        assert!((units.depth_to_map(0.3) - 1.0).abs() < 1e-5);
+        assert!((units.volume_to_map(2.0 * 2.0 * 0.3) - 400.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn maps_keep_their_coordinates_by_default() {
+        let points = vec![Coord::new(-10.0, 5.0, 20.0), Coord::new(40.0, 90.0, 0.0)];
+        let units = Units::new(&Normalisation::default(), &points);
+        assert_eq!(units, Units::default());
+        assert_eq!(units.to_world(&points[0]), points[0]);
+
+        let exaggerated = Normalisation {
+            exaggeration: 0.5,
+            ..Normalisation::default()
+        };
+        let units = Units::new(&exaggerated, &points);
+        assert_eq!(units.to_world(&points[0]), Coord::new(-10.0, 5.0, 10.0));
+        assert_eq!(units.volume_to_map(10.0), 20.0);
+    }
+}
@@ -0,0 +1,74 @@
+/// Small, seedable pseudo-random number generator (xorshift64*), so that simulations and
+/// generated terrain can be reproduced exactly from a seed.
+#[derive(Clone, Debug)]
+pub struct Random {
+    state: u64,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        // A zero state would only ever produce zeroes, so the one seed leading to it gets another
+        let state = match seed ^ 0x9E37_79B9_7F4A_7C15 {
+            0 => 0x2545_F491_4F6C_DD1D,
+            state => state,
+        };
+        Self { state }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniformly distributed in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniformly distributed in `[min, max)`.
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + self.next_f32() * (max - min)
+    }
+
+    /// Uniformly distributed in `[0, bound)`.
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_sequence() {
+        let mut a = Random::new(42);
+        let mut b = Random::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        assert_ne!(Random::new(1).next_u64(), Random::new(2).next_u64());
+    }
+
+    #[test]
+    fn every_seed_gives_a_varied_sequence() {
+        for &seed in [0, 0x9E37_79B9_7F4A_7C15, u64::MAX].iter() {
+            let mut random = Random::new(seed);
+            let values: Vec<u64> = (0..10).map(|_| random.next_u64()).collect();
+            assert!(values.iter().all(|&value| value != 0), "{:#x}", seed);
+            assert_ne!(values[0], values[1]);
+        }
+    }
+
+    #[test]
+    fn values_stay_in_range() {
+        let mut random = Random::new(0);
+        for _ in 0..1000 {
+            let value = random.range(-5.0, 5.0);
+            assert!((-5.0..5.0).contains(&value));
+            assert!(random.below(7) < 7);
+        }
+    }
+}
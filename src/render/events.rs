@@ -19,14 +19,7 @@ pub fn mouse_scroll(zoom: &mut f32, delta: MouseScrollDelta, scale: usize) {
 }
 
 fn adjust_water_level(water_level: &mut f32, change: f32) {
-    *water_level += change;
-    *water_level = if *water_level > 255.0 {
-        255.0
-    } else if *water_level < 0.0 {
-        0.0
-    } else {
-        *water_level
-    };
+    *water_level = (*water_level + change).clamp(0.0, 255.0);
 }
 
 pub fn key_event(
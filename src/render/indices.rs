@@ -0,0 +1,153 @@
+//! Vertex indices of triangle lists, stored as `u16` while every vertex fits and `u32` beyond.
+//!
+//! Small meshes keep the compact 16 bit indices, and large terrains switch to 32 bit ones as soon
+//! as an index needs it, instead of silently wrapping around.
+
+#[cfg(feature = "window")]
+use glium::index::{BufferCreationError, IndexBuffer, IndexBufferAny, PrimitiveType};
+
+/// Number of vertices that 16 bit indices can address. `u16::MAX` itself is left out, as some
+/// drivers treat it as the primitive restart index.
+pub const U16_VERTEX_LIMIT: usize = u16::MAX as usize;
+
+/// Indices of a triangle list, three per triangle.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Indices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl Indices {
+    /// No indices yet, stored as small as addressing `vertex_count` vertices allows.
+    pub fn new(vertex_count: usize) -> Self {
+        Self::with_capacity(vertex_count, 0)
+    }
+
+    pub fn with_capacity(vertex_count: usize, capacity: usize) -> Self {
+        if vertex_count <= U16_VERTEX_LIMIT {
+            Indices::U16(Vec::with_capacity(capacity))
+        } else {
+            Indices::U32(Vec::with_capacity(capacity))
+        }
+    }
+
+    /// Add an index, widening every index to 32 bits if it does not fit in 16.
+    pub fn push(&mut self, index: usize) {
+        if index >= U16_VERTEX_LIMIT {
+            self.widen();
+        }
+        match self {
+            Indices::U16(indices) => indices.push(index as u16),
+            Indices::U32(indices) => indices.push(index as u32),
+        }
+    }
+
+    /// Add every index of `other`.
+    pub fn append(&mut self, other: &Indices) {
+        if let Indices::U32(_) = other {
+            self.widen();
+        }
+        match (self, other) {
+            (Indices::U16(indices), Indices::U16(other)) => indices.extend_from_slice(other),
+            (Indices::U32(indices), Indices::U16(other)) => {
+                indices.extend(other.iter().map(|&index| index as u32))
+            }
+            (Indices::U32(indices), Indices::U32(other)) => indices.extend_from_slice(other),
+            (Indices::U16(_), Indices::U32(_)) => unreachable!(),
+        }
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        match self {
+            Indices::U16(indices) => indices.truncate(len),
+            Indices::U32(indices) => indices.truncate(len),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Indices::U16(indices) => indices.len(),
+            Indices::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, position: usize) -> Option<usize> {
+        match self {
+            Indices::U16(indices) => indices.get(position).map(|&index| index as usize),
+            Indices::U32(indices) => indices.get(position).map(|&index| index as usize),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len()).map(move |position| self.get(position).unwrap())
+    }
+
+    /// Upload the indices to the GPU as a triangle list.
+    #[cfg(feature = "window")]
+    pub fn index_buffer<F: glium::backend::Facade>(
+        &self,
+        facade: &F,
+    ) -> Result<IndexBufferAny, BufferCreationError> {
+        let primitive = PrimitiveType::TrianglesList;
+        Ok(match self {
+            Indices::U16(indices) => IndexBuffer::new(facade, primitive, indices)?.into(),
+            Indices::U32(indices) => IndexBuffer::new(facade, primitive, indices)?.into(),
+        })
+    }
+
+    fn widen(&mut self) {
+        if let Indices::U16(indices) = self {
+            *self = Indices::U32(indices.iter().map(|&index| index as u32).collect());
+        }
+    }
+}
+
+impl From<Vec<u16>> for Indices {
+    fn from(indices: Vec<u16>) -> Self {
+        Indices::U16(indices)
+    }
+}
+
+impl From<Vec<u32>> for Indices {
+    fn from(indices: Vec<u32>) -> Self {
+        Indices::U32(indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn storage_depends_on_vertex_count() {
+        assert!(matches!(Indices::new(4), Indices::U16(_)));
+        assert!(matches!(Indices::new(U16_VERTEX_LIMIT), Indices::U16(_)));
+        assert!(matches!(
+            Indices::new(U16_VERTEX_LIMIT + 1),
+            Indices::U32(_)
+        ));
+    }
+
+    #[test]
+    fn large_indices_widen_storage() {
+        let mut indices = Indices::new(3);
+        indices.push(0);
+        indices.push(65_534);
+        assert!(matches!(indices, Indices::U16(_)));
+        indices.push(70_000);
+        assert_eq!(indices, Indices::U32(vec![0, 65_534, 70_000]));
+
+        let mut small = Indices::from(vec![1u16, 2, 3]);
+        small.append(&indices);
+        assert_eq!(
+            small.iter().collect::<Vec<_>>(),
+            vec![1, 2, 3, 0, 65_534, 70_000]
+        );
+        small.truncate(3);
+        assert_eq!(small, Indices::U32(vec![1, 2, 3]));
+    }
+}
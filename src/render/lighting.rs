@@ -0,0 +1,203 @@
+//! Directional sunlight, shared by the window's shaders and the software renderer.
+//!
+//! Surfaces are lit with the Lambert term for the sun plus an even ambient term, so that slopes
+//! facing the sun are brighter than those facing away from it. Water also reflects a Blinn-Phong
+//! highlight of the sun towards the camera. Only the direct share of the light, one minus the
+//! ambient share, casts highlights, so that an ambient share of 1 gives the flat colours alone.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// How strongly water reflects the sun, unless specified otherwise.
+pub const WATER_SPECULAR: f32 = 0.5;
+
+/// Blinn-Phong exponent of the highlight on water: the higher, the tighter.
+pub const SHININESS: f32 = 40.0;
+
+/// Position of the sun in the sky, in degrees.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sun {
+    /// Compass bearing of the sun, clockwise from north along the y axis.
+    pub azimuth: f32,
+    /// Height of the sun above the horizon, from 0 to 90.
+    pub altitude: f32,
+}
+
+impl Default for Sun {
+    /// Low in the north west, the usual light for shaded relief maps.
+    fn default() -> Self {
+        Self {
+            azimuth: 315.0,
+            altitude: 45.0,
+        }
+    }
+}
+
+impl Sun {
+    /// Unit vector pointing towards the sun.
+    pub fn direction(&self) -> [f32; 3] {
+        let (azimuth, altitude) = (self.azimuth.to_radians(), self.altitude.to_radians());
+        [
+            azimuth.sin() * altitude.cos(),
+            azimuth.cos() * altitude.cos(),
+            altitude.sin(),
+        ]
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SunError;
+
+impl fmt::Display for SunError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Sun must be an azimuth and an altitude from 0 to 90 degrees, as azimuth,altitude"
+        )
+    }
+}
+
+impl std::error::Error for SunError {}
+
+impl FromStr for Sun {
+    type Err = SunError;
+
+    /// Parse a sun given as `azimuth,altitude`, in degrees.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut values = s.split(',').map(|value| value.trim().parse::<f32>());
+        match (values.next(), values.next(), values.next()) {
+            (Some(Ok(azimuth)), Some(Ok(altitude)), None)
+                if azimuth.is_finite() && (0.0..=90.0).contains(&altitude) =>
+            {
+                Ok(Sun { azimuth, altitude })
+            }
+            _ => Err(SunError),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Lighting {
+    pub sun: Sun,
+    /// Share of the light reaching every surface evenly, from 0 to 1.
+    pub ambient: f32,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Self {
+            sun: Sun::default(),
+            ambient: 0.35,
+        }
+    }
+}
+
+impl Lighting {
+    /// Colour of a surface of colour `base` facing along `normal`, seen from along `to_eye`, and
+    /// reflecting `specular` of the sun. Components may exceed 1 where the highlight is bright.
+    pub fn shade(
+        &self,
+        base: [f32; 3],
+        normal: [f32; 3],
+        to_eye: [f32; 3],
+        specular: f32,
+    ) -> [f32; 3] {
+        let sun = self.sun.direction();
+        let normal = normalize(normal);
+        let diffuse = dot(normal, sun).max(0.0);
+        let direct = 1.0 - self.ambient;
+        let light = self.ambient + direct * diffuse;
+
+        let mut shine = 0.0;
+        if specular > 0.0 && diffuse > 0.0 {
+            let to_eye = normalize(to_eye);
+            let halfway = normalize([sun[0] + to_eye[0], sun[1] + to_eye[1], sun[2] + to_eye[2]]);
+            shine = specular * direct * dot(normal, halfway).max(0.0).powf(SHININESS);
+        }
+        [
+            base[0] * light + shine,
+            base[1] * light + shine,
+            base[2] * light + shine,
+        ]
+    }
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(vector: [f32; 3]) -> [f32; 3] {
+    let length = dot(vector, vector).sqrt();
+    if length == 0.0 {
+        return vector;
+    }
+    [vector[0] / length, vector[1] / length, vector[2] / length]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slopes_facing_the_sun_are_brighter() {
+        let lighting = Lighting::default();
+        let green = [0.0, 1.0, 0.0];
+        let up = [0.0, 0.0, 1.0];
+        // Facing north west, towards the sun, and south east, away from it
+        let towards = lighting.shade(green, [-1.0, 1.0, 1.0], up, 0.0);
+        let away = lighting.shade(green, [1.0, -1.0, 1.0], up, 0.0);
+        let flat = lighting.shade(green, up, up, 0.0);
+        assert!(towards[1] > flat[1] && flat[1] > away[1]);
+        // Facing straight at the sun gets all of the light, and facing away only the ambient
+        assert!((lighting.shade(green, lighting.sun.direction(), up, 0.0)[1] - 1.0).abs() < 1e-5);
+        assert_eq!(away[1], lighting.ambient);
+
+        let unlit = Lighting {
+            ambient: 1.0,
+            ..Lighting::default()
+        };
+        assert_eq!(
+            unlit.shade(green, [1.0, -1.0, 1.0], up, WATER_SPECULAR),
+            green
+        );
+    }
+
+    #[test]
+    fn water_reflects_the_sun_towards_the_eye() {
+        let lighting = Lighting {
+            sun: Sun {
+                azimuth: 0.0,
+                altitude: 45.0,
+            },
+            ..Lighting::default()
+        };
+        let blue = [0.0, 0.0, 1.0];
+        let up = [0.0, 0.0, 1.0];
+        // Looking from the south, the mirror image of the sun in the north
+        let mirrored = lighting.shade(blue, up, [0.0, -1.0, 1.0], WATER_SPECULAR);
+        let elsewhere = lighting.shade(blue, up, [-1.0, 0.0, 0.2], WATER_SPECULAR);
+        assert!((mirrored[0] - WATER_SPECULAR * (1.0 - lighting.ambient)).abs() < 1e-5);
+        assert!(elsewhere[0] < 0.01);
+        assert_eq!(mirrored[2] - mirrored[0], elsewhere[2] - elsewhere[0]);
+    }
+
+    #[test]
+    fn sun_is_read_as_azimuth_and_altitude() {
+        assert_eq!(
+            "90, 30".parse(),
+            Ok(Sun {
+                azimuth: 90.0,
+                altitude: 30.0
+            })
+        );
+        assert_eq!("45".parse::<Sun>(), Err(SunError));
+        assert_eq!("45,100".parse::<Sun>(), Err(SunError));
+        assert_eq!("45,30,1".parse::<Sun>(), Err(SunError));
+        let east = Sun {
+            azimuth: 90.0,
+            altitude: 0.0,
+        }
+        .direction();
+        assert!((east[0] - 1.0).abs() < 1e-6 && east[1].abs() < 1e-6 && east[2].abs() < 1e-6);
+    }
+}
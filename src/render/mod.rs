@@ -1,5 +1,15 @@
+#[cfg(feature = "window")]
 pub mod events;
+pub mod indices;
+pub mod lighting;
+pub mod ramp;
+pub mod software;
 pub mod types;
 
+#[cfg(feature = "window")]
 pub use events::{key_event, mouse_scroll};
+pub use indices::Indices;
+pub use lighting::{Lighting, Sun};
+pub use ramp::Ramp;
+pub use software::Snapshot;
 pub use types::{CameraBuilder, CameraMatrix, Coord};
@@ -0,0 +1,307 @@
+//! Colour ramps that tint terrain by its elevation, from the lowest point of the map to the top.
+//!
+//! Ramps are either one of the named ones, `terrain`, `grayscale` and `viridis`, or read from a
+//! file of gradient stops. Every line of a ramp file holds a position and a colour as three
+//! values from 0 to 255, as in `0.5 232 214 140`. Positions only need to rise from one line to
+//! the next: the first is the bottom of the ramp, and the last the top, so that fractions and
+//! percentages work alike. Two stops at the same position make a sharp step from one colour to
+//! the next. Anything after a `#` is a comment, and blank lines are ignored.
+
+use crate::map::io::tokens;
+use crate::map::{Location, MapParseError};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::str::FromStr;
+
+/// Names of the built in ramps, as they are given on the command line.
+pub const RAMP_NAMES: [&str; 3] = ["terrain", "grayscale", "viridis"];
+
+/// Lowland greens, through sand and brown, up to snow.
+const TERRAIN: [(f32, [u8; 3]); 6] = [
+    (0.0, [38, 115, 77]),
+    (0.25, [140, 184, 94]),
+    (0.5, [232, 214, 140]),
+    (0.75, [158, 110, 64]),
+    (0.9, [200, 190, 180]),
+    (1.0, [255, 255, 255]),
+];
+
+const GRAYSCALE: [(f32, [u8; 3]); 2] = [(0.0, [0, 0, 0]), (1.0, [255, 255, 255])];
+
+/// Perceptually uniform, and readable by those with colour blindness.
+const VIRIDIS: [(f32, [u8; 3]); 9] = [
+    (0.0, [68, 1, 84]),
+    (0.125, [71, 45, 123]),
+    (0.25, [59, 82, 139]),
+    (0.375, [44, 114, 142]),
+    (0.5, [33, 145, 140]),
+    (0.625, [40, 174, 128]),
+    (0.75, [94, 201, 98]),
+    (0.875, [173, 220, 48]),
+    (1.0, [253, 231, 37]),
+];
+
+/// Colours at rising positions from 0, the lowest elevation, to 1, the highest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ramp {
+    stops: Vec<(f32, [f32; 3])>,
+}
+
+impl Default for Ramp {
+    fn default() -> Self {
+        Ramp::terrain()
+    }
+}
+
+impl Ramp {
+    pub fn terrain() -> Self {
+        Ramp::from_bytes(&TERRAIN)
+    }
+
+    /// Black at the bottom to white at the top.
+    pub fn grayscale() -> Self {
+        Ramp::from_bytes(&GRAYSCALE)
+    }
+
+    pub fn viridis() -> Self {
+        Ramp::from_bytes(&VIRIDIS)
+    }
+
+    /// Read a ramp from the file at `filename`.
+    pub fn open(filename: &str) -> Result<Self, MapParseError> {
+        let file = File::open(filename).map_err(|error| MapParseError::Io {
+            file: filename.to_string(),
+            error,
+        })?;
+        Ramp::read(BufReader::new(file), filename)
+    }
+
+    /// Parse the gradient stops in `reader`, reporting errors as being in `file`.
+    pub fn read<R: BufRead>(reader: R, file: &str) -> Result<Self, MapParseError> {
+        let mut stops: Vec<(f32, [u8; 3])> = Vec::new();
+        for (index, line) in reader.lines().enumerate() {
+            let line = line.map_err(|error| MapParseError::Io {
+                file: file.to_string(),
+                error,
+            })?;
+            let at = |column: usize| Location {
+                file: file.to_string(),
+                line: index + 1,
+                column,
+            };
+            let content = line.split('#').next().unwrap_or_default();
+            let tokens = tokens(content);
+            if tokens.is_empty() {
+                continue;
+            }
+            if tokens.len() != 4 {
+                return Err(MapParseError::Arity {
+                    location: at(tokens[0].0),
+                    expected: 4,
+                    found: tokens.len(),
+                });
+            }
+
+            let (column, value) = tokens[0];
+            let position = match value.parse::<f32>() {
+                Ok(position) if position.is_finite() => position,
+                _ => {
+                    return Err(MapParseError::BadFloat {
+                        location: at(column),
+                        found: value.to_string(),
+                    })
+                }
+            };
+            if stops.last().is_some_and(|&(last, _)| position < last) {
+                return Err(MapParseError::BadSetting {
+                    location: at(column),
+                    expected: "a position no lower than the one before",
+                    found: value.to_string(),
+                });
+            }
+
+            let mut colour = [0; 3];
+            for (channel, &(column, value)) in colour.iter_mut().zip(tokens[1..].iter()) {
+                *channel = value.parse::<u8>().map_err(|_| MapParseError::BadSetting {
+                    location: at(column),
+                    expected: "a colour value from 0 to 255",
+                    found: value.to_string(),
+                })?;
+            }
+            stops.push((position, colour));
+        }
+
+        let (first, last) = match (stops.first(), stops.last()) {
+            (Some(&(first, _)), Some(&(last, _))) => (first, last),
+            _ => {
+                return Err(MapParseError::Empty {
+                    file: file.to_string(),
+                })
+            }
+        };
+        // Stretch the positions given over the whole ramp
+        let span = last - first;
+        for stop in stops.iter_mut() {
+            stop.0 = if span > 0.0 {
+                (stop.0 - first) / span
+            } else {
+                0.0
+            };
+        }
+        Ok(Ramp::from_bytes(&stops))
+    }
+
+    /// Colour at `fraction` of the way up the ramp, with components from 0 to 1.
+    pub fn color(&self, fraction: f32) -> [f32; 3] {
+        let fraction = if fraction.is_nan() {
+            0.0
+        } else {
+            fraction.clamp(0.0, 1.0)
+        };
+        let above = self
+            .stops
+            .iter()
+            .position(|&(position, _)| position > fraction)
+            .unwrap_or(self.stops.len());
+        if above == 0 {
+            return self.stops[0].1;
+        }
+        let (low, low_color) = self.stops[above - 1];
+        let (high, high_color) = match self.stops.get(above) {
+            Some(&stop) => stop,
+            None => return low_color,
+        };
+        let t = (fraction - low) / (high - low);
+        [
+            low_color[0] + (high_color[0] - low_color[0]) * t,
+            low_color[1] + (high_color[1] - low_color[1]) * t,
+            low_color[2] + (high_color[2] - low_color[2]) * t,
+        ]
+    }
+
+    /// `count` colours evenly spaced from the bottom of the ramp to the top, to be looked up
+    /// by the window's fragment shader.
+    pub fn samples(&self, count: usize) -> Vec<(f32, f32, f32)> {
+        let last = (count.max(2) - 1) as f32;
+        (0..count)
+            .map(|index| {
+                let color = self.color(index as f32 / last);
+                (color[0], color[1], color[2])
+            })
+            .collect()
+    }
+
+    fn from_bytes(stops: &[(f32, [u8; 3])]) -> Self {
+        let channel = |value: u8| value as f32 / 255.0;
+        Self {
+            stops: stops
+                .iter()
+                .map(|&(position, [r, g, b])| (position, [channel(r), channel(g), channel(b)]))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RampError {
+    /// Neither the name of a ramp nor a file.
+    Unknown(String),
+    /// A ramp file that could not be read.
+    File(MapParseError),
+}
+
+impl fmt::Display for RampError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RampError::Unknown(found) => write!(
+                f,
+                "Ramp must be terrain, grayscale, viridis or a file of gradient stops, \
+                 found \"{}\"",
+                found
+            ),
+            RampError::File(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for RampError {}
+
+impl FromStr for Ramp {
+    type Err = RampError;
+
+    /// A ramp by name, or failing that read from the file at `s`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "terrain" => Ok(Ramp::terrain()),
+            "grayscale" | "greyscale" => Ok(Ramp::grayscale()),
+            "viridis" => Ok(Ramp::viridis()),
+            _ if Path::new(s).is_file() => Ramp::open(s).map_err(RampError::File),
+            _ => Err(RampError::Unknown(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colours_are_blended_between_stops() {
+        let ramp = Ramp::grayscale();
+        assert_eq!(ramp.color(0.0), [0.0, 0.0, 0.0]);
+        assert_eq!(ramp.color(0.5), [0.5, 0.5, 0.5]);
+        assert_eq!(ramp.color(1.0), [1.0, 1.0, 1.0]);
+        // Elevations beyond the range take the colour at the nearest end
+        assert_eq!(ramp.color(-3.0), [0.0, 0.0, 0.0]);
+        assert_eq!(ramp.color(7.0), [1.0, 1.0, 1.0]);
+
+        let viridis = Ramp::viridis();
+        let samples = viridis.samples(256);
+        assert_eq!(samples.len(), 256);
+        assert_eq!(samples[0], (68.0 / 255.0, 1.0 / 255.0, 84.0 / 255.0));
+        assert_eq!(samples[255], (253.0 / 255.0, 231.0 / 255.0, 37.0 / 255.0));
+    }
+
+    #[test]
+    fn ramps_are_found_by_name() {
+        for name in RAMP_NAMES.iter() {
+            assert!(name.parse::<Ramp>().is_ok(), "{}", name);
+        }
+        assert_eq!("Greyscale".parse::<Ramp>().unwrap(), Ramp::grayscale());
+        assert!(matches!(
+            "rainbow".parse::<Ramp>(),
+            Err(RampError::Unknown(name)) if name == "rainbow"
+        ));
+    }
+
+    #[test]
+    fn ramp_files_are_stretched_over_the_whole_ramp() -> Result<(), MapParseError> {
+        let file = "# Percentages, with a sharp step to snow\n\
+                    0 0 0 255\n\
+                    \n\
+                    50, 0, 255, 0\n\
+                    80 0 255 0 # Treeline\n\
+                    80 255 255 255\n\
+                    100 255 255 255\n";
+        let ramp = Ramp::read(file.as_bytes(), "bands.ramp")?;
+        assert_eq!(ramp.color(0.25), [0.0, 0.5, 0.5]);
+        assert_eq!(ramp.color(0.79), [0.0, 1.0, 0.0]);
+        assert_eq!(ramp.color(0.8), [1.0, 1.0, 1.0]);
+
+        let error = Ramp::read("0 0 0 0\n1 256 0 0\n".as_bytes(), "bad.ramp").unwrap_err();
+        assert_eq!(error.location().map(|location| location.column), Some(3));
+        let error = Ramp::read("1 0 0 0\n0 0 0 0\n".as_bytes(), "bad.ramp").unwrap_err();
+        assert_eq!(error.location().map(|location| location.line), Some(2));
+        assert!(matches!(
+            Ramp::read("0 0 0\n".as_bytes(), "bad.ramp"),
+            Err(MapParseError::Arity { found: 3, .. })
+        ));
+        assert!(matches!(
+            Ramp::read("# Nothing\n".as_bytes(), "bad.ramp"),
+            Err(MapParseError::Empty { .. })
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,404 @@
+//! Rendering on the CPU, without a window or a GPU.
+//!
+//! Meshes are drawn the same way the window draws them: through the camera's view, model and
+//! perspective matrices, with a depth test, and tinted and lit the same way as the fragment
+//! shader.
+//! This makes it possible to produce pictures of a map on headless machines.
+
+use super::lighting::WATER_SPECULAR;
+use super::types::Z_NEAR;
+use super::{CameraMatrix, Coord, Indices, Lighting, Ramp};
+use image::{ImageResult, Rgb, RgbImage};
+use std::path::Path;
+
+type Matrix = [[f32; 4]; 4];
+
+/// Elevations at the bottom and the top of the colour ramp, unless specified otherwise.
+pub const DEFAULT_ELEVATION_RANGE: (f32, f32) = (0.0, 255.0);
+
+/// A vertex after projection onto the image.
+#[derive(Copy, Clone, Debug)]
+struct ScreenVertex {
+    x: f32,
+    y: f32,
+    // Depth between 0.0 (near plane) and 1.0 (far plane)
+    depth: f32,
+    // Reciprocal of the clip space w, used for perspective correct interpolation
+    inverse_w: f32,
+    elevation: f32,
+    is_water: f32,
+    // Position before projection, and normal, for lighting
+    position: [f32; 3],
+    normal: [f32; 3],
+}
+
+/// Colour and depth buffers that meshes are drawn into, as seen through a camera.
+pub struct Snapshot {
+    camera: CameraMatrix,
+    perspective: Matrix,
+    image: RgbImage,
+    depth: Vec<f32>,
+    elevation_range: (f32, f32),
+    ramp: Ramp,
+    lighting: Lighting,
+}
+
+impl Snapshot {
+    /// An empty `width` by `height` picture, looking through `camera`.
+    pub fn new(width: u32, height: u32, camera: CameraMatrix) -> Self {
+        Self {
+            camera,
+            perspective: CameraMatrix::perspective(width, height),
+            image: RgbImage::new(width, height),
+            depth: vec![1.0; (width * height) as usize],
+            elevation_range: DEFAULT_ELEVATION_RANGE,
+            ramp: Ramp::default(),
+            lighting: Lighting::default(),
+        }
+    }
+
+    /// Tint terrain from the bottom of the ramp at `lowest` to its top at `highest`, as the window
+    /// does with the lowest and highest points of the map.
+    pub fn set_elevation_range(&mut self, lowest: f32, highest: f32) {
+        self.elevation_range = (lowest, highest);
+    }
+
+    pub fn set_ramp(&mut self, ramp: Ramp) {
+        self.ramp = ramp;
+    }
+
+    /// Light the meshes drawn from now on as the window does with `lighting`.
+    pub fn set_lighting(&mut self, lighting: Lighting) {
+        self.lighting = lighting;
+    }
+
+    /// Draw the triangles listed in `indices`, hiding whatever is behind them.
+    ///
+    /// Triangles with a corner behind the near plane are skipped rather than clipped.
+    pub fn draw(&mut self, vertices: &[Coord], indices: &Indices) {
+        let view = self.camera.mat4();
+        let model = self.camera.model();
+        let projected: Vec<Option<ScreenVertex>> = vertices
+            .iter()
+            .map(|vertex| self.project(vertex, &model, &view))
+            .collect();
+
+        let mut corners = indices.iter().map(|index| projected[index]);
+        while let (Some(a), Some(b), Some(c)) = (corners.next(), corners.next(), corners.next()) {
+            if let (Some(a), Some(b), Some(c)) = (a, b, c) {
+                self.rasterize(a, b, c);
+            }
+        }
+    }
+
+    pub fn image(&self) -> &RgbImage {
+        &self.image
+    }
+
+    pub fn into_image(self) -> RgbImage {
+        self.image
+    }
+
+    /// Save the picture, in the format given by the extension of `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> ImageResult<()> {
+        self.image.save(path)
+    }
+
+    fn project(&self, vertex: &Coord, model: &Matrix, view: &Matrix) -> Option<ScreenVertex> {
+        let world = [*vertex.x(), *vertex.y(), *vertex.z(), 1.0];
+        let clip = transform(&self.perspective, transform(view, transform(model, world)));
+        if clip[3] < Z_NEAR {
+            return None;
+        }
+
+        let (width, height) = self.image.dimensions();
+        let inverse_w = 1.0 / clip[3];
+        Some(ScreenVertex {
+            x: (clip[0] * inverse_w + 1.0) / 2.0 * width as f32,
+            y: (1.0 - clip[1] * inverse_w) / 2.0 * height as f32,
+            depth: (clip[2] * inverse_w + 1.0) / 2.0,
+            inverse_w,
+            elevation: *vertex.z(),
+            is_water: vertex.is_water,
+            position: vertex.vec3(),
+            normal: [vertex.normal.0, vertex.normal.1, vertex.normal.2],
+        })
+    }
+
+    fn rasterize(&mut self, a: ScreenVertex, b: ScreenVertex, c: ScreenVertex) {
+        let area = edge(&a, &b, c.x, c.y);
+        if area == 0.0 {
+            return;
+        }
+
+        let (width, height) = self.image.dimensions();
+        let min_x = a.x.min(b.x).min(c.x).floor().max(0.0) as u32;
+        let min_y = a.y.min(b.y).min(c.y).floor().max(0.0) as u32;
+        let max_x = a.x.max(b.x).max(c.x).ceil().min(width as f32) as u32;
+        let max_y = a.y.max(b.y).max(c.y).ceil().min(height as f32) as u32;
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                // Sample at the centre of the pixel
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let weight_a = edge(&b, &c, px, py) / area;
+                let weight_b = edge(&c, &a, px, py) / area;
+                let weight_c = edge(&a, &b, px, py) / area;
+                if weight_a < 0.0 || weight_b < 0.0 || weight_c < 0.0 {
+                    continue;
+                }
+
+                let depth = weight_a * a.depth + weight_b * b.depth + weight_c * c.depth;
+                let index = (y * width + x) as usize;
+                if !(0.0..=1.0).contains(&depth) || depth >= self.depth[index] {
+                    continue;
+                }
+                self.depth[index] = depth;
+
+                let inverse_w =
+                    weight_a * a.inverse_w + weight_b * b.inverse_w + weight_c * c.inverse_w;
+                let interpolate = |attribute: fn(&ScreenVertex) -> f32| {
+                    (weight_a * attribute(&a) * a.inverse_w
+                        + weight_b * attribute(&b) * b.inverse_w
+                        + weight_c * attribute(&c) * c.inverse_w)
+                        / inverse_w
+                };
+                let position = [
+                    interpolate(|vertex| vertex.position[0]),
+                    interpolate(|vertex| vertex.position[1]),
+                    interpolate(|vertex| vertex.position[2]),
+                ];
+                let normal = [
+                    interpolate(|vertex| vertex.normal[0]),
+                    interpolate(|vertex| vertex.normal[1]),
+                    interpolate(|vertex| vertex.normal[2]),
+                ];
+                let eye = self.camera.eye();
+                let to_eye = [
+                    eye[0] - position[0],
+                    eye[1] - position[1],
+                    eye[2] - position[2],
+                ];
+                // Flat maps are tinted with the bottom of the ramp
+                let (lowest, highest) = self.elevation_range;
+                let elevation = interpolate(|vertex| vertex.elevation);
+                let fraction = if highest > lowest {
+                    (elevation - lowest) / (highest - lowest)
+                } else {
+                    0.0
+                };
+                let color = shade(
+                    &self.lighting,
+                    self.ramp.color(fraction),
+                    interpolate(|vertex| vertex.is_water),
+                    normal,
+                    to_eye,
+                );
+                self.image.put_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+/// Same colours as the fragment shader used by the window, for terrain of colour `tint`, lit by
+/// `lighting`.
+fn shade(
+    lighting: &Lighting,
+    tint: [f32; 3],
+    is_water: f32,
+    normal: [f32; 3],
+    to_eye: [f32; 3],
+) -> Rgb<u8> {
+    let color = if is_water > 0.5 {
+        lighting.shade([0.0, 0.0, 1.0], normal, to_eye, WATER_SPECULAR)
+    } else {
+        lighting.shade(tint, normal, to_eye, 0.0)
+    };
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    Rgb([channel(color[0]), channel(color[1]), channel(color[2])])
+}
+
+/// Twice the signed area of the triangle (`a`, `b`, (`x`, `y`)).
+fn edge(a: &ScreenVertex, b: &ScreenVertex, x: f32, y: f32) -> f32 {
+    (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+}
+
+/// Multiply `vector` by a column major `matrix`, as GLSL does.
+fn transform(matrix: &Matrix, vector: [f32; 4]) -> [f32; 4] {
+    let mut result = [0.0; 4];
+    for (column, value) in matrix.iter().zip(vector.iter()) {
+        for (component, entry) in result.iter_mut().zip(column.iter()) {
+            *component += entry * value;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::{CameraBuilder, Sun};
+
+    /// Camera 100 units above the origin, looking straight down with north at the top.
+    fn camera() -> CameraMatrix {
+        CameraBuilder::new()
+            .zoom(0.0)
+            .position(Coord::new(0.0, 0.0, 100.0))
+            .direction(Coord::new(0.0, 0.0, -1.0))
+            .up(Coord::new(0.0, 1.0, 0.0))
+            .build()
+    }
+
+    /// A square 2 * `half_size` across, centred on the origin at `elevation`.
+    fn square(half_size: f32, elevation: f32) -> (Vec<Coord>, Indices) {
+        let vertices = vec![
+            Coord::new(-half_size, -half_size, elevation),
+            Coord::new(half_size, -half_size, elevation),
+            Coord::new(-half_size, half_size, elevation),
+            Coord::new(half_size, half_size, elevation),
+        ];
+        (vertices, Indices::from(vec![0u16, 1, 2, 1, 3, 2]))
+    }
+
+    /// A grayscale picture lit by ambient light alone, so that colours depend on elevation only.
+    fn unlit(width: u32, height: u32) -> Snapshot {
+        let mut snapshot = Snapshot::new(width, height, camera());
+        snapshot.set_ramp(Ramp::grayscale());
+        snapshot.set_lighting(Lighting {
+            ambient: 1.0,
+            ..Lighting::default()
+        });
+        snapshot
+    }
+
+    #[test]
+    fn terrain_is_coloured_by_elevation() {
+        let mut snapshot = unlit(64, 48);
+        let (vertices, indices) = square(10.0, 51.0);
+        snapshot.draw(&vertices, &indices);
+
+        let image = snapshot.into_image();
+        assert_eq!(*image.get_pixel(32, 24), Rgb([51, 51, 51]));
+        // The square covers only the middle of the picture
+        assert_eq!(*image.get_pixel(0, 0), Rgb([0, 0, 0]));
+
+        let mut snapshot = unlit(64, 48);
+        snapshot.set_elevation_range(0.0, 102.0);
+        snapshot.draw(&vertices, &indices);
+        assert_eq!(*snapshot.image().get_pixel(32, 24), Rgb([128, 128, 128]));
+
+        // Terrain below sea level is tinted too
+        let mut snapshot = unlit(64, 48);
+        snapshot.set_elevation_range(-110.0, 90.0);
+        snapshot.draw(&square(10.0, -100.0).0, &indices);
+        assert_eq!(*snapshot.image().get_pixel(32, 24), Rgb([13, 13, 13]));
+
+        let mut snapshot = unlit(64, 48);
+        snapshot.set_ramp(Ramp::viridis());
+        snapshot.set_elevation_range(51.0, 51.0);
+        snapshot.draw(&vertices, &indices);
+        assert_eq!(*snapshot.image().get_pixel(32, 24), Rgb([68, 1, 84]));
+    }
+
+    #[test]
+    fn nearer_triangles_hide_further_ones() {
+        let (terrain, indices) = square(30.0, 20.0);
+        let (water, _) = square(5.0, 30.0);
+        let water: Vec<Coord> = water.into_iter().map(Coord::set_as_water).collect();
+
+        // Drawing order makes no difference to what ends up visible
+        let mut first = unlit(64, 48);
+        first.draw(&terrain, &indices);
+        first.draw(&water, &indices);
+        let mut second = unlit(64, 48);
+        second.draw(&water, &indices);
+        second.draw(&terrain, &indices);
+
+        assert_eq!(first.image(), second.image());
+        assert_eq!(*first.image().get_pixel(32, 24), Rgb([0, 0, 255]));
+        assert_eq!(*first.image().get_pixel(32, 24 - 10), Rgb([20, 20, 20]));
+    }
+
+    #[test]
+    fn north_is_at_the_top() {
+        let mut snapshot = Snapshot::new(64, 64, camera());
+        let vertices = vec![
+            Coord::new(-10.0, 0.0, 50.0),
+            Coord::new(10.0, 0.0, 50.0),
+            Coord::new(0.0, 20.0, 50.0),
+        ];
+        snapshot.draw(&vertices, &Indices::from(vec![0u16, 1, 2]));
+        let marked: Vec<u32> = (0..64)
+            .filter(|&y| snapshot.image().get_pixel(32, y) != &Rgb([0, 0, 0]))
+            .collect();
+        assert!(!marked.is_empty());
+        assert!(marked.iter().all(|&y| y < 32));
+    }
+
+    #[test]
+    fn meshes_beyond_16_bit_indices_are_drawn() {
+        // Far more vertices than 16 bit indices can address, with the square at the end
+        let mut vertices = vec![Coord::new(0.0, 0.0, -1000.0); 70_000];
+        let (square, _) = square(10.0, 51.0);
+        vertices.extend(square);
+        let mut indices = Indices::new(vertices.len());
+        for &corner in [0, 1, 2, 1, 3, 2].iter() {
+            indices.push(70_000 + corner);
+        }
+
+        let mut snapshot = unlit(64, 48);
+        snapshot.draw(&vertices, &indices);
+        assert_eq!(*snapshot.image().get_pixel(32, 24), Rgb([51, 51, 51]));
+    }
+
+    #[test]
+    fn slopes_are_lit_by_the_sun() {
+        let (mut vertices, indices) = square(10.0, 51.0);
+        let brightness = |vertices: &[Coord]| {
+            let mut snapshot = Snapshot::new(64, 48, camera());
+            snapshot.set_ramp(Ramp::grayscale());
+            snapshot.draw(vertices, &indices);
+            snapshot.image().get_pixel(32, 24)[1]
+        };
+        let flat = brightness(&vertices);
+        // Tilted towards the sun in the north west, then away from it
+        for vertex in vertices.iter_mut() {
+            vertex.normal = (-0.5, 0.5, 0.7);
+        }
+        let towards = brightness(&vertices);
+        for vertex in vertices.iter_mut() {
+            vertex.normal = (0.5, -0.5, 0.7);
+        }
+        let away = brightness(&vertices);
+        assert!(towards > flat && flat > away && away > 0);
+        assert!(towards <= 51);
+
+        // Water seen from straight above catches the highlight of a sun straight above
+        let water: Vec<Coord> = vertices
+            .into_iter()
+            .map(|vertex| Coord::new(*vertex.x(), *vertex.y(), 51.0).set_as_water())
+            .collect();
+        let mut snapshot = Snapshot::new(64, 48, camera());
+        snapshot.set_lighting(Lighting {
+            sun: Sun {
+                azimuth: 0.0,
+                altitude: 90.0,
+            },
+            ..Lighting::default()
+        });
+        snapshot.draw(&water, &indices);
+        let pixel = snapshot.image().get_pixel(32, 24);
+        assert!(pixel[0] > 60 && pixel[0] == pixel[1] && pixel[2] == 255);
+    }
+
+    #[test]
+    fn triangles_behind_the_camera_are_skipped() {
+        let mut snapshot = Snapshot::new(32, 32, camera());
+        let (vertices, indices) = square(10.0, 150.0);
+        snapshot.draw(&vertices, &indices);
+        assert!(snapshot
+            .image()
+            .pixels()
+            .all(|pixel| *pixel == Rgb([0, 0, 0])));
+    }
+}
@@ -1,13 +1,12 @@
-use glium;
-
 use crate::delauney::Vec2;
 use std::fmt;
 use std::ops::Index;
-use std::str::FromStr;
 
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
 pub struct Coord {
     pub position: (f32, f32, f32),
+    /// Unit normal of the surface at the vertex, for lighting. Straight up unless set otherwise.
+    pub normal: (f32, f32, f32),
     pub is_water: f32,
 }
 
@@ -15,6 +14,7 @@ impl Coord {
     pub fn new(x: f32, y: f32, z: f32) -> Self {
         Self {
             position: (x, y, z),
+            normal: (0.0, 0.0, 1.0),
             is_water: 0.0,
         }
     }
@@ -51,25 +51,6 @@ impl Coord {
     }
 }
 
-// Could change to enum, and make ParseCoordError with 'kind' parameter, for more accurate error
-// output, if there is time
-#[derive(Debug, PartialEq)]
-pub struct CoordError;
-
-impl fmt::Display for CoordError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Invalid Coordinate")
-    }
-}
-
-impl From<std::num::ParseFloatError> for CoordError {
-    fn from(_: std::num::ParseFloatError) -> Self {
-        Self
-    }
-}
-
-impl std::error::Error for CoordError {}
-
 // Implicitly implements ToString
 impl fmt::Display for Coord {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -77,30 +58,6 @@ impl fmt::Display for Coord {
     }
 }
 
-impl FromStr for Coord {
-    type Err = CoordError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.len() < 7 {
-            return Err(CoordError);
-        }
-        if s.chars().next().unwrap() != '(' || s.chars().last().unwrap() != ')' {
-            return Err(CoordError);
-        }
-        let vals: Vec<&str> = s
-            .trim_matches(|p| p == '(' || p == ')')
-            .split(',')
-            .collect();
-        if vals.len() != 3 {
-            return Err(CoordError);
-        }
-        let x = vals[0].trim().parse::<f32>()?;
-        let y = vals[1].trim().parse::<f32>()?;
-        let z = vals[2].trim().parse::<f32>()?;
-        Ok(Coord::new(x, y, z))
-    }
-}
-
 impl Index<usize> for Coord {
     type Output = f32;
 
@@ -114,7 +71,15 @@ impl Index<usize> for Coord {
     }
 }
 
-glium::implement_vertex!(Coord, position, is_water);
+#[cfg(feature = "window")]
+glium::implement_vertex!(Coord, position, normal, is_water);
+
+/// Vertical field of view of the camera, in radians.
+pub const FIELD_OF_VIEW: f32 = std::f32::consts::PI / 3.0;
+/// Distance to the near clipping plane. Anything closer to the camera is not drawn.
+pub const Z_NEAR: f32 = 0.1;
+/// Distance to the far clipping plane. Anything further from the camera is not drawn.
+pub const Z_FAR: f32 = 1024.0;
 
 #[derive(Copy, Clone, Debug)]
 pub struct CameraMatrix {
@@ -166,6 +131,38 @@ impl CameraMatrix {
             [p[0], p[1], p[2], 1.0],
         ]
     }
+
+    /// Translation applied to the whole scene, zooming it towards or away from the camera.
+    pub fn model(&self) -> [[f32; 4]; 4] {
+        [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, self.zoom, 1.0f32],
+        ]
+    }
+
+    /// Position of the camera among the untranslated vertices, undoing the zoom of `model`.
+    pub fn eye(&self) -> [f32; 3] {
+        [
+            self.position[0],
+            self.position[1],
+            self.position[2] - self.zoom,
+        ]
+    }
+
+    /// Perspective projection onto a target `width` by `height` pixels in size.
+    pub fn perspective(width: u32, height: u32) -> [[f32; 4]; 4] {
+        let aspect_ratio = height as f32 / width as f32;
+        let f = 1.0 / (FIELD_OF_VIEW / 2.0).tan();
+
+        [
+            [f * aspect_ratio, 0.0, 0.0, 0.0],
+            [0.0, f, 0.0, 0.0],
+            [0.0, 0.0, (Z_FAR + Z_NEAR) / (Z_FAR - Z_NEAR), 1.0],
+            [0.0, 0.0, -(2.0 * Z_FAR * Z_NEAR) / (Z_FAR - Z_NEAR), 0.0],
+        ]
+    }
 }
 
 #[derive(Default)]
@@ -0,0 +1,167 @@
+//! Writing the water depth grid to disk, one file per frame, so that runs can be compared
+//! without a window.
+
+use crate::water::WaterGrid;
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// Grey levels per unit of depth in PGM frames. Depths of 256 or more saturate to white.
+pub const PGM_DEPTH_SCALE: f32 = 256.0;
+
+/// File format for a single frame of water depths.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FrameFormat {
+    /// One line of comma separated depths per grid row, starting with the southernmost row.
+    Csv,
+    /// Depths as little endian `f32`s, row by row in the same order as `WaterGrid::depths`.
+    Raw,
+    /// 16 bit greyscale image seen from above, with north at the top.
+    Pgm,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FrameFormatError;
+
+impl fmt::Display for FrameFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Frame format must be one of csv, raw or pgm")
+    }
+}
+
+impl std::error::Error for FrameFormatError {}
+
+impl FromStr for FrameFormat {
+    type Err = FrameFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(FrameFormat::Csv),
+            "raw" | "f32" => Ok(FrameFormat::Raw),
+            "pgm" => Ok(FrameFormat::Pgm),
+            _ => Err(FrameFormatError),
+        }
+    }
+}
+
+impl FrameFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FrameFormat::Csv => "csv",
+            FrameFormat::Raw => "f32",
+            FrameFormat::Pgm => "pgm",
+        }
+    }
+
+    pub fn write<W: Write>(&self, water: &WaterGrid, writer: &mut W) -> io::Result<()> {
+        self.write_scaled(water, 1.0, writer)
+    }
+
+    /// Write the depths multiplied by `depth_scale`, such as to convert them into the units of
+    /// the map file.
+    pub fn write_scaled<W: Write>(
+        &self,
+        water: &WaterGrid,
+        depth_scale: f32,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        let columns = water.columns();
+        let depths: Vec<f32> = water
+            .depths()
+            .iter()
+            .map(|depth| depth * depth_scale)
+            .collect();
+        match self {
+            FrameFormat::Csv => {
+                for row in depths.chunks(columns) {
+                    let line: Vec<String> = row.iter().map(|depth| depth.to_string()).collect();
+                    writeln!(writer, "{}", line.join(","))?;
+                }
+            }
+            FrameFormat::Raw => {
+                for depth in depths.iter() {
+                    writer.write_all(&depth.to_le_bytes())?;
+                }
+            }
+            FrameFormat::Pgm => {
+                write!(writer, "P5\n{} {}\n65535\n", columns, water.rows())?;
+                for row in depths.chunks(columns).rev() {
+                    for depth in row {
+                        let level = (depth * PGM_DEPTH_SCALE).round().clamp(0.0, 65535.0) as u16;
+                        writer.write_all(&level.to_be_bytes())?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{Heightfield, Rectangle};
+    use crate::render::Coord;
+
+    /// A 3x2 grid with depths increasing from the south west corner.
+    fn water() -> WaterGrid {
+        let base = Rectangle {
+            origin: Coord::new(0.0, 0.0, 0.0),
+            x_size: 20.0,
+            y_size: 10.0,
+        };
+        let heightfield = Heightfield::from_heights(base, 3, 2, vec![0.0; 6]);
+        let mut water = WaterGrid::new(&heightfield);
+        for (index, depth) in water.depths_mut().iter_mut().enumerate() {
+            *depth = index as f32 * 0.5;
+        }
+        water
+    }
+
+    #[test]
+    fn csv_has_a_line_per_row() {
+        let mut out = Vec::new();
+        FrameFormat::Csv.write(&water(), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "0,0.5,1\n1.5,2,2.5\n");
+    }
+
+    #[test]
+    fn raw_round_trips() {
+        let mut out = Vec::new();
+        FrameFormat::Raw.write(&water(), &mut out).unwrap();
+        let depths: Vec<f32> = out
+            .chunks(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+        assert_eq!(depths, water().depths());
+    }
+
+    #[test]
+    fn pgm_has_north_at_the_top() {
+        let mut out = Vec::new();
+        FrameFormat::Pgm.write(&water(), &mut out).unwrap();
+        let header = b"P5\n3 2\n65535\n";
+        assert_eq!(&out[..header.len()], header);
+        let pixels = &out[header.len()..];
+        assert_eq!(pixels.len(), 6 * 2);
+        // First pixel is the north west corner, at depth 1.5
+        assert_eq!(u16::from_be_bytes([pixels[0], pixels[1]]), 384);
+        assert_eq!(u16::from_be_bytes([pixels[6], pixels[7]]), 0);
+    }
+
+    #[test]
+    fn depths_can_be_scaled() {
+        let mut out = Vec::new();
+        FrameFormat::Csv
+            .write_scaled(&water(), 2.0, &mut out)
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "0,1,2\n3,4,5\n");
+    }
+
+    #[test]
+    fn formats_can_be_parsed() {
+        assert_eq!("CSV".parse::<FrameFormat>(), Ok(FrameFormat::Csv));
+        assert_eq!("f32".parse::<FrameFormat>(), Ok(FrameFormat::Raw));
+        assert_eq!("png".parse::<FrameFormat>(), Err(FrameFormatError));
+    }
+}
@@ -0,0 +1,345 @@
+//! Water simulation over a heightfield, independent of any window or graphics context.
+//!
+//! A `Simulation` owns the terrain and the water on top of it, and advances whichever
+//! `Scenario` it was built with every time `step` is called. Renderers only need to read the
+//! `heightfield` and `water` back out of it.
+
+pub mod frame;
+
+pub use frame::FrameFormat;
+
+use crate::map::Heightfield;
+use crate::water::{self, Drain, Pipes, Rain, Side, Sink, WaterGrid, Wave};
+
+/// How water is introduced to the map.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Scenario {
+    /// The sea around the map rises evenly to the water level.
+    Flood,
+    /// A wave crashes in from one side, pushed by a sea at the water level.
+    Wave(Side),
+    /// Rain falls at the given number of droplets per second and runs off downhill.
+    Rain(f32),
+    /// The map starts submerged up to the water level, and drains through its sinks.
+    Drain,
+}
+
+impl Scenario {
+    /// Parse a scenario from command line arguments: `flood`, `wave [side]`, `rain [rate]` or
+    /// `drain [sinks...]`. No arguments at all means flooding.
+    ///
+    /// Sinks are only accepted after `drain`, and are returned alongside the scenario.
+    pub fn from_args(args: &[String]) -> Result<(Self, Vec<Sink>), &'static str> {
+        let mut args = args.iter().map(String::as_str);
+        let scenario = match args.next() {
+            None | Some("flood") => Scenario::Flood,
+            Some("wave") => match args.next() {
+                Some(side) => match side.parse::<Side>() {
+                    Ok(side) => Scenario::Wave(side),
+                    Err(_) => return Err("Wave side must be one of north, east, south or west"),
+                },
+                None => Scenario::Wave(Side::West),
+            },
+            Some("rain") => match args.next() {
+                Some(rate) => match rate.parse::<f32>() {
+                    Ok(rate) if rate >= 0.0 => Scenario::Rain(rate),
+                    _ => return Err("Rain rate must be a positive number of droplets per second"),
+                },
+                None => Scenario::Rain(water::rain::DEFAULT_RATE),
+            },
+            Some("drain") => Scenario::Drain,
+            Some(_) => return Err("Scenario must be one of flood, wave, rain or drain"),
+        };
+
+        let mut sinks = Vec::new();
+        for sink in args {
+            match sink.parse::<Sink>() {
+                Ok(sink) if scenario == Scenario::Drain => sinks.push(sink),
+                _ => return Err("Sinks must be either edges or a point x,y, after drain"),
+            }
+        }
+        Ok((scenario, sinks))
+    }
+
+    /// Water level the scenario starts at, on a map rising to `elevation_max`. Floods and rain
+    /// start dry, waves and drains start with the sea at the top of the map.
+    pub fn initial_water_level(&self, elevation_max: f32) -> f32 {
+        match self {
+            Scenario::Flood | Scenario::Rain(_) => 0.0,
+            Scenario::Wave(_) | Scenario::Drain => elevation_max,
+        }
+    }
+}
+
+/// State of the water after a step.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Report {
+    /// Simulated time since the start, in seconds.
+    pub time: f32,
+    /// Volume of water on the map.
+    pub volume: f32,
+    /// Number of samples holding more than a film of water.
+    pub wet_count: usize,
+    /// Volume of water that has left the map through sinks.
+    pub drained: f32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Simulation {
+    heightfield: Heightfield,
+    water: WaterGrid,
+    pipes: Pipes,
+    scenario: Scenario,
+    water_level: f32,
+    rain: Rain,
+    drain: Drain,
+    // Level the water was last flooded to, so that floods are only recalculated on change
+    flooded_level: Option<f32>,
+    time: f32,
+}
+
+impl Simulation {
+    /// Advance the scenario by `dt` seconds.
+    pub fn step(&mut self, dt: f32) -> Report {
+        match self.scenario {
+            Scenario::Flood => {
+                if self.flooded_level != Some(self.water_level) {
+                    self.water.flood(&self.heightfield, self.water_level);
+                    self.flooded_level = Some(self.water_level);
+                }
+            }
+            Scenario::Wave(side) => {
+                Wave::new(side, self.water_level).feed(&self.heightfield, &mut self.water);
+                self.pipes.step(&self.heightfield, &mut self.water, dt);
+            }
+            Scenario::Rain(_) => {
+                self.rain.fall(&mut self.water, dt);
+                self.pipes.step(&self.heightfield, &mut self.water, dt);
+            }
+            Scenario::Drain => {
+                self.pipes.step(&self.heightfield, &mut self.water, dt);
+                self.drain.drain(&self.heightfield, &mut self.water);
+            }
+        }
+        self.time += dt;
+        self.report()
+    }
+
+    pub fn report(&self) -> Report {
+        Report {
+            time: self.time,
+            volume: self.water.volume(&self.heightfield),
+            wet_count: self.water.wet_count(),
+            drained: self.drain.drained(),
+        }
+    }
+
+    pub fn heightfield(&self) -> &Heightfield {
+        &self.heightfield
+    }
+
+    pub fn water(&self) -> &WaterGrid {
+        &self.water
+    }
+
+    pub fn scenario(&self) -> Scenario {
+        self.scenario
+    }
+
+    pub fn water_level(&self) -> f32 {
+        self.water_level
+    }
+
+    /// Change the flood level, or the level of the sea pushing a wave.
+    pub fn set_water_level(&mut self, water_level: f32) {
+        self.water_level = water_level;
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+}
+
+pub struct SimulationBuilder {
+    heightfield: Heightfield,
+    scenario: Scenario,
+    water_level: f32,
+    sinks: Vec<Sink>,
+    seed: u64,
+}
+
+impl SimulationBuilder {
+    pub fn new(heightfield: Heightfield) -> Self {
+        Self {
+            heightfield,
+            scenario: Scenario::Flood,
+            water_level: 0.0,
+            sinks: Vec::new(),
+            seed: 0,
+        }
+    }
+    pub fn scenario(mut self, scenario: Scenario) -> SimulationBuilder {
+        self.scenario = scenario;
+        self
+    }
+    pub fn water_level(mut self, water_level: f32) -> SimulationBuilder {
+        self.water_level = water_level;
+        self
+    }
+    /// Where water leaves the map in the drain scenario. Defaults to the edges of the map.
+    pub fn sinks(mut self, sinks: &[Sink]) -> SimulationBuilder {
+        self.sinks = sinks.to_vec();
+        self
+    }
+    /// Seed for anything random in the scenario, such as where rain falls.
+    pub fn seed(mut self, seed: u64) -> SimulationBuilder {
+        self.seed = seed;
+        self
+    }
+    pub fn build(self) -> Simulation {
+        let mut water = WaterGrid::new(&self.heightfield);
+        let pipes = Pipes::new(&water);
+        let sinks = if self.sinks.is_empty() {
+            vec![Sink::Edges]
+        } else {
+            self.sinks
+        };
+        let drain = Drain::new(&sinks, &self.heightfield);
+        let rain = match self.scenario {
+            Scenario::Rain(rate) => Rain::new(rate, self.seed),
+            _ => Rain::new(0.0, self.seed),
+        };
+
+        match self.scenario {
+            Scenario::Wave(side) => {
+                Wave::new(side, self.water_level).start(&self.heightfield, &mut water)
+            }
+            Scenario::Drain => water.fill_to(&self.heightfield, self.water_level),
+            Scenario::Flood | Scenario::Rain(_) => (),
+        }
+
+        Simulation {
+            heightfield: self.heightfield,
+            water,
+            pipes,
+            scenario: self.scenario,
+            water_level: self.water_level,
+            rain,
+            drain,
+            flooded_level: None,
+            time: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Rectangle;
+    use crate::render::Coord;
+
+    /// A 9x9 map with a hill in the middle, sloping down to the edges.
+    fn hill() -> Heightfield {
+        let base = Rectangle {
+            origin: Coord::new(0.0, 0.0, 0.0),
+            x_size: 80.0,
+            y_size: 80.0,
+        };
+        let mut heights = Vec::new();
+        for row in 0..9 {
+            for column in 0..9 {
+                let distance = (column as f32 - 4.0).abs().max((row as f32 - 4.0).abs());
+                heights.push(40.0 - distance * 10.0);
+            }
+        }
+        Heightfield::from_heights(base, 9, 9, heights)
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn scenarios_are_parsed_from_args() {
+        assert_eq!(Scenario::from_args(&[]), Ok((Scenario::Flood, vec![])));
+        assert_eq!(
+            Scenario::from_args(&args(&["wave", "north"])),
+            Ok((Scenario::Wave(Side::North), vec![]))
+        );
+        assert_eq!(
+            Scenario::from_args(&args(&["drain", "edges", "10,20"])),
+            Ok((Scenario::Drain, vec![Sink::Edges, Sink::Point(10.0, 20.0)]))
+        );
+        assert!(Scenario::from_args(&args(&["rain", "-5"])).is_err());
+        assert!(Scenario::from_args(&args(&["flood", "edges"])).is_err());
+    }
+
+    #[test]
+    fn flood_follows_water_level() {
+        let mut simulation = SimulationBuilder::new(hill()).water_level(15.0).build();
+
+        let report = simulation.step(0.1);
+        assert_eq!(report.wet_count, 9 * 9 - 5 * 5);
+
+        simulation.set_water_level(45.0);
+        let report = simulation.step(0.1);
+        assert_eq!(report.wet_count, 9 * 9);
+        assert_eq!(simulation.water().depth(4, 4), 5.0);
+    }
+
+    #[test]
+    fn wave_rolls_over_the_hill() {
+        let mut simulation = SimulationBuilder::new(hill())
+            .scenario(Scenario::Wave(Side::North))
+            .water_level(50.0)
+            .build();
+
+        assert!(simulation.water().is_wet(4, 8));
+        assert!(!simulation.water().is_wet(4, 0));
+        for _ in 0..300 {
+            simulation.step(0.1);
+        }
+        assert!(simulation.water().is_wet(4, 4));
+        assert!(simulation.water().is_wet(4, 0));
+    }
+
+    #[test]
+    fn rain_is_reproducible() {
+        let run = || {
+            let mut simulation = SimulationBuilder::new(hill())
+                .scenario(Scenario::Rain(100.0))
+                .seed(11)
+                .build();
+            for _ in 0..50 {
+                simulation.step(0.1);
+            }
+            simulation.water().depths().to_vec()
+        };
+        assert_eq!(run(), run());
+    }
+
+    #[test]
+    fn long_steps_finish() {
+        let mut simulation = SimulationBuilder::new(hill())
+            .scenario(Scenario::Rain(100.0))
+            .build();
+        let report = simulation.step(1e30);
+        assert!(report.volume.is_finite() && report.volume > 0.0);
+    }
+
+    #[test]
+    fn drain_conserves_volume() {
+        let mut simulation = SimulationBuilder::new(hill())
+            .scenario(Scenario::Drain)
+            .water_level(30.0)
+            .build();
+        let initial = simulation.report().volume;
+
+        for _ in 0..100 {
+            let report = simulation.step(0.1);
+            assert!((report.volume + report.drained - initial).abs() / initial < 1e-3);
+        }
+        assert!(simulation.report().drained > 0.0);
+        assert_eq!(simulation.time(), simulation.report().time);
+    }
+}
@@ -0,0 +1,184 @@
+use super::WaterGrid;
+use crate::map::Heightfield;
+use std::fmt;
+use std::str::FromStr;
+
+/// A place where water leaves the map.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Sink {
+    /// Drains the sample closest to (x, y), like a plughole.
+    Point(f32, f32),
+    /// Drains every sample along the edges of the map, as if it were surrounded by a cliff.
+    Edges,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SinkError;
+
+impl fmt::Display for SinkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Sink must be either edges or a point (x,y)")
+    }
+}
+
+impl std::error::Error for SinkError {}
+
+impl From<std::num::ParseFloatError> for SinkError {
+    fn from(_: std::num::ParseFloatError) -> Self {
+        Self
+    }
+}
+
+impl fmt::Display for Sink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Sink::Point(x, y) => write!(f, "({},{})", x, y),
+            Sink::Edges => write!(f, "edges"),
+        }
+    }
+}
+
+impl FromStr for Sink {
+    type Err = SinkError;
+
+    /// Accepts `edges`, or a point as `x,y` with optional surrounding parentheses.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("edges") {
+            return Ok(Sink::Edges);
+        }
+        let vals: Vec<&str> = s
+            .trim_start_matches('(')
+            .trim_end_matches(')')
+            .split(',')
+            .collect();
+        if vals.len() != 2 {
+            return Err(SinkError);
+        }
+        let x = vals[0].trim().parse::<f32>()?;
+        let y = vals[1].trim().parse::<f32>()?;
+        Ok(Sink::Point(x, y))
+    }
+}
+
+/// Removes all water reaching a set of sinks, keeping track of how much has left the map.
+#[derive(Clone, Debug)]
+pub struct Drain {
+    samples: Vec<usize>,
+    drained: f32,
+}
+
+impl Drain {
+    pub fn new(sinks: &[Sink], heightfield: &Heightfield) -> Self {
+        let (columns, rows) = (heightfield.columns(), heightfield.rows());
+        let mut samples = Vec::new();
+        for sink in sinks.iter() {
+            match *sink {
+                Sink::Point(x, y) => {
+                    let (column, row) = heightfield.nearest(x, y);
+                    samples.push(row * columns + column);
+                }
+                Sink::Edges => {
+                    for row in 0..rows {
+                        for column in 0..columns {
+                            if row == 0 || column == 0 || row == rows - 1 || column == columns - 1 {
+                                samples.push(row * columns + column);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        samples.sort_unstable();
+        samples.dedup();
+        Self {
+            samples,
+            drained: 0.0,
+        }
+    }
+
+    /// Empty every sink, returning the volume of water removed.
+    pub fn drain(&mut self, heightfield: &Heightfield, water: &mut WaterGrid) -> f32 {
+        let area = heightfield.x_step() * heightfield.y_step();
+        let mut removed = 0.0;
+        for &index in self.samples.iter() {
+            removed += water.depths()[index] * area;
+            water.depths_mut()[index] = 0.0;
+        }
+        self.drained += removed;
+        removed
+    }
+
+    /// Total volume of water removed since the drain was created.
+    pub fn drained(&self) -> f32 {
+        self.drained
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Rectangle;
+    use crate::render::Coord;
+    use crate::water::Pipes;
+
+    fn slope(size: usize) -> Heightfield {
+        let base = Rectangle {
+            origin: Coord::new(0.0, 0.0, 0.0),
+            x_size: (size - 1) as f32 * 10.0,
+            y_size: (size - 1) as f32 * 10.0,
+        };
+        let mut heights = Vec::new();
+        for _ in 0..size {
+            for column in 0..size {
+                heights.push(column as f32 * 5.0);
+            }
+        }
+        Heightfield::from_heights(base, size, size, heights)
+    }
+
+    #[test]
+    fn sinks_can_be_parsed() {
+        assert_eq!("edges".parse::<Sink>(), Ok(Sink::Edges));
+        assert_eq!("(10, -5.5)".parse::<Sink>(), Ok(Sink::Point(10.0, -5.5)));
+        assert_eq!("3,4".parse::<Sink>(), Ok(Sink::Point(3.0, 4.0)));
+        assert_eq!("(1,2,3)".parse::<Sink>(), Err(SinkError));
+        assert_eq!("plughole".parse::<Sink>(), Err(SinkError));
+    }
+
+    #[test]
+    fn volume_is_conserved_while_draining() {
+        let heightfield = slope(9);
+        let mut water = WaterGrid::new(&heightfield);
+        water.fill_to(&heightfield, 40.0);
+        let initial = water.volume(&heightfield);
+
+        let mut pipes = Pipes::new(&water);
+        let mut drain = Drain::new(&[Sink::Point(0.0, 40.0)], &heightfield);
+        let mut previous = initial;
+        for _ in 0..200 {
+            pipes.step(&heightfield, &mut water, 0.1);
+            drain.drain(&heightfield, &mut water);
+            let remaining = water.volume(&heightfield);
+            assert!(remaining <= previous + 1e-3);
+            assert!((remaining + drain.drained() - initial).abs() / initial < 1e-3);
+            previous = remaining;
+        }
+        assert!(drain.drained() > 0.0);
+    }
+
+    #[test]
+    fn edges_drain_everything_eventually() {
+        let heightfield = slope(7);
+        let mut water = WaterGrid::new(&heightfield);
+        water.fill_to(&heightfield, 20.0);
+
+        let mut pipes = Pipes::new(&water);
+        let mut drain = Drain::new(&[Sink::Edges], &heightfield);
+        for _ in 0..2000 {
+            pipes.step(&heightfield, &mut water, 0.1);
+            drain.drain(&heightfield, &mut water);
+        }
+        assert_eq!(water.wet_count(), 0);
+    }
+}
@@ -0,0 +1,313 @@
+pub mod drain;
+pub mod pipes;
+pub mod rain;
+pub mod wave;
+
+pub use drain::{Drain, Sink};
+pub use pipes::Pipes;
+pub use rain::Rain;
+pub use wave::{Side, Wave};
+
+use crate::map::Heightfield;
+use crate::render::{Coord, Indices};
+use std::collections::VecDeque;
+
+/// Depth below which a sample is considered dry, to avoid drawing films of water.
+pub const WET_THRESHOLD: f32 = 0.01;
+
+/// Water depth for every sample of a `Heightfield`, using the same row by row layout.
+#[derive(Clone, Debug)]
+pub struct WaterGrid {
+    columns: usize,
+    rows: usize,
+    depths: Vec<f32>,
+}
+
+impl WaterGrid {
+    /// A dry grid matching the dimensions of `heightfield`.
+    pub fn new(heightfield: &Heightfield) -> Self {
+        Self {
+            columns: heightfield.columns(),
+            rows: heightfield.rows(),
+            depths: vec![0.0; heightfield.columns() * heightfield.rows()],
+        }
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn depths(&self) -> &[f32] {
+        &self.depths
+    }
+
+    pub fn depths_mut(&mut self) -> &mut [f32] {
+        &mut self.depths
+    }
+
+    pub fn depth(&self, column: usize, row: usize) -> f32 {
+        self.depths[row * self.columns + column]
+    }
+
+    pub fn is_wet(&self, column: usize, row: usize) -> bool {
+        self.depth(column, row) > WET_THRESHOLD
+    }
+
+    /// Total volume of water on the map, each sample standing for one grid cell of area.
+    pub fn volume(&self, heightfield: &Heightfield) -> f32 {
+        let area = heightfield.x_step() * heightfield.y_step();
+        self.depths.iter().sum::<f32>() * area
+    }
+
+    /// Number of samples holding more than a film of water.
+    pub fn wet_count(&self) -> usize {
+        self.depths
+            .iter()
+            .filter(|&&depth| depth > WET_THRESHOLD)
+            .count()
+    }
+
+    /// Remove all water from the grid.
+    pub fn clear(&mut self) {
+        self.depths.iter_mut().for_each(|depth| *depth = 0.0);
+    }
+
+    /// Fill the grid as if the sea surrounding the map had risen to `level`.
+    ///
+    /// Water enters from the edges of the map, and only reaches samples lying below `level` that
+    /// can be reached from the edges without crossing terrain at or above `level`. Enclosed
+    /// basins stay dry until their rim is submerged.
+    pub fn flood(&mut self, heightfield: &Heightfield, level: f32) {
+        self.clear();
+
+        let mut queue = VecDeque::new();
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let on_edge =
+                    row == 0 || column == 0 || row == self.rows - 1 || column == self.columns - 1;
+                if on_edge {
+                    self.fill(heightfield, level, column, row, &mut queue);
+                }
+            }
+        }
+
+        while let Some((column, row)) = queue.pop_front() {
+            for (next_column, next_row) in self.neighbours(column, row) {
+                self.fill(heightfield, level, next_column, next_row, &mut queue);
+            }
+        }
+    }
+
+    /// Fill every sample below `level` up to `level`, whether or not it is connected to the
+    /// edges of the map.
+    pub fn fill_to(&mut self, heightfield: &Heightfield, level: f32) {
+        for (depth, terrain) in self.depths.iter_mut().zip(heightfield.heights()) {
+            *depth = (level - terrain).max(*depth);
+        }
+    }
+
+    fn fill(
+        &mut self,
+        heightfield: &Heightfield,
+        level: f32,
+        column: usize,
+        row: usize,
+        queue: &mut VecDeque<(usize, usize)>,
+    ) {
+        let index = row * self.columns + column;
+        let terrain = heightfield.height(column, row);
+        if self.depths[index] == 0.0 && terrain < level {
+            self.depths[index] = level - terrain;
+            queue.push_back((column, row));
+        }
+    }
+
+    /// The (up to) four samples sharing an edge with (`column`, `row`).
+    pub fn neighbours(&self, column: usize, row: usize) -> Vec<(usize, usize)> {
+        let mut neighbours = Vec::with_capacity(4);
+        if column > 0 {
+            neighbours.push((column - 1, row));
+        }
+        if column + 1 < self.columns {
+            neighbours.push((column + 1, row));
+        }
+        if row > 0 {
+            neighbours.push((column, row - 1));
+        }
+        if row + 1 < self.rows {
+            neighbours.push((column, row + 1));
+        }
+        neighbours
+    }
+
+    /// Elevation of the water surface at every sample.
+    ///
+    /// Dry samples bordering water take the average surface of their wet neighbours, so that the
+    /// water mesh reaches into the surrounding terrain and the shoreline is drawn where the two
+    /// surfaces intersect.
+    pub fn surface(&self, heightfield: &Heightfield) -> Vec<f32> {
+        let mut surface = Vec::with_capacity(self.depths.len());
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let terrain = heightfield.height(column, row);
+                if self.is_wet(column, row) {
+                    surface.push(terrain + self.depth(column, row));
+                    continue;
+                }
+                let wet_surfaces: Vec<f32> = self
+                    .neighbours(column, row)
+                    .into_iter()
+                    .filter(|&(c, r)| self.is_wet(c, r))
+                    .map(|(c, r)| heightfield.height(c, r) + self.depth(c, r))
+                    .collect();
+                if wet_surfaces.is_empty() {
+                    surface.push(terrain);
+                } else {
+                    surface.push(wet_surfaces.iter().sum::<f32>() / wet_surfaces.len() as f32);
+                }
+            }
+        }
+        surface
+    }
+
+    /// One water vertex per sample, positioned on the water surface and facing the way it does.
+    pub fn vertices(&self, heightfield: &Heightfield) -> Vec<Coord> {
+        let surface = self.surface(heightfield);
+        let normals = heightfield.normals(&surface);
+        let mut vertices = Vec::with_capacity(surface.len());
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let mut vertex = heightfield.position(column, row);
+                *vertex.z_mut() = surface[row * self.columns + column];
+                vertex.normal = normals[row * self.columns + column];
+                vertices.push(vertex.set_as_water());
+            }
+        }
+        vertices
+    }
+
+    /// Two triangles for every grid cell with at least one wet corner, indexing into `vertices`
+    /// offset by `first_index`.
+    pub fn indices(&self, first_index: usize) -> Indices {
+        let mut indices = Indices::new(first_index + self.columns * self.rows);
+        for row in 0..self.rows - 1 {
+            for column in 0..self.columns - 1 {
+                let is_wet = self.is_wet(column, row)
+                    || self.is_wet(column + 1, row)
+                    || self.is_wet(column, row + 1)
+                    || self.is_wet(column + 1, row + 1);
+                if !is_wet {
+                    continue;
+                }
+                let bottom_left = first_index + row * self.columns + column;
+                let bottom_right = bottom_left + 1;
+                let top_left = bottom_left + self.columns;
+                let top_right = top_left + 1;
+
+                for &index in [bottom_left, bottom_right, top_left].iter() {
+                    indices.push(index);
+                }
+                for &index in [bottom_right, top_right, top_left].iter() {
+                    indices.push(index);
+                }
+            }
+        }
+        indices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Rectangle;
+
+    /// A 7x7 heightfield with a ring of hills at height 50 enclosing a basin at height 0,
+    /// surrounded by lowland at height 10.
+    fn ringed_basin() -> Heightfield {
+        #[rustfmt::skip]
+        let heights = vec![
+            10.0, 10.0, 10.0, 10.0, 10.0, 10.0, 10.0,
+            10.0, 50.0, 50.0, 50.0, 50.0, 50.0, 10.0,
+            10.0, 50.0,  0.0,  0.0,  0.0, 50.0, 10.0,
+            10.0, 50.0,  0.0,  0.0,  0.0, 50.0, 10.0,
+            10.0, 50.0,  0.0,  0.0,  0.0, 50.0, 10.0,
+            10.0, 50.0, 50.0, 50.0, 50.0, 50.0, 10.0,
+            10.0, 10.0, 10.0, 10.0, 10.0, 10.0, 10.0,
+        ];
+        let base = Rectangle {
+            origin: Coord::new(0.0, 0.0, 0.0),
+            x_size: 60.0,
+            y_size: 60.0,
+        };
+        Heightfield::from_heights(base, 7, 7, heights)
+    }
+
+    #[test]
+    fn flood_fills_from_edges() {
+        let heightfield = ringed_basin();
+        let mut water = WaterGrid::new(&heightfield);
+
+        water.flood(&heightfield, 20.0);
+        assert!(water.is_wet(0, 0));
+        assert_eq!(water.depth(6, 3), 10.0);
+        assert!(!water.is_wet(1, 1));
+    }
+
+    #[test]
+    fn flood_does_not_jump_over_hills() {
+        let heightfield = ringed_basin();
+        let mut water = WaterGrid::new(&heightfield);
+
+        water.flood(&heightfield, 20.0);
+        assert!(!water.is_wet(3, 3));
+
+        water.flood(&heightfield, 60.0);
+        assert!(water.is_wet(1, 1));
+        assert_eq!(water.depth(3, 3), 60.0);
+    }
+
+    #[test]
+    fn lowering_level_recedes_water() {
+        let heightfield = ringed_basin();
+        let mut water = WaterGrid::new(&heightfield);
+
+        water.flood(&heightfield, 20.0);
+        water.flood(&heightfield, 5.0);
+        assert!(water.depths().iter().all(|&depth| depth == 0.0));
+    }
+
+    #[test]
+    fn mesh_only_covers_wet_cells() {
+        let heightfield = ringed_basin();
+        let mut water = WaterGrid::new(&heightfield);
+
+        assert!(water.indices(0).is_empty());
+
+        water.flood(&heightfield, 20.0);
+        let vertices = water.vertices(&heightfield);
+        let indices = water.indices(0);
+        assert_eq!(vertices.len(), 49);
+        // Only the outermost ring of cells touches the flooded lowland
+        assert_eq!(indices.len(), 20 * 6);
+        assert!(vertices.iter().all(|vertex| vertex.is_water == 1.0));
+        // Shoreline vertices on the hills sit at the flood level
+        assert_eq!(*vertices[8].z(), 20.0);
+    }
+
+    #[test]
+    fn mesh_indices_widen_after_large_terrain() {
+        let heightfield = ringed_basin();
+        let mut water = WaterGrid::new(&heightfield);
+        water.flood(&heightfield, 20.0);
+
+        let first_index = 100_000;
+        let indices = water.indices(first_index);
+        assert!(matches!(indices, Indices::U32(_)));
+        assert_eq!(indices.iter().min(), Some(first_index));
+        assert_eq!(indices.iter().max(), Some(first_index + 48));
+    }
+}
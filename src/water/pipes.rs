@@ -0,0 +1,216 @@
+//! Shallow water flow using the "virtual pipes" model (Mei, Decaudin & Hu, 2007).
+//!
+//! Every sample is connected to its four neighbours by a pipe. Differences in water surface
+//! height accelerate the flow through each pipe, and the resulting outflows move water between
+//! samples without ever taking more than a sample holds.
+
+use super::WaterGrid;
+use crate::map::Heightfield;
+
+pub const GRAVITY: f32 = 9.81;
+
+/// Fraction of the flux through a pipe kept from one step to the next. Values below 1.0 slowly
+/// calm the water instead of letting it slosh back and forth forever.
+pub const FLUX_DAMPING: f32 = 0.995;
+
+/// Fraction of the largest stable timestep actually used for each substep.
+const COURANT_FACTOR: f32 = 0.5;
+
+/// Most substeps taken by a single step. Steps too long to finish within them are cut short
+/// instead of keeping the simulation busy for ever.
+const MAX_SUBSTEPS: usize = 10_000;
+
+const LEFT: usize = 0;
+const RIGHT: usize = 1;
+const DOWN: usize = 2;
+const UP: usize = 3;
+
+/// Outflow from every sample towards its left, right, lower and upper neighbours.
+#[derive(Clone, Debug)]
+pub struct Pipes {
+    columns: usize,
+    rows: usize,
+    flux: Vec<[f32; 4]>,
+}
+
+impl Pipes {
+    pub fn new(water: &WaterGrid) -> Self {
+        Self {
+            columns: water.columns(),
+            rows: water.rows(),
+            flux: vec![[0.0; 4]; water.columns() * water.rows()],
+        }
+    }
+
+    /// Stop all flow, leaving the water where it is.
+    pub fn reset(&mut self) {
+        self.flux.iter_mut().for_each(|flux| *flux = [0.0; 4]);
+    }
+
+    /// Advance the flow by `dt` seconds, split into as many substeps as needed to stay stable,
+    /// up to `MAX_SUBSTEPS`.
+    pub fn step(&mut self, heightfield: &Heightfield, water: &mut WaterGrid, dt: f32) {
+        let mut remaining = dt;
+        for _ in 0..MAX_SUBSTEPS {
+            if remaining <= 0.0 {
+                break;
+            }
+            let substep = stable_timestep(heightfield, water).min(remaining);
+            self.substep(heightfield, water, substep);
+            remaining -= substep;
+        }
+    }
+
+    fn substep(&mut self, heightfield: &Heightfield, water: &mut WaterGrid, dt: f32) {
+        let x_step = heightfield.x_step();
+        let y_step = heightfield.y_step();
+        let area = x_step * y_step;
+        let surface = |index: usize| heightfield.heights()[index] + water.depths()[index];
+
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let index = row * self.columns + column;
+                let neighbours = [
+                    (column > 0, index.wrapping_sub(1), x_step),
+                    (column + 1 < self.columns, index + 1, x_step),
+                    (row > 0, index.wrapping_sub(self.columns), y_step),
+                    (row + 1 < self.rows, index + self.columns, y_step),
+                ];
+
+                let mut outflow = 0.0;
+                for (direction, &(exists, neighbour, length)) in neighbours.iter().enumerate() {
+                    let flux = &mut self.flux[index][direction];
+                    *flux = if exists {
+                        let difference = surface(index) - surface(neighbour);
+                        (*flux * FLUX_DAMPING + dt * area * GRAVITY * difference / length).max(0.0)
+                    } else {
+                        0.0
+                    };
+                    outflow += *flux;
+                }
+
+                // Never let more water leave a sample than it holds
+                let volume = water.depths()[index] * area;
+                if outflow * dt > volume {
+                    let scale = if outflow > 0.0 {
+                        volume / (outflow * dt)
+                    } else {
+                        0.0
+                    };
+                    self.flux[index].iter_mut().for_each(|flux| *flux *= scale);
+                }
+            }
+        }
+
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let index = row * self.columns + column;
+                let mut inflow = 0.0;
+                if column > 0 {
+                    inflow += self.flux[index - 1][RIGHT];
+                }
+                if column + 1 < self.columns {
+                    inflow += self.flux[index + 1][LEFT];
+                }
+                if row > 0 {
+                    inflow += self.flux[index - self.columns][UP];
+                }
+                if row + 1 < self.rows {
+                    inflow += self.flux[index + self.columns][DOWN];
+                }
+                let outflow: f32 = self.flux[index].iter().sum();
+
+                let depth = &mut water.depths_mut()[index];
+                *depth = (*depth + dt * (inflow - outflow) / area).max(0.0);
+            }
+        }
+    }
+
+    /// Flow leaving the sample at (`column`, `row`), as volume per second, in the order left,
+    /// right, down, up.
+    pub fn outflow(&self, column: usize, row: usize) -> [f32; 4] {
+        self.flux[row * self.columns + column]
+    }
+}
+
+/// Largest substep for which a wave cannot cross more than a fraction of a cell.
+fn stable_timestep(heightfield: &Heightfield, water: &WaterGrid) -> f32 {
+    let max_depth = water
+        .depths()
+        .iter()
+        .cloned()
+        .fold(0.0_f32, f32::max)
+        .max(1.0);
+    let wave_speed = (GRAVITY * max_depth).sqrt();
+    COURANT_FACTOR * heightfield.x_step().min(heightfield.y_step()) / wave_speed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Rectangle;
+    use crate::render::Coord;
+
+    fn flat(size: usize, height: f32) -> Heightfield {
+        let base = Rectangle {
+            origin: Coord::new(0.0, 0.0, 0.0),
+            x_size: (size - 1) as f32 * 10.0,
+            y_size: (size - 1) as f32 * 10.0,
+        };
+        Heightfield::from_heights(base, size, size, vec![height; size * size])
+    }
+
+    #[test]
+    fn volume_is_conserved() {
+        let heightfield = flat(9, 0.0);
+        let mut water = WaterGrid::new(&heightfield);
+        water.depths_mut()[4 * 9 + 4] = 50.0;
+        let initial = water.volume(&heightfield);
+
+        let mut pipes = Pipes::new(&water);
+        for _ in 0..100 {
+            pipes.step(&heightfield, &mut water, 0.1);
+        }
+        assert!((water.volume(&heightfield) - initial).abs() / initial < 1e-4);
+    }
+
+    #[test]
+    fn water_spreads_out() {
+        let heightfield = flat(9, 0.0);
+        let mut water = WaterGrid::new(&heightfield);
+        water.depths_mut()[4 * 9 + 4] = 50.0;
+
+        let mut pipes = Pipes::new(&water);
+        pipes.step(&heightfield, &mut water, 1.0);
+        assert!(water.depth(4, 4) < 50.0);
+        assert!(water.is_wet(3, 4) && water.is_wet(5, 4));
+        assert!(water.is_wet(4, 3) && water.is_wet(4, 5));
+    }
+
+    #[test]
+    fn still_water_stays_still() {
+        let heightfield = flat(5, 0.0);
+        let mut water = WaterGrid::new(&heightfield);
+        water
+            .depths_mut()
+            .iter_mut()
+            .for_each(|depth| *depth = 10.0);
+
+        let mut pipes = Pipes::new(&water);
+        pipes.step(&heightfield, &mut water, 1.0);
+        assert!(water.depths().iter().all(|&depth| depth == 10.0));
+        assert_eq!(pipes.outflow(2, 2), [0.0; 4]);
+    }
+
+    #[test]
+    fn long_steps_finish() {
+        let heightfield = flat(5, 0.0);
+        let mut water = WaterGrid::new(&heightfield);
+        water.depths_mut()[2 * 5 + 2] = 50.0;
+        let initial = water.volume(&heightfield);
+
+        let mut pipes = Pipes::new(&water);
+        pipes.step(&heightfield, &mut water, 1e30);
+        assert!((water.volume(&heightfield) - initial).abs() / initial < 1e-4);
+    }
+}
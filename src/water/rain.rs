@@ -0,0 +1,107 @@
+use super::WaterGrid;
+use crate::random::Random;
+
+/// Droplets falling over the whole map per second, unless specified otherwise.
+pub const DEFAULT_RATE: f32 = 2000.0;
+
+/// Depth of water added to a sample by a single droplet.
+pub const DROPLET_DEPTH: f32 = 1.0;
+
+/// Most droplets let fall by a single step, however long it is.
+const MAX_DROPLETS: f32 = 1_000_000.0;
+
+/// Droplets falling at random positions across the map. Where they run off to is left to the
+/// flow simulation.
+#[derive(Clone, Debug)]
+pub struct Rain {
+    /// Droplets per second, over the whole map.
+    pub rate: f32,
+    random: Random,
+    // Fraction of a droplet left over from the previous step
+    pending: f32,
+}
+
+impl Rain {
+    pub fn new(rate: f32, seed: u64) -> Self {
+        Self {
+            rate,
+            random: Random::new(seed),
+            pending: 0.0,
+        }
+    }
+
+    /// Let `dt` seconds worth of droplets fall onto `water`, returning how many fell.
+    pub fn fall(&mut self, water: &mut WaterGrid, dt: f32) -> usize {
+        self.pending = (self.pending + self.rate.max(0.0) * dt).min(MAX_DROPLETS);
+        let droplets = self.pending.floor() as usize;
+        self.pending -= droplets as f32;
+
+        let samples = water.depths().len();
+        for _ in 0..droplets {
+            let index = self.random.below(samples);
+            water.depths_mut()[index] += DROPLET_DEPTH;
+        }
+        droplets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::{Heightfield, Rectangle};
+    use crate::render::Coord;
+    use crate::water::Pipes;
+
+    /// A bowl sloping down towards its centre sample.
+    fn bowl(size: usize) -> Heightfield {
+        let base = Rectangle {
+            origin: Coord::new(0.0, 0.0, 0.0),
+            x_size: (size - 1) as f32 * 10.0,
+            y_size: (size - 1) as f32 * 10.0,
+        };
+        let centre = (size / 2) as f32;
+        let mut heights = Vec::new();
+        for row in 0..size {
+            for column in 0..size {
+                let distance = (column as f32 - centre).hypot(row as f32 - centre);
+                heights.push(distance * 10.0);
+            }
+        }
+        Heightfield::from_heights(base, size, size, heights)
+    }
+
+    #[test]
+    fn droplets_accumulate() {
+        let heightfield = bowl(9);
+        let mut water = WaterGrid::new(&heightfield);
+        let mut rain = Rain::new(100.0, 7);
+
+        let mut droplets = 0;
+        for _ in 0..30 {
+            droplets += rain.fall(&mut water, 0.1);
+        }
+        assert_eq!(droplets, 300);
+        let expected = 300.0 * DROPLET_DEPTH * heightfield.x_step() * heightfield.y_step();
+        assert!((water.volume(&heightfield) - expected).abs() < 1e-2);
+        assert!(water.wet_count() > 0);
+    }
+
+    #[test]
+    fn rain_pools_in_basins() {
+        let heightfield = bowl(9);
+        let mut water = WaterGrid::new(&heightfield);
+        let mut pipes = Pipes::new(&water);
+        let mut rain = Rain::new(50.0, 3);
+
+        for _ in 0..100 {
+            rain.fall(&mut water, 0.1);
+            pipes.step(&heightfield, &mut water, 0.1);
+        }
+        for _ in 0..500 {
+            pipes.step(&heightfield, &mut water, 0.1);
+        }
+        let deepest = water.depths().iter().cloned().fold(0.0, f32::max);
+        assert_eq!(water.depth(4, 4), deepest);
+        assert!(!water.is_wet(0, 0));
+    }
+}
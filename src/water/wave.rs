@@ -0,0 +1,149 @@
+use super::WaterGrid;
+use crate::map::Heightfield;
+use std::fmt;
+use std::str::FromStr;
+
+/// Fraction of the map covered by the wall of water when a wave starts.
+pub const WALL_WIDTH: f32 = 0.1;
+
+/// Edge of the map, with north pointing along the y axis.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Side {
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Side {
+    /// How many samples (`column`, `row`) lies away from this edge of a `columns` by `rows` grid.
+    pub fn distance(&self, column: usize, row: usize, columns: usize, rows: usize) -> usize {
+        match self {
+            Side::North => rows - 1 - row,
+            Side::East => columns - 1 - column,
+            Side::South => row,
+            Side::West => column,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SideError;
+
+impl fmt::Display for SideError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Side must be one of north, east, south or west")
+    }
+}
+
+impl std::error::Error for SideError {}
+
+impl FromStr for Side {
+    type Err = SideError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "north" | "n" => Ok(Side::North),
+            "east" | "e" => Ok(Side::East),
+            "south" | "s" => Ok(Side::South),
+            "west" | "w" => Ok(Side::West),
+            _ => Err(SideError),
+        }
+    }
+}
+
+/// A wall of water rolling in from one side of the map, fed for as long as it runs so that the
+/// sea behind it stays at `level`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Wave {
+    pub side: Side,
+    pub level: f32,
+}
+
+impl Wave {
+    pub fn new(side: Side, level: f32) -> Self {
+        Self { side, level }
+    }
+
+    /// Drop the initial wall of water along `side`.
+    pub fn start(&self, heightfield: &Heightfield, water: &mut WaterGrid) {
+        let across = match self.side {
+            Side::North | Side::South => water.rows(),
+            Side::East | Side::West => water.columns(),
+        };
+        let width = ((across as f32 * WALL_WIDTH).ceil() as usize).max(1);
+        self.raise(heightfield, water, width);
+    }
+
+    /// Top up the samples along `side` so that water keeps pouring in behind the wave.
+    pub fn feed(&self, heightfield: &Heightfield, water: &mut WaterGrid) {
+        self.raise(heightfield, water, 1);
+    }
+
+    fn raise(&self, heightfield: &Heightfield, water: &mut WaterGrid, width: usize) {
+        let (columns, rows) = (water.columns(), water.rows());
+        for row in 0..rows {
+            for column in 0..columns {
+                if self.side.distance(column, row, columns, rows) >= width {
+                    continue;
+                }
+                let index = row * columns + column;
+                let depth = self.level - heightfield.heights()[index];
+                let current = &mut water.depths_mut()[index];
+                *current = current.max(depth);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Rectangle;
+    use crate::render::Coord;
+    use crate::water::pipes::Pipes;
+
+    fn flat(columns: usize, rows: usize) -> Heightfield {
+        let base = Rectangle {
+            origin: Coord::new(0.0, 0.0, 0.0),
+            x_size: (columns - 1) as f32 * 10.0,
+            y_size: (rows - 1) as f32 * 10.0,
+        };
+        Heightfield::from_heights(base, columns, rows, vec![0.0; columns * rows])
+    }
+
+    #[test]
+    fn sides_can_be_parsed() {
+        assert_eq!("north".parse::<Side>(), Ok(Side::North));
+        assert_eq!("W".parse::<Side>(), Ok(Side::West));
+        assert_eq!("up".parse::<Side>(), Err(SideError));
+    }
+
+    #[test]
+    fn wall_starts_on_chosen_side() {
+        let heightfield = flat(20, 10);
+        let mut water = WaterGrid::new(&heightfield);
+
+        Wave::new(Side::East, 30.0).start(&heightfield, &mut water);
+        assert_eq!(water.depth(19, 5), 30.0);
+        assert_eq!(water.depth(18, 0), 30.0);
+        assert!(!water.is_wet(17, 5));
+        assert!(!water.is_wet(0, 5));
+    }
+
+    #[test]
+    fn wave_crosses_the_map() {
+        let heightfield = flat(20, 10);
+        let mut water = WaterGrid::new(&heightfield);
+        let mut pipes = Pipes::new(&water);
+        let wave = Wave::new(Side::South, 30.0);
+
+        wave.start(&heightfield, &mut water);
+        for _ in 0..200 {
+            wave.feed(&heightfield, &mut water);
+            pipes.step(&heightfield, &mut water, 0.1);
+        }
+        assert!(water.is_wet(10, 9));
+        assert!(water.depth(10, 0) > water.depth(10, 9) - 1.0);
+    }
+}
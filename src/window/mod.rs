@@ -0,0 +1,406 @@
+use crate::map::{self, Heightfield, Units};
+use crate::render::lighting::{SHININESS, WATER_SPECULAR};
+use crate::render::ramp::RAMP_NAMES;
+use crate::render::{self, CameraMatrix, Coord, Indices, Lighting, Ramp};
+use crate::simulation::{Scenario, Simulation, SimulationBuilder};
+use crate::water::WaterGrid;
+use glium::glutin::event::{ElementState, VirtualKeyCode};
+use glium::texture::Texture1d;
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerWrapFunction};
+use glium::{self, glutin, Program, Surface, VertexBuffer};
+use std::error::Error;
+
+/// Simulated time between two frames, in seconds.
+const FRAME_TIME: f32 = 1.0 / 60.0;
+
+/// Colours in the texture the fragment shader looks the colour ramp up in.
+const RAMP_SAMPLES: usize = 256;
+
+#[derive(Debug)]
+pub struct Config {
+    pub event_loop: glutin::event_loop::EventLoop<()>,
+    pub display: glium::Display,
+    pub scale: usize,
+    pub camera_matrix: CameraMatrix,
+    pub program: Program,
+    pub vertices: Vec<Coord>,
+    pub indices: Indices,
+    pub simulation: Simulation,
+    /// Lowest and highest elevations of the map, tinted with the bottom and top of the ramp.
+    pub elevation_range: (f32, f32),
+    pub units: Units,
+    pub lighting: Lighting,
+    pub ramp: Ramp,
+}
+
+impl Config {
+    pub fn new(mut args: std::env::Args) -> Result<Self, Box<dyn Error>> {
+        args.next(); // Skip executable name
+
+        let filename = match args.next() {
+            Some(arg) => arg,
+            None => {
+                return Err(
+                    "mod1 must be run with at least one argument, a mod1 file or heightmap".into(),
+                )
+            }
+        };
+
+        // Everything up to the first option describes the scenario
+        let args: Vec<String> = args.collect();
+        let options_start = args
+            .iter()
+            .position(|arg| arg.starts_with("--"))
+            .unwrap_or(args.len());
+        let (scenario, sinks) = Scenario::from_args(&args[..options_start])?;
+
+        let mut lighting = Lighting::default();
+        let mut ramp = Ramp::default();
+        let mut options = args[options_start..].iter();
+        while let Some(option) = options.next() {
+            let value = match options.next() {
+                Some(value) => value,
+                None => return Err("Every option must be followed by a value".into()),
+            };
+            match option.as_str() {
+                "--sun" => lighting.sun = value.parse()?,
+                "--ambient" => match value.parse::<f32>() {
+                    Ok(value) if (0.0..=1.0).contains(&value) => lighting.ambient = value,
+                    _ => return Err("Ambient light must be a number from 0 to 1".into()),
+                },
+                "--ramp" => ramp = value.parse()?,
+                _ => return Err("Options are --sun, --ambient and --ramp".into()),
+            }
+        }
+
+        // Read the map before opening a window, so that problems with it are reported straight away
+        let map = match map::io::Format::of(&filename) {
+            map::io::Format::Heightmap => {
+                map::Map::from_heightmap(&filename, &map::HeightmapOptions::default())?
+            }
+            map::io::Format::Asc => map::Map::from_heightfield(
+                map::io::open_asc(&filename)?,
+                &map::Normalisation::default(),
+                None,
+            ),
+            map::io::Format::Xyz => {
+                map::Map::from_map_file(map::io::open_xyz(&filename)?, map::DEFAULT_RESOLUTION)
+            }
+            map::io::Format::Mod1 => map::Map::new_from_file(&filename)?,
+        };
+        for warning in map.warnings.iter() {
+            eprintln!("Warning: {}", warning);
+        }
+
+        let event_loop = glutin::event_loop::EventLoop::new();
+        let wb = glutin::window::WindowBuilder::new();
+        let cb = glutin::ContextBuilder::new().with_depth_buffer(24);
+        let display = glium::Display::new(wb, cb, &event_loop).unwrap();
+
+        let camera_matrix = map.camera();
+
+        let water_level = scenario.initial_water_level(map.elevation_max);
+        let elevation_range = map.heightfield.range();
+        // Sinks given on the command line are added to those declared in the map file, and
+        // water drains off the edges of the map if neither declares any
+        let mut sinks: Vec<_> = sinks
+            .into_iter()
+            .map(|sink| map.units.sink_to_world(sink))
+            .collect();
+        sinks.extend(map.sinks.iter());
+        let seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or_default();
+        let simulation = SimulationBuilder::new(map.heightfield)
+            .scenario(scenario)
+            .water_level(water_level)
+            .sinks(&sinks)
+            .seed(seed)
+            .build();
+
+        let vertex_shader_src = create_vertex_shader();
+        let fragment_shader_src = create_fragment_shader();
+        let program =
+            Program::from_source(&display, &vertex_shader_src, &fragment_shader_src, None).unwrap();
+        Ok(Self {
+            event_loop,
+            display,
+            scale: map.scale,
+            camera_matrix,
+            program,
+            vertices: map.vertices,
+            indices: map.indices,
+            simulation,
+            elevation_range,
+            units: map.units,
+            lighting,
+            ramp,
+        })
+    }
+}
+
+fn create_vertex_shader() -> String {
+    String::from(
+        r#"
+        #version 150
+
+        in vec3 position;
+        in vec3 normal;
+        in float is_water; // Can't use bool in GLSL
+        out float elevation; // pass position on to fragment shader
+        out float v_water;
+        out vec3 v_position;
+        out vec3 v_normal;
+
+        uniform mat4 perspective;
+        uniform mat4 model;
+        uniform mat4 view;
+
+        void main() {
+            elevation = position.z;
+            v_water = is_water;
+            v_position = position;
+            v_normal = normal;
+
+            mat4 modelview = view * model;
+            gl_Position = perspective * modelview * vec4(position, 1.0);
+        }
+        "#,
+    )
+}
+
+fn create_fragment_shader() -> String {
+    String::from(
+        r#"
+            #version 150
+
+            in float elevation;
+            in float v_water;
+            in vec3 v_position;
+            in vec3 v_normal;
+            out vec4 color;
+
+            uniform sampler1D ramp;
+            uniform float lowest;
+            uniform float highest;
+            uniform vec3 sun; // Unit vector towards the sun
+            uniform float ambient;
+            uniform vec3 eye;
+            uniform float specular;
+            uniform float shininess;
+
+            // Same lighting as render::Lighting::shade
+            void main() {
+                vec3 normal = normalize(v_normal);
+                float diffuse = max(dot(normal, sun), 0.0);
+                float direct = 1.0 - ambient;
+                float light = ambient + direct * diffuse;
+
+                if (v_water > 0.5) {
+                    float shine = 0.0;
+                    if (diffuse > 0.0) {
+                        vec3 halfway = normalize(sun + normalize(eye - v_position));
+                        shine = specular * direct * pow(max(dot(normal, halfway), 0.0), shininess);
+                    }
+                    color = vec4(vec3(0.0, 0.0, 1.0) * light + vec3(shine), 1.0);
+                } else {
+                    // Flat maps are tinted with the bottom of the ramp
+                    float fraction = 0.0;
+                    if (highest > lowest) {
+                        fraction = clamp((elevation - lowest) / (highest - lowest), 0.0, 1.0);
+                    }
+                    // Look up between the centres of the first and last colours
+                    float size = float(textureSize(ramp, 0));
+                    vec3 tint = texture(ramp, (0.5 + fraction * (size - 1.0)) / size).rgb;
+                    color = vec4(tint * light, 1.0);
+                }
+            }
+        "#,
+    )
+}
+
+fn add_water(
+    vertices: &mut Vec<Coord>,
+    indices: &mut Indices,
+    heightfield: &Heightfield,
+    water: &WaterGrid,
+) {
+    let first_water_index = vertices.len();
+    vertices.extend(water.vertices(heightfield));
+    indices.append(&water.indices(first_water_index));
+}
+
+fn remove_water(
+    vertices: &mut Vec<Coord>,
+    indices: &mut Indices,
+    terrain_vertices: usize,
+    terrain_indices: usize,
+) {
+    vertices.truncate(terrain_vertices);
+    indices.truncate(terrain_indices);
+}
+
+pub fn run(config: Config) -> Result<(), &'static str> {
+    let Config {
+        event_loop,
+        display,
+        scale,
+        mut camera_matrix,
+        program,
+        mut vertices,
+        mut indices,
+        mut simulation,
+        elevation_range: (lowest, highest),
+        units,
+        lighting,
+        ramp,
+    } = config;
+
+    // The ramp chosen first, followed by the named ones to cycle through with C
+    let mut ramps = vec![ramp];
+    for name in RAMP_NAMES.iter() {
+        let named: Ramp = name.parse().expect("named ramps always parse");
+        if !ramps.contains(&named) {
+            ramps.push(named);
+        }
+    }
+    let mut ramp_index = 0;
+    let mut ramp_texture = Texture1d::new(&display, ramps[0].samples(RAMP_SAMPLES))
+        .map_err(|_| "Unable to create colour ramp texture")?;
+
+    let terrain_vertices = vertices.len();
+    let terrain_indices = indices.len();
+    let mut water_level = simulation.water_level();
+    let mut frame: u64 = 0;
+
+    let params = glium::DrawParameters {
+        depth: glium::Depth {
+            test: glium::draw_parameters::DepthTest::IfLess,
+            write: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    event_loop.run(move |ev, _, control_flow| {
+        let mut target = display.draw();
+        target.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), 1.0);
+
+        simulation.set_water_level(water_level);
+        let report = simulation.step(FRAME_TIME);
+        frame += 1;
+        if simulation.scenario() == Scenario::Drain && frame % 60 == 0 {
+            println!(
+                "Drained {}, remaining water volume: {}",
+                units.volume_to_map(report.drained),
+                units.volume_to_map(report.volume)
+            );
+        }
+        add_water(
+            &mut vertices,
+            &mut indices,
+            simulation.heightfield(),
+            simulation.water(),
+        );
+
+        let vertex_buffer = match VertexBuffer::new(&display, &vertices) {
+            Ok(vertex_buffer) => vertex_buffer,
+            Err(_) => {
+                eprintln!("Application error: Unable to create vertex buffer");
+                *control_flow = glutin::event_loop::ControlFlow::Exit;
+                return;
+            }
+        };
+        let indices_buffer = match indices.index_buffer(&display) {
+            Ok(indices_buffer) => indices_buffer,
+            Err(_) => {
+                eprintln!("Application error: Unable to create index buffer");
+                *control_flow = glutin::event_loop::ControlFlow::Exit;
+                return;
+            }
+        };
+
+        let (width, height) = target.get_dimensions();
+        let perspective = CameraMatrix::perspective(width, height);
+        let model = camera_matrix.model();
+
+        target
+            .draw(
+                &vertex_buffer,
+                &indices_buffer,
+                &program,
+                &glium::uniform! {
+                    model: model,
+                    perspective: perspective,
+                    view: camera_matrix.mat4(),
+                    ramp: ramp_texture
+                        .sampled()
+                        .magnify_filter(MagnifySamplerFilter::Linear)
+                        .minify_filter(MinifySamplerFilter::Linear)
+                        .wrap_function(SamplerWrapFunction::Clamp),
+                    lowest: lowest,
+                    highest: highest,
+                    sun: lighting.sun.direction(),
+                    ambient: lighting.ambient,
+                    eye: camera_matrix.eye(),
+                    specular: WATER_SPECULAR,
+                    shininess: SHININESS,
+                },
+                &params,
+            )
+            .unwrap();
+
+        target.finish().unwrap();
+
+        remove_water(
+            &mut vertices,
+            &mut indices,
+            terrain_vertices,
+            terrain_indices,
+        );
+
+        let next_frame_time =
+            std::time::Instant::now() + std::time::Duration::from_nanos(16_666_667);
+        *control_flow = glutin::event_loop::ControlFlow::WaitUntil(next_frame_time);
+
+        if let glutin::event::Event::WindowEvent { event, .. } = ev {
+            match event {
+                glutin::event::WindowEvent::KeyboardInput {
+                    device_id: _,
+                    input,
+                    is_synthetic: _,
+                } => {
+                    if input.state == ElementState::Pressed
+                        && input.virtual_keycode == Some(VirtualKeyCode::C)
+                    {
+                        ramp_index = (ramp_index + 1) % ramps.len();
+                        match Texture1d::new(&display, ramps[ramp_index].samples(RAMP_SAMPLES)) {
+                            Ok(texture) => ramp_texture = texture,
+                            Err(_) => {
+                                eprintln!(
+                                    "Application error: Unable to create colour ramp texture"
+                                );
+                                *control_flow = glutin::event_loop::ControlFlow::Exit;
+                                return;
+                            }
+                        }
+                    }
+                    render::key_event(input, &mut camera_matrix, &mut water_level, scale);
+                }
+                glutin::event::WindowEvent::MouseWheel {
+                    device_id: _,
+                    delta,
+                    phase: _,
+                    ..
+                } => {
+                    render::mouse_scroll(&mut camera_matrix.zoom, delta, scale);
+                }
+                glutin::event::WindowEvent::CloseRequested => {
+                    *control_flow = glutin::event_loop::ControlFlow::Exit;
+                }
+                _ => (),
+            }
+        }
+    })
+}